@@ -0,0 +1,23 @@
+use thepipelinetool_core::{prelude::*, tpt};
+
+async fn fetch_number(_: ()) -> u8 {
+    async { 41 }.await
+}
+
+async fn add_one(n: u8) -> u8 {
+    n + 1
+}
+
+fn print_number(n: u8) {
+    println!("{n}");
+}
+
+#[tpt::main]
+fn main() {
+    let opts = &TaskOptions::default();
+
+    // async functions are driven on the runtime created by 'parse_cli'
+    let a = add_task(fetch_number, (), opts);
+    let b = add_task_with_ref(add_one, &a, opts);
+    let _ = add_task_with_ref(print_number, &b, opts);
+}
@@ -0,0 +1,29 @@
+use thepipelinetool_core::{prelude::*, tpt};
+
+fn flaky(_: ()) -> Result<(), TaskFailure> {
+    Err(TaskFailure::new("unavailable", "not today"))
+}
+
+fn alert(context: CallbackContext) {
+    println!(
+        "{} failed after {} attempts in run {}",
+        context.task_result.name, context.task_result.attempt, context.run_id
+    );
+}
+
+#[tpt::main]
+fn main() {
+    add_task(
+        flaky,
+        (),
+        &TaskOptions {
+            max_attempts: 2,
+            on_retry: vec![callback_with_args(
+                bash_operator,
+                json!(["echo", "retrying {{ task_result.name }}"]),
+            )],
+            on_failure: vec![callback(alert)],
+            ..Default::default()
+        },
+    );
+}
@@ -0,0 +1,29 @@
+use thepipelinetool_core::{prelude::*, tpt};
+
+// run with TPT_CONNECTION_warehouse='{"type": "postgres", "host": "localhost", "port": 5432,
+// "login": "tpt", "password": "..."}'
+fn connect(_: (), ctx: TaskContext) -> String {
+    let warehouse = ctx.get_connection("warehouse").unwrap();
+    format!(
+        "{}://{}@{}:{}",
+        warehouse.conn_type,
+        warehouse.login,
+        warehouse.host,
+        warehouse.port.unwrap_or(5432)
+    )
+}
+
+#[tpt::main]
+fn main() {
+    let opts = &TaskOptions::default();
+
+    let _ = add_task(connect, (), opts).with_connections(&["warehouse"]);
+
+    // scripts receive the same connection as TPT_CONN_<ID>_<FIELD>
+    let _ = add_task(
+        bash_operator,
+        json!(["bash", "-c", "echo connecting to $TPT_CONN_WAREHOUSE_HOST"]),
+        opts,
+    )
+    .with_connections(&["warehouse"]);
+}
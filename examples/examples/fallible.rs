@@ -0,0 +1,20 @@
+use thepipelinetool_core::{prelude::*, tpt};
+
+fn parse_number(_: ()) -> Result<u8, TaskFailure> {
+    "300"
+        .parse::<u8>()
+        .map_err(|e| TaskFailure::new("parse_error", e).with_data(json!({ "input": "300" })))
+}
+
+fn print_number(n: u8) {
+    println!("{n}");
+}
+
+#[tpt::main]
+fn main() {
+    let opts = &TaskOptions::default();
+
+    // the error is reported on the task result instead of a panic
+    let n = add_task(parse_number, (), opts);
+    let _ = add_task_with_ref(print_number, &n, opts);
+}
@@ -0,0 +1,24 @@
+use thepipelinetool_core::{prelude::*, tpt};
+
+#[tpt::task(retries = 2, retry_delay = "1s", timeout = "5m", name = "produce")]
+fn produce_data(_: ()) -> Result<u8, String> {
+    Ok(42)
+}
+
+// `Result` and async tasks produce a `TaskRef` of their output
+#[tpt::task]
+async fn double_data(data: u8) -> Result<u8, String> {
+    Ok(data * 2)
+}
+
+#[tpt::task(trigger_rule = "all_success")]
+fn print_data(data: u8) {
+    println!("{data}");
+}
+
+#[tpt::main]
+fn main() {
+    let data = produce_data_task(());
+    let doubled: TaskRef<u8> = double_data_task_with_ref(&data);
+    let _ = print_data_task_with_ref(&doubled);
+}
@@ -0,0 +1,26 @@
+use thepipelinetool_core::{prelude::*, tpt};
+
+fn count_rows(rows: Vec<u32>, ctx: TaskContext) -> usize {
+    ctx.log("info", "counting rows", json!({ "rows": rows.len() }));
+    ctx.metric("rows_processed", rows.len() as f64);
+
+    println!(
+        "run {} of '{}' scheduled for {:?}, attempt {}/{}",
+        ctx.run_id, ctx.pipeline_name, ctx.scheduled_date, ctx.attempt, ctx.max_attempts
+    );
+    rows.len()
+}
+
+#[tpt::main]
+fn main() {
+    let opts = &TaskOptions::default();
+
+    let _ = add_task(count_rows, vec![1, 2, 3], opts);
+
+    // the same metadata is exported to scripts as TPT_* environment variables
+    let _ = add_task(
+        bash_operator,
+        json!(["bash", "-c", "echo task $TPT_TASK_KEY in run $TPT_RUN_ID"]),
+        opts,
+    );
+}
@@ -0,0 +1,33 @@
+use thepipelinetool_core::{prelude::*, tpt};
+
+#[derive(Serialize, Deserialize, TaskOutput)]
+struct User {
+    name: String,
+    #[serde(rename = "user_age")]
+    age: u8,
+}
+
+fn get_user(_: ()) -> User {
+    User {
+        name: "world".into(),
+        age: 42,
+    }
+}
+
+fn greet(name: String) {
+    println!("hello {name}");
+}
+
+fn check_age(age: u8) {
+    assert!(age > 18);
+}
+
+#[tpt::main]
+fn main() {
+    let opts = &TaskOptions::default();
+    let user = add_task(get_user, (), opts);
+
+    // accessors are generated by #[derive(TaskOutput)], so 'user.age()' is a 'TaskRef<u8>'
+    let _ = add_task_with_ref(greet, &user.name(), opts);
+    let _ = add_task_with_ref(check_age, &user.age(), opts);
+}
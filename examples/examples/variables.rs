@@ -0,0 +1,28 @@
+use anyhow::{Context, Result};
+use thepipelinetool_core::{prelude::*, tpt};
+
+// only reads rows newer than the watermark left by the previous run
+fn read_new_rows(_: (), ctx: TaskContext) -> Result<Vec<u32>> {
+    let watermark: u32 = ctx.get_variable("watermark")?.unwrap_or(0);
+    let rows: Vec<u32> = (watermark + 1..=watermark + 3).collect();
+
+    ctx.set_variable("watermark", rows.last().unwrap());
+    ctx.set_run_variable("rows_read", rows.len());
+    Ok(rows)
+}
+
+fn report(rows: Vec<u32>, ctx: TaskContext) -> Result<()> {
+    let rows_read: usize = ctx
+        .get_run_variable("rows_read")?
+        .context("rows_read was not set")?;
+    println!("read {rows_read} rows: {rows:?}");
+    Ok(())
+}
+
+#[tpt::main]
+fn main() {
+    let opts = &TaskOptions::default();
+
+    let rows = add_task(read_new_rows, (), opts);
+    let _ = add_task_with_ref(report, &rows, opts);
+}
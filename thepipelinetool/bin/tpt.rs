@@ -7,8 +7,15 @@ use std::{
 use anyhow::Result;
 use clap::Arg;
 use thepipelinetool::{
-    commands::create_commands, process_subcommands, read_from_executable::read_from_executable,
-    read_from_yaml::read_from_yaml, source_type::SourceType,
+    commands::create_commands,
+    display_diff::display_diff,
+    process_subcommands,
+    read_from_endpoint::read_from_endpoint,
+    read_from_executable::read_from_executable,
+    read_from_yaml::read_from_yaml,
+    source_type::SourceType,
+    validate::validate,
+    variables::{connections, variables},
 };
 use thepipelinetool_core::dev::{
     assert::assert_operator, params::params_operator, print::print_operator,
@@ -22,10 +29,35 @@ fn main() -> Result<()> {
     let matches = command.get_matches();
     let pipeline_source = matches.get_one::<String>("pipeline_source");
 
-    let source_type = SourceType::from_source(pipeline_source);
+    let source_type = SourceType::from_source(pipeline_source.map(String::as_str));
 
     let subcommand_name = matches.subcommand_name().unwrap();
 
+    // diff reads both of its pipelines from its own arguments
+    if subcommand_name == "diff" {
+        let matches = matches.subcommand_matches("diff").unwrap();
+        return display_diff(
+            matches.get_one::<String>("old").unwrap(),
+            matches.get_one::<String>("new").unwrap(),
+            matches.get_flag("json"),
+        );
+    }
+
+    // variables and connections talk to a server instead of loading a pipeline
+    if subcommand_name == "variables" {
+        return variables(matches.subcommand_matches("variables").unwrap());
+    }
+    if subcommand_name == "connections" {
+        return connections(matches.subcommand_matches("connections").unwrap());
+    }
+
+    // validate checks the source before loading it, since loading an invalid pipeline panics
+    if subcommand_name == "validate" {
+        return validate(&source_type, pipeline_source.map_or("", |s| s.as_str()));
+    }
+
+    let mut endpoint_options = PipelineOptions::default();
+
     match source_type {
         SourceType::Exe => {
             if args.len() > 4 && args[2..4] == ["run", "function"] {
@@ -56,6 +88,9 @@ fn main() -> Result<()> {
         SourceType::Raw => {
             read_from_yaml(serde_json::from_str(pipeline_source.unwrap())?);
         }
+        SourceType::Endpoint => {
+            endpoint_options = read_from_endpoint(pipeline_source.unwrap())?;
+        }
         SourceType::None => {
             // try parse operator
             let operator = &serde_json::from_value::<Operator>(json!(args[4])).ok();
@@ -109,6 +144,7 @@ fn main() -> Result<()> {
         SourceType::Exe => PipelineOptions::default(), // TODO read options from exe?
         SourceType::Yaml => serde_yaml::from_reader(File::open(pipeline_source.unwrap())?)?,
         SourceType::Raw => serde_yaml::from_str(pipeline_source.unwrap())?,
+        SourceType::Endpoint => endpoint_options,
         SourceType::None => PipelineOptions::default(),
     };
 
@@ -116,6 +152,7 @@ fn main() -> Result<()> {
         SourceType::Exe => pipeline_source.unwrap(),
         SourceType::Yaml => "",
         SourceType::Raw => "",
+        SourceType::Endpoint => "",
         SourceType::None => "",
     };
 
@@ -13,6 +13,7 @@ pub fn create_commands() -> CliCommand {
                 .subcommand(CliCommand::new("options").about("Displays options as JSON")),
         )
         .subcommand(CliCommand::new("check").about("Check for circular depencencies"))
+        .subcommand(CliCommand::new("validate").about("Check pipeline for all known problems"))
         .subcommand(
             CliCommand::new("graph")
                 .about("Displays graph")
@@ -52,7 +53,8 @@ pub fn create_commands() -> CliCommand {
                             .value_parser(value_parser!(String))
                             .default_value("")
                             .default_missing_value(""),
-                        ),
+                        )
+                        .arg(arg!(--notify "Sends the notifications configured for the pipeline")),
                 )
                 .subcommand(
                     CliCommand::new("function")
@@ -78,6 +80,74 @@ pub fn create_commands() -> CliCommand {
                 )
                 .subcommand_required(true),
         )
+        .subcommand(
+            CliCommand::new("diff")
+                .about("Displays changes between two pipelines")
+                .arg_required_else_help(true)
+                .arg(arg!(<old> "Old pipeline (executable, YAML file or server endpoint)"))
+                .arg(arg!(<new> "New pipeline (executable, YAML file or server endpoint)"))
+                .arg(arg!(--json "Displays changes as JSON")),
+        )
+        .subcommand(
+            CliCommand::new("variables")
+                .about("Manage variables stored on a server")
+                .arg_required_else_help(true)
+                .subcommand(
+                    CliCommand::new("list")
+                        .about("Displays all variables as JSON")
+                        .arg(arg!(<endpoint> "Variables endpoint")),
+                )
+                .subcommand(
+                    CliCommand::new("get")
+                        .about("Displays a variable as JSON")
+                        .arg(arg!(<endpoint> "Variables endpoint"))
+                        .arg(arg!(<key> "Variable key")),
+                )
+                .subcommand(
+                    CliCommand::new("set")
+                        .about("Sets a variable")
+                        .arg(arg!(<endpoint> "Variables endpoint"))
+                        .arg(arg!(<key> "Variable key"))
+                        .arg(arg!(<value> "Variable value as JSON")),
+                )
+                .subcommand(
+                    CliCommand::new("delete")
+                        .about("Deletes a variable")
+                        .arg(arg!(<endpoint> "Variables endpoint"))
+                        .arg(arg!(<key> "Variable key")),
+                )
+                .subcommand_required(true),
+        )
+        .subcommand(
+            CliCommand::new("connections")
+                .about("Manage connections stored on a server")
+                .arg_required_else_help(true)
+                .subcommand(
+                    CliCommand::new("list")
+                        .about("Displays all connection ids")
+                        .arg(arg!(<endpoint> "Connections endpoint")),
+                )
+                .subcommand(
+                    CliCommand::new("get")
+                        .about("Displays a connection without its password")
+                        .arg(arg!(<endpoint> "Connections endpoint"))
+                        .arg(arg!(<key> "Connection id")),
+                )
+                .subcommand(
+                    CliCommand::new("set")
+                        .about("Sets a connection")
+                        .arg(arg!(<endpoint> "Connections endpoint"))
+                        .arg(arg!(<key> "Connection id"))
+                        .arg(arg!(<value> "Connection as JSON with type, host, port, login, password and extras")),
+                )
+                .subcommand(
+                    CliCommand::new("delete")
+                        .about("Deletes a connection")
+                        .arg(arg!(<endpoint> "Connections endpoint"))
+                        .arg(arg!(<key> "Connection id")),
+                )
+                .subcommand_required(true),
+        )
         .subcommand(
             CliCommand::new("upload")
                 .about("Upload pipeline")
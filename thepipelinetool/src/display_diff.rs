@@ -0,0 +1,99 @@
+use std::fs::File;
+
+use anyhow::{anyhow, Result};
+use thepipelinetool_core::dev::{assign_task_keys, get_edges, get_tasks};
+use thepipelinetool_runner::{
+    pipeline::Pipeline,
+    pipeline_diff::{diff_pipelines, PipelineDiff, ValueChange},
+    pipeline_options::PipelineOptions,
+};
+
+use crate::{
+    read_from_endpoint::fetch_pipeline, read_from_executable::read_from_executable,
+    read_from_yaml::read_from_yaml, source_type::SourceType,
+};
+
+fn load_pipeline(source: &str) -> Result<Pipeline> {
+    let (path, options) = match SourceType::from_source(Some(source)) {
+        SourceType::Endpoint => return fetch_pipeline(source),
+        SourceType::Exe => {
+            read_from_executable(source);
+            (source.to_string(), PipelineOptions::default())
+        }
+        SourceType::Yaml => {
+            read_from_yaml(serde_yaml::from_reader(File::open(source)?)?);
+            (
+                "".to_string(),
+                serde_yaml::from_reader(File::open(source)?)?,
+            )
+        }
+        SourceType::Raw | SourceType::None => {
+            return Err(anyhow!(
+                "'{source}' is not an executable, YAML file or endpoint"
+            ))
+        }
+    };
+
+    // readers load into the global pipeline, so take it to make room for the next one
+    assign_task_keys();
+    Ok(Pipeline {
+        path,
+        options,
+        tasks: std::mem::take(&mut *get_tasks().write().unwrap()),
+        edges: std::mem::take(&mut *get_edges().write().unwrap()),
+    })
+}
+
+fn display_change(change: &ValueChange) {
+    println!("    {}: {} -> {}", change.field, change.old, change.new);
+}
+
+fn display_diff_text(diff: &PipelineDiff) {
+    if diff.is_empty() {
+        println!("no changes");
+        return;
+    }
+
+    for change in &diff.changed_options {
+        println!(
+            "~ option {}: {} -> {}",
+            change.field, change.old, change.new
+        );
+    }
+    for task in &diff.added_tasks {
+        println!("+ task {task}");
+    }
+    for task in &diff.removed_tasks {
+        println!("- task {task}");
+    }
+    for (old, new) in &diff.renamed_tasks {
+        println!("~ task {old} renamed to {new}");
+    }
+    for task_diff in &diff.changed_tasks {
+        println!("~ task {}", task_diff.task);
+        task_diff.changed_fields.iter().for_each(display_change);
+        task_diff.changed_options.iter().for_each(display_change);
+        if let Some(change) = &task_diff.template_args {
+            display_change(change);
+        }
+    }
+    for (upstream, downstream) in &diff.added_edges {
+        println!("+ edge {upstream} -> {downstream}");
+    }
+    for (upstream, downstream) in &diff.removed_edges {
+        println!("- edge {upstream} -> {downstream}");
+    }
+}
+
+pub fn display_diff(old_source: &str, new_source: &str, json: bool) -> Result<()> {
+    let old = load_pipeline(old_source)?;
+    let new = load_pipeline(new_source)?;
+    let diff = diff_pipelines(&old, &new)?;
+
+    if json {
+        print!("{}", serde_json::to_string_pretty(&diff)?);
+    } else {
+        display_diff_text(&diff);
+    }
+    Ok(())
+}
@@ -1,12 +1,18 @@
 use std::collections::HashSet;
 
+use anyhow::Result;
+
 use thepipelinetool_core::dev::Task;
 use thepipelinetool_runner::{
     backend::Backend, blanket_backend::BlanketBackend, in_memory_backend::InMemoryBackend, run::Run,
 };
 
-pub fn display_tree(tasks: &[Task], edges: &HashSet<(usize, usize)>, pipeline_path: &str) {
-    let mut runner = InMemoryBackend::new(pipeline_path, tasks, edges);
+pub fn display_tree(
+    tasks: &[Task],
+    edges: &HashSet<(usize, usize)>,
+    pipeline_path: &str,
+) -> Result<()> {
+    let mut runner = InMemoryBackend::new(pipeline_path, tasks, edges)?;
     let run = Run::dummy();
     runner.enqueue_run(&run, None).unwrap();
     let tasks = runner
@@ -36,6 +42,7 @@ pub fn display_tree(tasks: &[Task], edges: &HashSet<(usize, usize)>, pipeline_pa
         ));
     }
     println!("{}", output);
+    Ok(())
 }
 
 fn get_tree(
@@ -4,7 +4,13 @@ use thepipelinetool_runner::{
     backend::Backend, blanket_backend::BlanketBackend, in_memory_backend::InMemoryBackend,
 };
 
-pub fn run_in_memory(backend: &mut InMemoryBackend, max_parallelism: usize, tpt_path: String) {
+// notifications are only sent with `notify`, otherwise they are dropped
+pub fn run_in_memory(
+    backend: &mut InMemoryBackend,
+    max_parallelism: usize,
+    tpt_path: String,
+    notify: bool,
+) {
     let (tx, rx) = channel();
     let mut current_parallel_tasks_count = 0;
 
@@ -15,8 +21,19 @@ pub fn run_in_memory(backend: &mut InMemoryBackend, max_parallelism: usize, tpt_
             let tpt_path = tpt_path.clone();
 
             thread::spawn(move || {
-                backend.work(&temp_queued_task, tpt_path).unwrap();
+                backend.work(&temp_queued_task, &tpt_path).unwrap();
                 backend.remove_from_temp_queue(&temp_queued_task).unwrap();
+                while let Some(queued_callback) = backend.pop_callback_queue().unwrap() {
+                    backend.run_callbacks(&queued_callback, &tpt_path).unwrap();
+                    backend
+                        .remove_from_callback_temp_queue(&queued_callback)
+                        .unwrap();
+                }
+                while let Some(notification) = backend.pop_notification_queue().unwrap() {
+                    if notify {
+                        backend.send_notification(&notification).unwrap();
+                    }
+                }
                 tx.send(()).unwrap();
             });
 
@@ -43,8 +60,19 @@ pub fn run_in_memory(backend: &mut InMemoryBackend, max_parallelism: usize, tpt_
             let tpt_path = tpt_path.clone();
 
             thread::spawn(move || {
-                backend.work(&temp_queued_task, tpt_path).unwrap();
+                backend.work(&temp_queued_task, &tpt_path).unwrap();
                 backend.remove_from_temp_queue(&temp_queued_task).unwrap();
+                while let Some(queued_callback) = backend.pop_callback_queue().unwrap() {
+                    backend.run_callbacks(&queued_callback, &tpt_path).unwrap();
+                    backend
+                        .remove_from_callback_temp_queue(&queued_callback)
+                        .unwrap();
+                }
+                while let Some(notification) = backend.pop_notification_queue().unwrap() {
+                    if notify {
+                        backend.send_notification(&notification).unwrap();
+                    }
+                }
                 tx.send(()).unwrap();
             });
             current_parallel_tasks_count += 1;
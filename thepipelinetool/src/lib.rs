@@ -18,13 +18,17 @@ use std::collections::HashSet;
 use crate::in_memory_runner::run_in_memory;
 
 pub mod commands;
+pub mod display_diff;
 pub mod display_hash;
 pub mod display_tree;
 mod in_memory_runner;
+pub mod read_from_endpoint;
 pub mod read_from_executable;
 pub mod read_from_yaml;
 pub mod source_type;
 pub mod templating;
+pub mod validate;
+pub mod variables;
 
 pub fn display_default_mermaid_graph(tasks: &[Task], edges: &HashSet<(usize, usize)>) {
     print!("{}", get_default_mermaid_graph(tasks, edges));
@@ -47,6 +51,7 @@ pub fn process_subcommands(
     options: &PipelineOptions,
     matches: &ArgMatches,
 ) -> Result<()> {
+    assign_task_keys();
     let tasks = &get_tasks().read().unwrap();
     let edges = &get_edges().read().unwrap();
 
@@ -59,7 +64,7 @@ pub fn process_subcommands(
         {
             "tasks" => display_tasks(),
             "edges" => display_edges(),
-            "hash" => display_hash(tasks, edges),
+            "hash" => display_hash(tasks, edges, options),
             "options" => display_options(options),
             _ => {}
         },
@@ -75,7 +80,7 @@ pub fn process_subcommands(
             _ => {}
         },
 
-        "tree" => display_tree(tasks, edges, pipeline_path),
+        "tree" => display_tree(tasks, edges, pipeline_path)?,
         "check" => {
             if let Some(err) = check_for_cycles(tasks, edges) {
                 eprintln!("{err}");
@@ -111,11 +116,20 @@ pub fn process_subcommands(
 
                     check_for_cycles(tasks, edges);
 
-                    let mut backend = InMemoryBackend::new(pipeline_path, tasks, edges);
+                    let mut backend =
+                        InMemoryBackend::new(pipeline_path, tasks, edges)?.with_options(options);
                     let run = Run::dummy();
                     backend.enqueue_run(&run, trigger_params)?;
 
-                    run_in_memory(&mut backend, max_parallelism, env::args().next().unwrap());
+                    run_in_memory(
+                        &mut backend,
+                        max_parallelism,
+                        env::args().next().unwrap(),
+                        matches
+                            .subcommand_matches("in_memory")
+                            .unwrap()
+                            .get_flag("notify"),
+                    );
 
                     let run_status = backend.get_run_status(run.run_id).unwrap();
 
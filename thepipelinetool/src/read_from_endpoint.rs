@@ -0,0 +1,28 @@
+use anyhow::{anyhow, Result};
+use thepipelinetool_core::dev::{get_edges, get_tasks};
+use thepipelinetool_runner::{pipeline::Pipeline, pipeline_options::PipelineOptions};
+
+pub fn fetch_pipeline(endpoint: &str) -> Result<Pipeline> {
+    let res = reqwest::blocking::get(endpoint)?;
+    if !res.status().is_success() {
+        return Err(anyhow!(
+            "failed to fetch pipeline from '{endpoint}'\n{}",
+            res.text()?
+        ));
+    }
+    Ok(res.json()?)
+}
+
+pub fn read_from_endpoint(endpoint: &str) -> Result<PipelineOptions> {
+    let pipeline = fetch_pipeline(endpoint)?;
+
+    for task in pipeline.tasks {
+        get_tasks().write().unwrap().insert(task.id, task);
+    }
+
+    for edge in pipeline.edges {
+        get_edges().write().unwrap().insert(edge);
+    }
+
+    Ok(pipeline.options)
+}
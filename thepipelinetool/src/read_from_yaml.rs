@@ -74,6 +74,9 @@ pub fn read_from_yaml(value: Value) {
             //     );
             // }
 
+            let connections: Vec<&str> =
+                template_task.connections.iter().map(|c| c.as_str()).collect();
+
             if template_task.lazy_expand {
                 assert!(depends_on.len() == 1);
                 _expand_lazy_with_function_name::<Value, Vec<Value>, Value>(
@@ -81,7 +84,8 @@ pub fn read_from_yaml(value: Value) {
                     &template_task.options,
                     &template_task.name,
                     &template_task.operator,
-                );
+                )
+                .with_connections(&connections);
             } else {
                 _add_task_with_function_name::<Value, Value>(
                     create_template_args_by_operator(id, value, operator, &task_id_by_name),
@@ -89,7 +93,8 @@ pub fn read_from_yaml(value: Value) {
                     &template_task.name,
                     &template_task.operator,
                     use_trigger_params,
-                );
+                )
+                .with_connections(&connections);
             }
         }
     }
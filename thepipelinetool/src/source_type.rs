@@ -5,14 +5,17 @@ pub enum SourceType {
     Exe,
     Yaml,
     Raw,
+    Endpoint,
     None,
 }
 
 impl SourceType {
-    pub fn from_source(source: Option<&String>) -> Self {
+    pub fn from_source(source: Option<&str>) -> Self {
         if let Some(source) = source {
             if source == "" {
                 SourceType::None
+            } else if source.starts_with("http://") || source.starts_with("https://") {
+                SourceType::Endpoint
             } else {
                 let p = Path::new(source);
                 if p.exists() {
@@ -8,6 +8,7 @@ use thepipelinetool_core::dev::{
     python::{TemplatePythonArgs, REQUIREMENTS_KEY},
     Operator, TaskOptions, ORIGINAL_STRING_KEY,
 };
+use thepipelinetool_runner::template_variables::is_variable_reference;
 use thepipelinetool_utils::{
     function_name_as_string, UPSTREAM_TASK_ID_KEY, UPSTREAM_TASK_RESULT_KEY,
 };
@@ -35,6 +36,9 @@ pub struct TemplateTask {
 
     #[serde(default)]
     pub depends_on: Vec<String>,
+
+    #[serde(default)]
+    pub connections: Vec<String>,
 }
 
 const LEFT_INTERPOLATION_IDENTIFIER: &str = "{{";
@@ -77,6 +81,26 @@ pub fn create_template_args_by_operator(
     }
 }
 
+// returns the trimmed contents of every '{{ }}' in the string
+pub fn get_template_references(original_string: &str) -> Vec<String> {
+    let mut references = vec![];
+    let mut temp_string = original_string;
+
+    while let (Some(left), Some(right)) = (
+        temp_string.find(LEFT_INTERPOLATION_IDENTIFIER),
+        temp_string.find(RIGHT_INTERPOLATION_IDENTIFIER),
+    ) {
+        if right < left {
+            temp_string = &temp_string[(right + 2)..];
+            continue;
+        }
+        references.push(temp_string[(left + 2)..right].trim().to_string());
+        temp_string = &temp_string[(right + 2)..];
+    }
+
+    references
+}
+
 pub fn create_template_args_from_string(
     task_id: usize,
     original_string: &str,
@@ -97,7 +121,14 @@ pub fn create_template_args_from_string(
             break;
         }
         let (left, right) = (left.unwrap(), right.unwrap());
-        let chunks: Vec<&str> = temp_string[(left + 2)..(right)].trim().split('.').collect();
+        let reference = temp_string[(left + 2)..(right)].trim();
+
+        // variables and secrets are resolved when the task runs
+        if is_variable_reference(reference) {
+            temp_string.replace_range(left..(right + 2), "");
+            continue;
+        }
+        let chunks: Vec<&str> = reference.split('.').collect();
 
         let upstream_task_name = chunks[0];
         let upstream_id = task_id_by_name
@@ -126,9 +157,10 @@ mod tests {
     use std::collections::HashMap;
 
     use serde_json::json;
+    use thepipelinetool_core::dev::ORIGINAL_STRING_KEY;
     use thepipelinetool_utils::{UPSTREAM_TASK_ID_KEY, UPSTREAM_TASK_RESULT_KEY};
 
-    use crate::templating::create_template_args_from_string;
+    use crate::templating::{create_template_args_from_string, get_template_references};
 
     #[test]
     fn test_create_bash_args() {
@@ -164,4 +196,24 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_create_bash_args_with_variables() {
+        let task_id_by_name = HashMap::from([("t1".to_string(), 0)]);
+        let script = "echo {{ var.host }} {{ secret.token }} {{t1}}";
+        let args = create_template_args_from_string(1, script, &task_id_by_name);
+
+        assert_eq!(args[ORIGINAL_STRING_KEY], script);
+        assert_eq!(args["{{t1}}"], json!({ UPSTREAM_TASK_ID_KEY: 0 }));
+        assert_eq!(args.as_object().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_get_template_references() {
+        assert_eq!(
+            vec!["t1", "t2", "t3.data"],
+            get_template_references("echo {{  t1 }}{{t2}} }} {{t3.data}}")
+        );
+        assert!(get_template_references("echo {{ t1").is_empty());
+    }
 }
@@ -0,0 +1,165 @@
+use std::{collections::HashSet, fs, process};
+
+use anyhow::Result;
+use saffron::Cron;
+use serde_json::{json, Value};
+use thepipelinetool_core::dev::{
+    assign_task_keys, bash::TemplateBashTaskArgs, get_edges, get_tasks, python::TemplatePythonArgs,
+    validate_tasks, Operator,
+};
+use thepipelinetool_runner::{
+    pipeline_options::PipelineOptions, template_variables::is_variable_reference,
+};
+
+use crate::{
+    read_from_endpoint::read_from_endpoint,
+    read_from_executable::read_from_executable,
+    read_from_yaml::read_from_yaml,
+    source_type::SourceType,
+    templating::{get_template_references, TemplateTask},
+};
+
+// parsed the same way the server's scheduler does
+fn validate_schedule(options: &PipelineOptions) -> Vec<String> {
+    match &options.schedule {
+        Some(schedule) => match schedule.parse::<Cron>() {
+            Ok(cron) if !cron.any() => vec![format!("schedule '{schedule}' never matches")],
+            Ok(_) => vec![],
+            Err(_) => vec![format!("invalid cron schedule '{schedule}'")],
+        },
+        None => vec![],
+    }
+}
+
+fn validate_script(
+    task_name: &str,
+    script: &str,
+    task_names: &HashSet<&str>,
+    problems: &mut Vec<String>,
+) {
+    for reference in get_template_references(script) {
+        if is_variable_reference(&reference) {
+            continue;
+        }
+        let chunks: Vec<&str> = reference.split('.').collect();
+        if !task_names.contains(chunks[0]) {
+            problems.push(format!(
+                "task '{task_name}' references unknown task '{}' in '{{{{ {reference} }}}}'",
+                chunks[0]
+            ));
+        } else if chunks.len() > 2 || chunks.iter().any(|c| c.is_empty()) {
+            problems.push(format!(
+                "task '{task_name}' has invalid reference '{{{{ {reference} }}}}', expected '{{{{ task }}}}' or '{{{{ task.key }}}}'"
+            ));
+        }
+    }
+}
+
+// checks the YAML template itself, since loading a broken template panics
+fn validate_template(value: &Value) -> Vec<String> {
+    let mut problems = vec![];
+
+    let tasks = match value.get("tasks").map(|tasks| tasks.as_object()) {
+        Some(Some(tasks)) => tasks,
+        Some(None) => return vec!["'tasks' must be a map of task names to tasks".into()],
+        None => return problems,
+    };
+    let task_names: HashSet<&str> = tasks.keys().map(|k| k.as_str()).collect();
+
+    for (task_name, task_value) in tasks {
+        let template_task: TemplateTask = match serde_json::from_value(task_value.clone()) {
+            Ok(template_task) => template_task,
+            Err(err) => {
+                problems.push(format!("task '{task_name}' is invalid: {err}"));
+                continue;
+            }
+        };
+
+        for dependency in &template_task.depends_on {
+            if !task_names.contains(dependency.as_str()) {
+                problems.push(format!(
+                    "task '{task_name}' depends on unknown task '{dependency}'"
+                ));
+            }
+        }
+
+        if template_task.lazy_expand && template_task.depends_on.len() != 1 {
+            problems.push(format!(
+                "lazy_expand task '{task_name}' must depend on exactly one task, found {}",
+                template_task.depends_on.len()
+            ));
+        }
+
+        let script = match serde_json::from_value::<Operator>(json!(template_task.operator)) {
+            Ok(Operator::BashOperator) => {
+                serde_json::from_value::<TemplateBashTaskArgs>(task_value.clone())
+                    .map(|args| args.script)
+            }
+            Ok(Operator::PythonOperator) => {
+                serde_json::from_value::<TemplatePythonArgs>(task_value.clone())
+                    .map(|args| args.script)
+            }
+            Ok(_) => continue,
+            Err(_) => {
+                problems.push(format!(
+                    "task '{task_name}' uses unknown operator '{}'",
+                    template_task.operator
+                ));
+                continue;
+            }
+        };
+        match script {
+            Ok(script) => validate_script(task_name, &script, &task_names, &mut problems),
+            Err(err) => problems.push(format!("task '{task_name}' has invalid args: {err}")),
+        }
+    }
+
+    problems
+}
+
+pub fn validate(source_type: &SourceType, pipeline_source: &str) -> Result<()> {
+    let mut problems = vec![];
+
+    match source_type {
+        SourceType::Yaml | SourceType::Raw => {
+            let contents = match source_type {
+                SourceType::Yaml => fs::read_to_string(pipeline_source)?,
+                _ => pipeline_source.to_string(),
+            };
+            match serde_yaml::from_str::<Value>(&contents) {
+                Ok(value) => {
+                    match serde_json::from_value::<PipelineOptions>(value.clone()) {
+                        Ok(options) => problems.extend(validate_schedule(&options)),
+                        Err(err) => problems.push(format!("invalid pipeline options: {err}")),
+                    }
+                    let template_problems = validate_template(&value);
+                    if template_problems.is_empty() {
+                        read_from_yaml(value);
+                    }
+                    problems.extend(template_problems);
+                }
+                Err(err) => problems.push(format!("invalid pipeline: {err}")),
+            }
+        }
+        SourceType::Exe => read_from_executable(pipeline_source),
+        SourceType::Endpoint => {
+            problems.extend(validate_schedule(&read_from_endpoint(pipeline_source)?));
+        }
+        SourceType::None => problems.push("no pipeline to validate".into()),
+    }
+
+    assign_task_keys();
+    problems.extend(validate_tasks(
+        &get_tasks().read().unwrap(),
+        &get_edges().read().unwrap(),
+    ));
+
+    if !problems.is_empty() {
+        for problem in &problems {
+            eprintln!("{problem}");
+        }
+        eprintln!("found {} problem(s)", problems.len());
+        process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use std::process;
+
+use anyhow::Result;
+use clap::ArgMatches;
+use reqwest::blocking::{Client, Response};
+use serde_json::Value;
+
+fn exit_on_error(res: Response) -> Result<Response> {
+    if !res.status().is_success() {
+        eprintln!("{}", res.text()?);
+        process::exit(1);
+    }
+    Ok(res)
+}
+
+// endpoint is a server variables route, e.g. http://localhost:8000/variables/my_pipeline,
+// http://localhost:8000/run_variables/3 or http://localhost:8000/global_variables
+pub fn variables(matches: &ArgMatches) -> Result<()> {
+    manage_server_values(matches)
+}
+
+// endpoint is the server connections route, e.g. http://localhost:8000/connections
+pub fn connections(matches: &ArgMatches) -> Result<()> {
+    manage_server_values(matches)
+}
+
+// both are key-value stores with list/get/set/delete routes
+fn manage_server_values(matches: &ArgMatches) -> Result<()> {
+    let client = Client::new();
+    let (subcommand_name, matches) = matches.subcommand().unwrap();
+    let endpoint = matches
+        .get_one::<String>("endpoint")
+        .unwrap()
+        .trim_end_matches('/');
+    let key_endpoint = || format!("{endpoint}/{}", matches.get_one::<String>("key").unwrap());
+
+    match subcommand_name {
+        "list" => {
+            let values: Value = exit_on_error(client.get(endpoint).send()?)?.json()?;
+            println!("{}", serde_json::to_string_pretty(&values)?);
+        }
+        "get" => {
+            let value: Value = exit_on_error(client.get(key_endpoint()).send()?)?.json()?;
+            println!("{}", serde_json::to_string_pretty(&value)?);
+        }
+        "set" => {
+            let value = matches.get_one::<String>("value").unwrap();
+            // values that are not valid JSON are stored as strings
+            let value = serde_json::from_str(value).unwrap_or(Value::String(value.into()));
+            exit_on_error(client.post(key_endpoint()).json(&value).send()?)?;
+        }
+        "delete" => {
+            exit_on_error(client.delete(key_endpoint()).send()?)?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
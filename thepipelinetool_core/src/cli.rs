@@ -96,8 +96,13 @@ fn create_commands() -> CliCommand {
 /// The behavior of the CLI tool depends on the subcommands and options passed on the command
 /// line. Use the "--help" command to see the CLI details.
 pub fn parse_cli() {
+    assign_task_keys();
     let command = create_commands();
     let matches = command.get_matches();
+
+    // async task functions are driven on this runtime
+    get_runtime();
+
     match matches.subcommand_name().unwrap() {
         "describe" => match matches
             .subcommand_matches("describe")
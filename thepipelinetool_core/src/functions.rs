@@ -4,7 +4,7 @@ use serde::de::DeserializeOwned;
 
 use crate::dev::*;
 
-pub fn expand<F, T, G, const N: usize>(
+pub fn expand<F, T, G, FM, const N: usize>(
     function: F,
     template_args_vec: &[T; N],
     options: &TaskOptions,
@@ -12,7 +12,8 @@ pub fn expand<F, T, G, const N: usize>(
 where
     T: Serialize + DeserializeOwned + 'static,
     G: Serialize + 'static,
-    F: Fn(T) -> G + 'static + Sync + Send,
+    F: TaskFunction<T, G, FM>,
+    FM: 'static,
 {
     let function_name = register_function(function);
 
@@ -25,14 +26,16 @@ where
                 id,
                 Task {
                     id,
+                    key: String::new(),
                     name: function_name.to_string(),
                     function: function_name.clone(),
                     template_args: serde_json::to_value(&template_args_vec[i]).unwrap(),
-                    options: *options,
+                    options: options.clone(),
                     lazy_expand: false,
                     is_dynamic: false,
                     is_branch: false,
                     use_trigger_params: false,
+                    connections: vec![],
                 },
             );
         }
@@ -46,7 +49,7 @@ where
     })
 }
 
-pub fn add_task_with_ref<F, T, G>(
+pub fn add_task_with_ref<F, T, G, FM>(
     function: F,
     task_ref: &TaskRef<T>,
     options: &TaskOptions,
@@ -54,7 +57,8 @@ pub fn add_task_with_ref<F, T, G>(
 where
     T: Serialize + DeserializeOwned + 'static,
     G: Serialize + 'static,
-    F: Fn(T) -> G + 'static + Sync + Send,
+    F: TaskFunction<T, G, FM>,
+    FM: 'static,
 {
     let function_name = register_function(function);
 
@@ -68,11 +72,12 @@ where
         )
 }
 
-pub fn add_task<F, T, G>(function: F, template_args: T, options: &TaskOptions) -> TaskRef<G>
+pub fn add_task<F, T, G, FM>(function: F, template_args: T, options: &TaskOptions) -> TaskRef<G>
 where
     T: Serialize + DeserializeOwned + 'static,
     G: Serialize + 'static,
-    F: Fn(T) -> G + 'static + Sync + Send,
+    F: TaskFunction<T, G, FM>,
+    FM: 'static,
 {
     let function_name = register_function(function);
 
@@ -85,18 +90,19 @@ where
     )
 }
 
-pub fn add_task_using_trigger_params<F, T, G>(function: F, options: &TaskOptions) -> TaskRef<G>
+pub fn add_task_using_trigger_params<F, T, G, FM>(function: F, options: &TaskOptions) -> TaskRef<G>
 where
     T: Serialize + DeserializeOwned + 'static,
     G: Serialize + 'static,
-    F: Fn(T) -> G + 'static + Sync + Send,
+    F: TaskFunction<T, G, FM>,
+    FM: 'static,
 {
     let function_name = register_function(function);
 
     _add_task_with_function_name::<T, G>(Value::Null, options, &function_name, &function_name, true)
 }
 
-pub fn branch<F, K, T, L, J, R, M>(
+pub fn branch<F, K, T, L, J, R, M, FM, LM, RM>(
     function: F,
     template_args: K,
     left: L,
@@ -108,9 +114,12 @@ where
     K: Serialize + DeserializeOwned + 'static,
     J: Serialize + 'static,
     M: Serialize + 'static,
-    F: Fn(K) -> Branch<T> + 'static + Sync + Send,
-    L: Fn(T) -> J + 'static + Sync + Send,
-    R: Fn(T) -> M + 'static + Sync + Send,
+    F: TaskFunction<K, Branch<T>, FM>,
+    FM: 'static,
+    L: TaskFunction<T, J, LM>,
+    R: TaskFunction<T, M, RM>,
+    LM: 'static,
+    RM: 'static,
 {
     let id = get_tasks().read().unwrap().len();
     let function_name = register_function(function);
@@ -120,14 +129,16 @@ where
             id,
             Task {
                 id,
+                key: String::new(),
                 name: function_name.to_string(),
                 function: function_name.to_string(),
                 template_args: serde_json::to_value(template_args).unwrap(),
-                options: *options,
+                options: options.clone(),
                 lazy_expand: false,
                 is_dynamic: false,
                 is_branch: true,
                 use_trigger_params: false,
+                connections: vec![],
             },
         );
     }
@@ -144,7 +155,7 @@ where
     )
 }
 
-pub fn expand_lazy<K, F, T, G>(
+pub fn expand_lazy<K, F, T, G, FM>(
     function: F,
     task_ref: &TaskRef<T>,
     options: &TaskOptions,
@@ -153,7 +164,8 @@ where
     K: Serialize + DeserializeOwned + 'static,
     T: Serialize + DeserializeOwned + IntoIterator<Item = K>,
     G: Serialize + 'static,
-    F: Fn(K) -> G + 'static + Sync + Send,
+    F: TaskFunction<K, G, FM>,
+    FM: 'static,
 {
     register_function(collector);
     let function_name = register_function(function);
@@ -161,13 +173,41 @@ where
     _expand_lazy_with_function_name::<K, T, G>(task_ref, options, &function_name, &function_name)
 }
 
-pub fn register_function<G, T, F>(function: F) -> String
+pub fn register_function<G, T, F, FM>(function: F) -> String
 where
     T: Serialize + DeserializeOwned + 'static,
     G: Serialize + 'static,
-    F: Fn(T) -> G + 'static + Sync + Send,
+    F: TaskFunction<T, G, FM>,
+    FM: 'static,
 {
     let function_name = function_name_as_string(&function).to_string();
     _register_function_with_name(function, &function_name);
     function_name
 }
+
+// registers `function` to be run as a callback of a task, it receives a `CallbackContext`
+pub fn callback<G, F, FM>(function: F) -> TaskCallback
+where
+    G: Serialize + 'static,
+    F: TaskFunction<CallbackContext, G, FM>,
+    FM: 'static,
+{
+    TaskCallback {
+        function: register_function(function),
+        args: None,
+    }
+}
+
+// registers `function` to be run as a callback with `args`, see `TaskCallback::args`
+pub fn callback_with_args<T, G, F, FM>(function: F, args: Value) -> TaskCallback
+where
+    T: Serialize + DeserializeOwned + 'static,
+    G: Serialize + 'static,
+    F: TaskFunction<T, G, FM>,
+    FM: 'static,
+{
+    TaskCallback {
+        function: register_function(function),
+        args: Some(args),
+    }
+}
@@ -22,14 +22,17 @@ where
             id,
             Task {
                 id,
+                // assigned by assign_task_keys unless set with `with_task_key`
+                key: String::new(),
                 name: name.to_string(),
                 function: function_name.to_string(),
                 template_args: serde_json::to_value(template_args).unwrap(),
-                options: *options,
+                options: options.clone(),
                 lazy_expand: false,
                 is_dynamic: false,
                 is_branch: false,
                 use_trigger_params,
+                connections: vec![],
             },
         );
     }
@@ -41,11 +44,12 @@ where
     })
 }
 
-pub fn _register_function_with_name<G, T, F>(function: F, name: &str)
+pub fn _register_function_with_name<G, T, F, FM>(function: F, name: &str)
 where
     T: Serialize + DeserializeOwned + 'static,
     G: Serialize + 'static,
-    F: Fn(T) -> G + 'static + Sync + Send,
+    F: TaskFunction<T, G, FM>,
+    FM: 'static,
 {
     get_functions()
         .write()
@@ -71,14 +75,16 @@ where
             id,
             Task {
                 id,
+                key: String::new(),
                 name: name.to_string(),
                 function: function_name.to_string(),
                 template_args: serde_json::to_value(task_ref).unwrap(),
-                options: *options,
+                options: options.clone(),
                 lazy_expand: true,
                 is_dynamic: false,
                 is_branch: false,
                 use_trigger_params: false,
+                connections: vec![],
             },
         );
     }
@@ -91,15 +97,18 @@ where
         })
 }
 
-pub fn _wrap_function<K, T, F>(function: F) -> impl Fn(Value) -> Value
+pub fn _wrap_function<K, T, F, FM>(function: F) -> impl Fn(Value) -> Value
 where
     T: Serialize,
     K: Serialize + DeserializeOwned,
-    F: Fn(K) -> T + 'static + Sync + Send,
+    F: TaskFunction<K, T, FM>,
+    FM: 'static,
 {
     move |value: Value| -> Value {
         let input: K = serde_json::from_value(value).unwrap();
-        let output: T = function(input);
-        serde_json::to_value(output).unwrap()
+        match function.call(input) {
+            Ok(output) => serde_json::to_value(output).unwrap(),
+            Err(error) => json!({ TASK_ERROR_KEY: error }),
+        }
     }
 }
@@ -10,24 +10,33 @@ mod graph;
 mod helpers;
 mod ops;
 mod statics;
+mod task_function;
+mod validate;
 
 use serde::Serialize;
 
 pub struct TaskRef<T: Serialize>(dev::TaskRefInner<T>);
 
 pub mod tpt {
-    pub use thepipelinetool_proc_macro::main;
+    pub use thepipelinetool_proc_macro::{main, task};
 }
 
 pub mod prelude {
     pub use crate::cli::parse_cli;
+    pub use crate::task_function::{Plain, TaskValue};
     pub use crate::{functions::*, TaskRef};
     pub use thepipelinetool_operators::*;
     pub use crate::tpt::*;
+    pub use thepipelinetool_proc_macro::TaskOutput;
 
     pub use serde::{Deserialize, Serialize};
     pub use serde_json::{json, Value};
     pub use thepipelinetool_task::branch::Branch;
+    pub use thepipelinetool_task::connection::Connection;
+    pub use thepipelinetool_task::task_callback::{CallbackContext, CallbackEvent, TaskCallback};
+    pub use thepipelinetool_task::task_context::TaskContext;
+    pub use thepipelinetool_task::task_error::TaskFailure;
+    pub use thepipelinetool_task::task_kind::TaskKind;
     pub use thepipelinetool_task::task_options::TaskOptions;
     pub use thepipelinetool_task::trigger_rule::TriggerRule;
 }
@@ -52,8 +61,14 @@ pub mod dev {
     pub use crate::helpers::*;
     pub use crate::prelude::*;
     pub use crate::statics::*;
+    pub use crate::task_function::*;
+    pub use crate::validate::*;
+    pub use thepipelinetool_task::log_line::{render_log_lines, LogFilter, LogLine, LogStream};
     pub use thepipelinetool_task::ordered_queued_task::OrderedQueuedTask;
     pub use thepipelinetool_task::queued_task::QueuedTask;
+    pub use thepipelinetool_task::task_callback::QueuedCallback;
+    pub use thepipelinetool_task::task_context::TRACEPARENT_ENV;
+    pub use thepipelinetool_task::task_error::{IntoTaskError, TaskError};
     pub use thepipelinetool_task::task_result::TaskResult;
     pub use thepipelinetool_task::task_status::TaskStatus;
     pub use thepipelinetool_task::temp_queued_task::TempQueuedTask;
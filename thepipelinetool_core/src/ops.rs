@@ -150,6 +150,12 @@ impl<T: Serialize> Serialize for TaskRef<T> {
 
 impl<T: Serialize> TaskRef<T> {
     pub fn get(&self, key: &str) -> TaskRef<Value> {
+        self._get_typed(key)
+    }
+
+    // used by #[derive(TaskOutput)] accessors
+    #[doc(hidden)]
+    pub fn _get_typed<G: Serialize>(&self, key: &str) -> TaskRef<G> {
         assert!(self.0.task_ids.len() == 1, "Cannot use parallel ref as arg");
 
         TaskRef(TaskRefInner {
@@ -159,6 +165,33 @@ impl<T: Serialize> TaskRef<T> {
         })
     }
 
+    pub fn with_task_key(self, key: &str) -> Self {
+        assert!(self.0.task_ids.len() == 1, "Cannot set key of parallel ref");
+        assert!(!key.is_empty(), "task keys cannot be empty");
+        let id = *self.0.task_ids.iter().next().unwrap();
+
+        if let Some(existing_id) = get_id_by_task_key(key) {
+            assert!(existing_id == id, "task key '{key}' is already in use");
+        }
+        get_tasks().write().unwrap()[id].key = key.to_string();
+        self
+    }
+
+    // the task receives these connections through its TaskContext and TPT_CONN_* variables,
+    // in addition to connections added before
+    pub fn with_connections(self, connection_ids: &[&str]) -> Self {
+        let mut tasks = get_tasks().write().unwrap();
+        for id in &self.0.task_ids {
+            let connections = &mut tasks[*id].connections;
+            for connection_id in connection_ids {
+                if !connections.iter().any(|c| c == connection_id) {
+                    connections.push(connection_id.to_string());
+                }
+            }
+        }
+        self
+    }
+
     pub fn value(&self) -> TaskRef<Value> {
         assert!(self.0.task_ids.len() == 1, "Cannot use parallel ref as arg");
 
@@ -1,7 +1,10 @@
 use crate::dev::*;
+use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
 use std::sync::{OnceLock, RwLock};
 
+use tokio::runtime::Runtime;
+
 type StaticTasks = RwLock<Vec<Task>>;
 type StaticFunctions = RwLock<HashMap<String, Box<dyn Fn(Value) -> Value + Sync + Send>>>;
 type StaticEdges = RwLock<HashSet<(usize, usize)>>;
@@ -9,6 +12,7 @@ type StaticEdges = RwLock<HashSet<(usize, usize)>>;
 static TASKS: OnceLock<StaticTasks> = OnceLock::new();
 static FUNCTIONS: OnceLock<StaticFunctions> = OnceLock::new();
 static EDGES: OnceLock<StaticEdges> = OnceLock::new();
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
 
 pub fn get_tasks() -> &'static StaticTasks {
     TASKS.get_or_init(StaticTasks::default)
@@ -22,10 +26,16 @@ pub fn get_edges() -> &'static StaticEdges {
     EDGES.get_or_init(StaticEdges::default)
 }
 
+// drives async task functions
+pub fn get_runtime() -> &'static Runtime {
+    RUNTIME.get_or_init(|| Runtime::new().expect("failed to create runtime"))
+}
+
 pub fn function_with_name_exists(task_name: &str) -> bool {
     get_functions().read().unwrap().contains_key(task_name)
 }
 
+// the first task with the name, tasks sharing a name are told apart by their key
 pub fn get_id_by_task_name(name: &str) -> usize {
     get_tasks()
         .read()
@@ -35,3 +45,165 @@ pub fn get_id_by_task_name(name: &str) -> usize {
         .unwrap_or_else(|| panic!("missing task {name}"))
         .id
 }
+
+pub fn get_id_by_task_key(key: &str) -> Option<usize> {
+    get_tasks()
+        .read()
+        .unwrap()
+        .iter()
+        .find(|t| t.key == key)
+        .map(|t| t.id)
+}
+
+// tasks without an explicit key are keyed by their name once the pipeline is complete. tasks
+// sharing a name get a suffix derived from their function and upstream keys instead of their
+// position, so adding or moving other tasks doesn't change any key
+pub fn assign_task_keys() {
+    let edges = get_edges().read().unwrap().clone();
+    fill_task_keys(&mut get_tasks().write().unwrap(), &edges);
+}
+
+fn fill_task_keys(tasks: &mut [Task], edges: &HashSet<(usize, usize)>) {
+    let mut used: HashSet<String> = tasks
+        .iter()
+        .filter(|t| !t.key.is_empty())
+        .map(|t| t.key.clone())
+        .collect();
+    let mut name_counts: HashMap<String, usize> = HashMap::new();
+    for task in tasks.iter().filter(|t| t.key.is_empty()) {
+        *name_counts.entry(task.name.clone()).or_default() += 1;
+    }
+
+    for id in upstream_first(tasks.len(), edges) {
+        if !tasks[id].key.is_empty() {
+            continue;
+        }
+        let name = &tasks[id].name;
+        let mut key = name.clone();
+        if name_counts[name] > 1 || used.contains(&key) {
+            let mut upstream_keys: Vec<&str> = edges
+                .iter()
+                .filter(|(_, down)| *down == id)
+                .map(|(up, _)| tasks[*up].key.as_str())
+                .collect();
+            upstream_keys.sort();
+            let digest = Sha256::digest(json!([tasks[id].function, upstream_keys]).to_string());
+            let base = format!(
+                "{name}_{:08x}",
+                u32::from_be_bytes(digest[..4].try_into().unwrap())
+            );
+
+            // only tasks with the same function and upstream tasks, e.g. from `expand`, are
+            // numbered in the order they were added
+            key = base.clone();
+            let mut i = 0;
+            while used.contains(&key) {
+                i += 1;
+                key = format!("{base}_{i}");
+            }
+        }
+        used.insert(key.clone());
+        tasks[id].key = key;
+    }
+}
+
+// task ids ordered so upstream tasks come before their downstream tasks, cycles in id order
+fn upstream_first(task_count: usize, edges: &HashSet<(usize, usize)>) -> Vec<usize> {
+    let mut upstream_counts = vec![0; task_count];
+    for (_, down) in edges {
+        upstream_counts[*down] += 1;
+    }
+    let mut ready: Vec<usize> = (0..task_count)
+        .filter(|id| upstream_counts[*id] == 0)
+        .rev()
+        .collect();
+    let mut order = vec![];
+    while let Some(id) = ready.pop() {
+        order.push(id);
+        let mut downstream: Vec<usize> = edges
+            .iter()
+            .filter(|(up, _)| *up == id)
+            .map(|(_, down)| *down)
+            .collect();
+        downstream.sort_by(|a, b| b.cmp(a));
+        for down in downstream {
+            upstream_counts[down] -= 1;
+            if upstream_counts[down] == 0 {
+                ready.push(down);
+            }
+        }
+    }
+    let mut seen: HashSet<usize> = order.iter().copied().collect();
+    for id in 0..task_count {
+        if seen.insert(id) {
+            order.push(id);
+        }
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unkeyed(id: usize, name: &str) -> Task {
+        Task {
+            key: String::new(),
+            ..Task::dummy(id, name)
+        }
+    }
+
+    fn keys(tasks: &[Task]) -> HashMap<usize, String> {
+        tasks.iter().map(|t| (t.id, t.key.clone())).collect()
+    }
+
+    #[test]
+    fn test_duplicate_keys_do_not_depend_on_insertion_order() {
+        // a -> foo, b -> foo
+        let mut tasks = vec![
+            unkeyed(0, "a"),
+            unkeyed(1, "b"),
+            unkeyed(2, "foo"),
+            unkeyed(3, "foo"),
+        ];
+        let edges = HashSet::from([(0, 2), (1, 3)]);
+        fill_task_keys(&mut tasks, &edges);
+        let before = keys(&tasks);
+
+        // the same pipeline with the duplicates added the other way round
+        let mut swapped = vec![
+            unkeyed(0, "a"),
+            unkeyed(1, "b"),
+            unkeyed(2, "foo"),
+            unkeyed(3, "foo"),
+        ];
+        fill_task_keys(&mut swapped, &HashSet::from([(0, 3), (1, 2)]));
+
+        assert_eq!(before[&0], "a");
+        assert_ne!(before[&2], before[&3]);
+        assert_eq!(before[&2], swapped[3].key);
+        assert_eq!(before[&3], swapped[2].key);
+    }
+
+    #[test]
+    fn test_generated_keys_do_not_collide() {
+        let mut tasks = vec![
+            unkeyed(0, "foo"),
+            Task {
+                key: "foo".into(),
+                ..Task::dummy(1, "foo_1")
+            },
+            unkeyed(2, "bar"),
+        ];
+        fill_task_keys(&mut tasks, &HashSet::new());
+
+        assert_eq!(tasks[1].key, "foo");
+        assert!(tasks[0].key.starts_with("foo_"));
+        assert_eq!(tasks[2].key, "bar");
+
+        // identical siblings are still told apart
+        let mut tasks = vec![unkeyed(0, "foo"), unkeyed(1, "foo")];
+        fill_task_keys(&mut tasks, &HashSet::new());
+        assert_eq!(tasks[1].key, format!("{}_1", tasks[0].key));
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    future::Future,
+    marker::PhantomData,
+};
+
+use serde::Serialize;
+use serde_json::Value;
+use thepipelinetool_task::{
+    branch::Branch,
+    task_context::TaskContext,
+    task_error::{IntoTaskError, TaskError},
+};
+
+use crate::statics::get_runtime;
+
+pub struct PlainReturn;
+pub struct WrappedReturn;
+pub struct FallibleReturn<E, M>(PhantomData<(E, M)>);
+pub struct AsyncReturn<M>(PhantomData<M>);
+
+/// Plain values a task function may return. `Result` is not one of them, so a function
+/// returning `Result<G, E>` always outputs `G`.
+///
+/// Implemented for the std types serde supports, `Branch` and structs deriving `TaskOutput`,
+/// anything else `Serialize` can be returned wrapped in `Plain`.
+pub trait TaskValue: Serialize {}
+
+/// Returns any `Serialize` value from a task, the task outputs the wrapped value.
+pub struct Plain<G>(pub G);
+
+macro_rules! task_values {
+    ($($ty:ty),*) => {
+        $(impl TaskValue for $ty {})*
+    };
+}
+
+task_values!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    String,
+    &'static str,
+    Value
+);
+
+macro_rules! task_value_tuples {
+    ($(($($name:ident),+)),*) => {
+        $(impl<$($name: Serialize),+> TaskValue for ($($name,)+) {})*
+    };
+}
+
+task_value_tuples!(
+    (A),
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F)
+);
+
+impl<G: Serialize> TaskValue for Option<G> {}
+impl<G: Serialize> TaskValue for Box<G> {}
+impl<G: Serialize> TaskValue for Vec<G> {}
+impl<G: Serialize> TaskValue for VecDeque<G> {}
+impl<G: Serialize, S> TaskValue for HashSet<G, S> {}
+impl<G: Serialize> TaskValue for BTreeSet<G> {}
+impl<K: Serialize, V: Serialize, S> TaskValue for HashMap<K, V, S> {}
+impl<K: Serialize, V: Serialize> TaskValue for BTreeMap<K, V> {}
+impl<G: Serialize> TaskValue for Branch<G> {}
+
+mod sealed {
+    pub trait Sealed<M> {}
+}
+
+/// Values a task function may return: a `TaskValue`, a `Plain` value, `Result<G, E>` with
+/// `E: IntoTaskError`, or a future resolving to any of them. `Output` is what the task outputs.
+pub trait TaskReturn<M>: sealed::Sealed<M> {
+    type Output: Serialize;
+
+    fn into_task_return(self) -> Result<Self::Output, TaskError>;
+}
+
+impl<G: TaskValue> sealed::Sealed<PlainReturn> for G {}
+
+impl<G: TaskValue> TaskReturn<PlainReturn> for G {
+    type Output = G;
+
+    fn into_task_return(self) -> Result<G, TaskError> {
+        Ok(self)
+    }
+}
+
+impl<G: Serialize> sealed::Sealed<WrappedReturn> for Plain<G> {}
+
+impl<G: Serialize> TaskReturn<WrappedReturn> for Plain<G> {
+    type Output = G;
+
+    fn into_task_return(self) -> Result<G, TaskError> {
+        Ok(self.0)
+    }
+}
+
+impl<R: TaskReturn<M>, E: IntoTaskError, M> sealed::Sealed<FallibleReturn<E, M>> for Result<R, E> {}
+
+impl<R: TaskReturn<M>, E: IntoTaskError, M> TaskReturn<FallibleReturn<E, M>> for Result<R, E> {
+    type Output = R::Output;
+
+    fn into_task_return(self) -> Result<Self::Output, TaskError> {
+        self.map_err(IntoTaskError::into_task_error)?
+            .into_task_return()
+    }
+}
+
+impl<M, Fut> sealed::Sealed<AsyncReturn<M>> for Fut
+where
+    Fut: Future,
+    Fut::Output: TaskReturn<M>,
+{
+}
+
+impl<M, Fut> TaskReturn<AsyncReturn<M>> for Fut
+where
+    Fut: Future,
+    Fut::Output: TaskReturn<M>,
+{
+    type Output = <Fut::Output as TaskReturn<M>>::Output;
+
+    fn into_task_return(self) -> Result<Self::Output, TaskError> {
+        get_runtime().block_on(self).into_task_return()
+    }
+}
+
+pub struct WithoutContext<M>(PhantomData<M>);
+pub struct WithContext<M>(PhantomData<M>);
+
+/// A function that can be added as a task, either `Fn(T) -> R` or `Fn(T, TaskContext) -> R`
+/// where `R` is any `TaskReturn`.
+///
+/// The marker `M` only exists so all kinds of functions can be accepted by the same
+/// `add_task`, `expand`, `expand_lazy` and `branch` functions and is always inferred.
+pub trait TaskFunction<T, G, M>: 'static + Sync + Send {
+    fn call(&self, input: T) -> Result<G, TaskError>;
+}
+
+impl<F, T, G, R, M> TaskFunction<T, G, WithoutContext<M>> for F
+where
+    F: Fn(T) -> R + 'static + Sync + Send,
+    R: TaskReturn<M, Output = G>,
+{
+    fn call(&self, input: T) -> Result<G, TaskError> {
+        self(input).into_task_return()
+    }
+}
+
+impl<F, T, G, R, M> TaskFunction<T, G, WithContext<M>> for F
+where
+    F: Fn(T, TaskContext) -> R + 'static + Sync + Send,
+    R: TaskReturn<M, Output = G>,
+{
+    fn call(&self, input: T) -> Result<G, TaskError> {
+        self(input, TaskContext::from_env()).into_task_return()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: String) -> Result<u8, String> {
+        input
+            .parse()
+            .map_err(|_| format!("'{input}' is not a number"))
+    }
+
+    // the output type comes from the return type, so neither call needs annotations
+    fn output_of<T, G, M, F: TaskFunction<T, G, M>>(
+        function: &F,
+        input: T,
+    ) -> Result<G, TaskError> {
+        function.call(input)
+    }
+
+    #[test]
+    fn test_result_with_string_error() {
+        let output: u8 = output_of(&parse, "7".into()).unwrap();
+        assert_eq!(output, 7);
+
+        let error = output_of(&parse, "x".into()).unwrap_err();
+        assert_eq!(error.message, "'x' is not a number");
+        assert_eq!(error.kind, "error");
+    }
+
+    #[test]
+    fn test_result_with_std_error() {
+        let read = |path: String| std::fs::read_to_string(path);
+        assert!(output_of(&read, "/nonexistent".into()).is_err());
+    }
+
+    #[test]
+    fn test_plain_result() {
+        struct Output(u8);
+        impl Serialize for Output {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.0.serialize(serializer)
+            }
+        }
+
+        let plain = |_: ()| Plain(Output(3));
+        assert_eq!(output_of(&plain, ()).unwrap().0, 3);
+
+        let fallible = |_: ()| -> Result<Plain<Option<u8>>, String> { Ok(Plain(None)) };
+        assert_eq!(output_of(&fallible, ()).unwrap(), None);
+    }
+}
@@ -0,0 +1,222 @@
+use std::collections::{HashMap, HashSet};
+
+use serde_json::Value;
+use thepipelinetool_task::Task;
+use thepipelinetool_utils::{UPSTREAM_TASK_ID_KEY, UPSTREAM_TASK_RESULT_KEY};
+
+use crate::check_for_cycles::check_for_cycles;
+
+// upstream task ids referenced in the args, with the key of their result if one is used
+fn get_references(value: &Value, references: &mut Vec<(u64, Option<String>)>) {
+    match value {
+        Value::Object(map) => match map.get(UPSTREAM_TASK_ID_KEY).and_then(|id| id.as_u64()) {
+            Some(id) => references.push((
+                id,
+                map.get(UPSTREAM_TASK_RESULT_KEY)
+                    .and_then(|key| key.as_str())
+                    .map(|key| key.to_string()),
+            )),
+            None => map.values().for_each(|v| get_references(v, references)),
+        },
+        Value::Array(array) => array.iter().for_each(|v| get_references(v, references)),
+        _ => {}
+    }
+}
+
+fn is_upstream(upstream_id: usize, task_id: usize, edges: &HashSet<(usize, usize)>) -> bool {
+    let mut visited = HashSet::new();
+    let mut to_visit = vec![task_id];
+    while let Some(curr) = to_visit.pop() {
+        for (up, _) in edges.iter().filter(|(_, down)| *down == curr) {
+            if *up == upstream_id {
+                return true;
+            }
+            if visited.insert(*up) {
+                to_visit.push(*up);
+            }
+        }
+    }
+    false
+}
+
+pub fn validate_tasks(tasks: &[Task], edges: &HashSet<(usize, usize)>) -> Vec<String> {
+    let mut problems = vec![];
+
+    // referenced tasks become upstream when the run starts, see update_referenced_dependencies
+    let mut references_by_task = vec![];
+    let mut run_edges = edges.clone();
+    for task in tasks {
+        let mut references = vec![];
+        get_references(&task.template_args, &mut references);
+        for (id, _) in &references {
+            if (*id as usize) < tasks.len() && *id as usize != task.id {
+                run_edges.insert((*id as usize, task.id));
+            }
+        }
+        references_by_task.push(references);
+    }
+
+    if let Some(err) = check_for_cycles(tasks, &run_edges) {
+        problems.push(err);
+    }
+
+    let mut tasks_by_id: HashMap<usize, Vec<&Task>> = HashMap::new();
+    for task in tasks {
+        tasks_by_id.entry(task.id).or_default().push(task);
+    }
+    let mut duplicate_ids: Vec<(&usize, &Vec<&Task>)> = tasks_by_id
+        .iter()
+        .filter(|(_, tasks)| tasks.len() > 1)
+        .collect();
+    duplicate_ids.sort_by_key(|(id, _)| **id);
+    for (id, tasks) in duplicate_ids {
+        problems.push(format!(
+            "duplicate task id {id} used by tasks {}",
+            tasks
+                .iter()
+                .map(|task| format!("'{}'", task.key))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ));
+    }
+
+    // tasks sharing a name, e.g. from `expand`, are told apart by their key
+    let mut tasks_by_key: HashMap<&str, Vec<&Task>> = HashMap::new();
+    for task in tasks {
+        tasks_by_key.entry(&task.key).or_default().push(task);
+    }
+    let mut duplicate_keys: Vec<(&&str, &Vec<&Task>)> = tasks_by_key
+        .iter()
+        .filter(|(_, tasks)| tasks.len() > 1)
+        .collect();
+    duplicate_keys.sort_by_key(|(key, _)| **key);
+    for (key, tasks) in duplicate_keys {
+        problems.push(format!(
+            "duplicate task key '{key}' used by tasks {}",
+            tasks
+                .iter()
+                .map(|task| format!("({}_{})", task.name, task.id))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ));
+    }
+
+    for (task, references) in tasks.iter().zip(&references_by_task) {
+        for (id, key) in references {
+            match tasks.get(*id as usize) {
+                None => problems.push(format!(
+                    "task '{}' references unknown task id {id}",
+                    task.key
+                )),
+                // the reference itself is an edge, so it is only wrong for tasks running after it
+                Some(upstream)
+                    if upstream.id == task.id || is_upstream(task.id, upstream.id, &run_edges) =>
+                {
+                    problems.push(format!(
+                        "task '{}' references task '{}', which is downstream of it",
+                        task.key, upstream.key
+                    ))
+                }
+                Some(upstream) if upstream.lazy_expand && key.is_some() => {
+                    problems.push(format!(
+                        "task '{}' references key '{}' of lazy_expand task '{}', whose result is a list",
+                        task.key,
+                        key.as_deref().unwrap_or_default(),
+                        upstream.key
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        if task.lazy_expand {
+            let upstream_count = edges.iter().filter(|e| e.1 == task.id).count();
+            if upstream_count != 1 {
+                problems.push(format!(
+                    "lazy_expand task '{}' must depend on exactly one task, found {upstream_count}",
+                    task.key
+                ));
+            }
+        }
+
+        // branch results pick between the branch's two downstream tasks, left before right
+        if task.is_branch {
+            let target_count = edges.iter().filter(|e| e.0 == task.id).count();
+            if target_count != 2 {
+                problems.push(format!(
+                    "branch task '{}' must have exactly two downstream tasks, found {target_count}",
+                    task.key
+                ));
+            }
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_duplicate_keys() {
+        // tasks sharing a name are fine as long as their keys differ
+        let mut tasks = [
+            Task::dummy(0, "load"),
+            Task {
+                key: "load_1".into(),
+                ..Task::dummy(1, "load")
+            },
+        ];
+        assert!(validate_tasks(&tasks, &HashSet::new()).is_empty());
+
+        tasks[1].key = tasks[0].key.clone();
+        assert_eq!(
+            validate_tasks(&tasks, &HashSet::new()),
+            vec!["duplicate task key 'load' used by tasks (load_0), (load_1)"]
+        );
+    }
+
+    #[test]
+    fn test_branch_targets() {
+        let mut tasks = [
+            Task::dummy(0, "pick"),
+            Task::dummy(1, "left"),
+            Task::dummy(2, "right"),
+        ];
+        tasks[0].is_branch = true;
+
+        // the targets don't have to directly follow the branch
+        assert!(validate_tasks(&tasks, &HashSet::from([(0, 1), (0, 2)])).is_empty());
+        assert_eq!(
+            validate_tasks(&tasks, &HashSet::from([(0, 2)])),
+            vec!["branch task 'pick' must have exactly two downstream tasks, found 1"]
+        );
+    }
+
+    #[test]
+    fn test_references() {
+        let tasks = [
+            Task::dummy(0, "a"),
+            Task {
+                template_args: json!([{ UPSTREAM_TASK_ID_KEY: 0, UPSTREAM_TASK_RESULT_KEY: "x" }]),
+                ..Task::dummy(1, "b")
+            },
+            Task {
+                template_args: json!({ UPSTREAM_TASK_ID_KEY: 7 }),
+                ..Task::dummy(2, "c")
+            },
+        ];
+        assert_eq!(
+            validate_tasks(&tasks, &HashSet::new()),
+            vec!["task 'c' references unknown task id 7"]
+        );
+
+        // a depends on b, which references a
+        let problems = validate_tasks(&tasks[..2], &HashSet::from([(1, 0)]));
+        assert!(problems
+            .contains(&"task 'b' references task 'a', which is downstream of it".to_string()));
+    }
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use thepipelinetool_utils::run_bash_command;
+use thepipelinetool_utils::{read_script_result, run_bash_command};
 
 use crate::ORIGINAL_STRING_KEY;
 
@@ -10,8 +10,10 @@ pub struct TemplateBashTaskArgs {
     pub script: String,
 }
 
+// scripts may write their result to $TPT_RESULT_PATH, otherwise their stdout is the result
+// as it was before the result file existed
 pub fn bash_operator(args: Value) -> Value {
-    if args.is_object() {
+    let output = if args.is_object() {
         let mut command_string = args[ORIGINAL_STRING_KEY].as_str().unwrap().to_string();
 
         for (k, v) in args.as_object().unwrap() {
@@ -21,7 +23,7 @@ pub fn bash_operator(args: Value) -> Value {
         }
 
         println!("bash_operator$ {}", command_string);
-        run_bash_command(&["bash", "-c", &command_string], true, true)
+        run_bash_command(&["bash", "-c", &command_string], false, true)
     } else {
         println!("bash_operator$ {}", args);
         let args = args
@@ -31,6 +33,7 @@ pub fn bash_operator(args: Value) -> Value {
             .map(|v| v.as_str().unwrap())
             .collect::<Vec<&str>>();
 
-        run_bash_command(&args, true, true)
-    }
+        run_bash_command(&args, false, true)
+    };
+    read_script_result().unwrap_or(output)
 }
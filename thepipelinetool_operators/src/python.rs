@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use thepipelinetool_utils::run_bash_command;
+use thepipelinetool_utils::{read_script_result, run_bash_command};
 
 use crate::ORIGINAL_STRING_KEY;
 
@@ -15,6 +15,7 @@ pub struct TemplatePythonArgs {
     pub script: String,
 }
 
+// like bash scripts, python scripts may write their result to $TPT_RESULT_PATH
 pub fn python_operator(args: Value) -> Value {
     let mut command_string = args[ORIGINAL_STRING_KEY].as_str().unwrap().to_string();
 
@@ -34,5 +35,6 @@ pub fn python_operator(args: Value) -> Value {
     }
 
     // println!("python_operator$\n{}", command_string);
-    run_bash_command(&["bash", "-c", &formatted_command_string], false, true)
+    let output = run_bash_command(&["bash", "-c", &formatted_command_string], false, true);
+    read_script_result().unwrap_or(output)
 }
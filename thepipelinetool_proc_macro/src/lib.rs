@@ -1,7 +1,11 @@
 extern crate proc_macro;
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, ItemFn};
+use quote::{format_ident, quote, quote_spanned};
+use syn::{ext::IdentExt, spanned::Spanned};
+use syn::{
+    meta::ParseNestedMeta, parse_macro_input, Data, DeriveInput, Fields, FnArg, GenericArgument,
+    ItemFn, LitBool, LitInt, LitStr, PathArguments, ReturnType, Type, TypeParamBound,
+};
 
 #[proc_macro_attribute]
 pub fn main(_attr: TokenStream, item: TokenStream) -> TokenStream {
@@ -12,3 +16,439 @@ pub fn main(_attr: TokenStream, item: TokenStream) -> TokenStream {
     TokenStream::from(quote!(#input_fn))
 }
 
+// skips the value of a serde attribute we do not care about, e.g. `default = "..."`
+fn skip_meta_value(meta: &ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        let content;
+        syn::parenthesized!(content in meta.input);
+        content.parse::<proc_macro2::TokenStream>()?;
+    }
+    Ok(())
+}
+
+// the serialized name in `rename = "..."` or `rename(serialize = "...")`
+fn serialize_name(meta: &ParseNestedMeta) -> syn::Result<Option<LitStr>> {
+    if meta.input.peek(syn::Token![=]) {
+        return Ok(Some(meta.value()?.parse()?));
+    }
+    let mut name = None;
+    meta.parse_nested_meta(|meta| {
+        if meta.path.is_ident("serialize") {
+            name = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            skip_meta_value(&meta)
+        }
+    })?;
+    Ok(name)
+}
+
+// "all_success" -> AllSuccess
+fn pascal_case(value: &str) -> String {
+    value
+        .split('_')
+        .map(|w| {
+            let mut chars = w.chars();
+            match chars.next() {
+                Some(c) => c.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+// renames a snake_case field the way serde(rename_all) does
+fn rename_field(field: &str, rule: &LitStr) -> syn::Result<String> {
+    Ok(match rule.value().as_str() {
+        "lowercase" | "snake_case" => field.to_string(),
+        "UPPERCASE" | "SCREAMING_SNAKE_CASE" => field.to_ascii_uppercase(),
+        "PascalCase" => pascal_case(field),
+        "camelCase" => {
+            let pascal_case = pascal_case(field);
+            let mut chars = pascal_case.chars();
+            match chars.next() {
+                Some(c) => c.to_lowercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        }
+        "kebab-case" => field.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => field.replace('_', "-").to_ascii_uppercase(),
+        _ => return Err(syn::Error::new_spanned(rule, "unknown serde(rename_all) rule")),
+    })
+}
+
+/// Generates a `<Name>TaskRef` trait with one accessor per serialized field, implemented for
+/// `TaskRef<Name>`, so `task_ref.field()` returns a `TaskRef<FieldType>` checked at compile time.
+/// Also implements `TaskValue`, so tasks can return the struct.
+///
+/// Follows `serde(rename, rename_all, skip)`, `serde(flatten)` is not supported.
+#[proc_macro_derive(TaskOutput, attributes(serde))]
+pub fn task_output(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    TokenStream::from(task_output_impl(&input).unwrap_or_else(|err| err.to_compile_error()))
+}
+
+fn task_output_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let vis = &input.vis;
+    let trait_name = format_ident!("{}TaskRef", name);
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(name, "TaskOutput requires named fields")),
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "TaskOutput can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut rename_all = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename_all") {
+                rename_all = serialize_name(&meta)?;
+                Ok(())
+            } else {
+                skip_meta_value(&meta)
+            }
+        })?;
+    }
+
+    let mut signatures = vec![];
+    let mut accessors = vec![];
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+
+        // the result key is the serialized field name
+        let field_name = ident.unraw().to_string();
+        let mut key = match &rename_all {
+            Some(rule) => rename_field(&field_name, rule)?,
+            None => field_name,
+        };
+        let mut skip = false;
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    if let Some(name) = serialize_name(&meta)? {
+                        key = name.value();
+                    }
+                } else if meta.path.is_ident("skip") || meta.path.is_ident("skip_serializing") {
+                    skip = true;
+                } else if meta.path.is_ident("flatten") {
+                    return Err(meta.error("TaskOutput does not support serde(flatten)"));
+                } else {
+                    skip_meta_value(&meta)?;
+                }
+                Ok(())
+            })?;
+        }
+        if skip {
+            continue;
+        }
+
+        signatures.push(quote! {
+            fn #ident(&self) -> ::thepipelinetool_core::TaskRef<#ty>;
+        });
+        accessors.push(quote! {
+            fn #ident(&self) -> ::thepipelinetool_core::TaskRef<#ty> {
+                self._get_typed(#key)
+            }
+        });
+    }
+
+    Ok(quote! {
+        #vis trait #trait_name {
+            #(#signatures)*
+        }
+
+        impl #trait_name for ::thepipelinetool_core::TaskRef<#name> {
+            #(#accessors)*
+        }
+
+        impl ::thepipelinetool_core::prelude::TaskValue for #name {}
+    })
+}
+
+// parses durations like "500ms", "30s", "5m" or "1h"
+fn parse_duration(lit: &LitStr) -> syn::Result<proc_macro2::TokenStream> {
+    let value = lit.value();
+    let unit_start = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let amount = value[..unit_start]
+        .parse::<u64>()
+        .map_err(|_| syn::Error::new_spanned(lit, "expected a duration like \"30s\""))?;
+
+    let millis = match &value[unit_start..] {
+        "ms" => amount,
+        "s" | "" => amount * 1000,
+        "m" => amount * 60 * 1000,
+        "h" => amount * 60 * 60 * 1000,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                lit,
+                "unknown duration unit, expected one of ms, s, m, h",
+            ))
+        }
+    };
+    Ok(quote!(::std::time::Duration::from_millis(#millis)))
+}
+
+// the variant for one of the snake_case `variants`
+fn variant_ident(lit: &LitStr, variants: &[&str]) -> syn::Result<syn::Ident> {
+    let value = lit.value();
+    if !variants.contains(&value.as_str()) {
+        return Err(syn::Error::new_spanned(
+            lit,
+            format!("expected one of {}", variants.join(", ")),
+        ));
+    }
+    Ok(format_ident!("{}", pascal_case(&value), span = lit.span()))
+}
+
+// the type of the first generic argument of the last path segment named `ident`,
+// e.g. `u8` for `Result<u8, E>`
+fn first_type_argument<'a>(ty: &'a Type, ident: &str) -> Option<&'a Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != ident {
+        return None;
+    }
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) => match args.args.first()? {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// the type a task outputs, unwrapped from `impl Future<Output = ...>` and `Result` the same way
+// `TaskReturn` does, so `TaskRef`s of tasks declared with and without the attribute match.
+// only paths ending in `Result` are unwrapped, aliases need the `output` option
+fn output_type(ty: &Type) -> &Type {
+    if let Type::ImplTrait(impl_trait) = ty {
+        for bound in &impl_trait.bounds {
+            let TypeParamBound::Trait(bound) = bound else {
+                continue;
+            };
+            let Some(segment) = bound.path.segments.last() else {
+                continue;
+            };
+            if segment.ident != "Future" {
+                continue;
+            }
+            if let PathArguments::AngleBracketed(args) = &segment.arguments {
+                for arg in &args.args {
+                    if let GenericArgument::AssocType(assoc) = arg {
+                        if assoc.ident == "Output" {
+                            return output_type(&assoc.ty);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    first_type_argument(ty, "Result").unwrap_or(ty)
+}
+
+/// Declares a task next to its function, e.g.
+/// `#[tpt::task(retries = 3, timeout = "5m", trigger_rule = "all_success", name = "...")]`.
+///
+/// `output = "u8"` sets the output when it can't be read off the return type, e.g. for a type
+/// alias of `Result`.
+///
+/// Keeps the function as is and generates `<function>_options()`, `<function>_task(args)`
+/// and `<function>_task_with_ref(&task_ref)`, which add the task with these options. Their
+/// `TaskRef`s hold the output of the task, e.g. `u8` for an `async fn` returning `Result<u8, E>`.
+#[proc_macro_attribute]
+pub fn task(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let input_fn = parse_macro_input!(item as ItemFn);
+
+    let mut name: Option<LitStr> = None;
+    let mut output: Option<Type> = None;
+    let mut options = vec![];
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            name = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("output") {
+            output = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+        } else if meta.path.is_ident("retries") {
+            let retries: LitInt = meta.value()?.parse()?;
+            options.push(quote!(max_attempts: #retries + 1));
+        } else if meta.path.is_ident("retry_delay") {
+            let retry_delay = parse_duration(&meta.value()?.parse()?)?;
+            options.push(quote!(retry_delay: #retry_delay));
+        } else if meta.path.is_ident("timeout") {
+            let timeout = parse_duration(&meta.value()?.parse()?)?;
+            options.push(quote!(timeout: Some(#timeout)));
+        } else if meta.path.is_ident("expected_duration") {
+            let expected_duration = parse_duration(&meta.value()?.parse()?)?;
+            options.push(quote!(expected_duration: Some(#expected_duration)));
+        } else if meta.path.is_ident("is_sensor") {
+            let is_sensor: LitBool = meta.value()?.parse()?;
+            options.push(quote!(is_sensor: #is_sensor));
+        } else if meta.path.is_ident("trigger_rule") {
+            let lit: LitStr = meta.value()?.parse()?;
+            let variant = variant_ident(
+                &lit,
+                &["all_done", "any_done", "all_success", "any_success", "any_failed", "all_failed"],
+            )?;
+            options.push(quote_spanned!(lit.span()=>
+                trigger_rule: ::thepipelinetool_core::prelude::TriggerRule::#variant
+            ));
+        } else if meta.path.is_ident("kind") {
+            let lit: LitStr = meta.value()?.parse()?;
+            let variant = variant_ident(&lit, &["normal", "setup", "teardown"])?;
+            options.push(quote_spanned!(lit.span()=>
+                kind: ::thepipelinetool_core::prelude::TaskKind::#variant
+            ));
+        } else {
+            return Err(meta.error(
+                "expected one of name, output, retries, retry_delay, timeout, expected_duration, is_sensor, trigger_rule, kind",
+            ));
+        }
+        Ok(())
+    });
+    parse_macro_input!(attr with parser);
+
+    let sig = &input_fn.sig;
+    if !sig.generics.params.is_empty() {
+        return syn::Error::new_spanned(&sig.generics, "tasks cannot be generic")
+            .to_compile_error()
+            .into();
+    }
+    // the optional second argument is the TaskContext
+    let arg_type = match (sig.inputs.len(), sig.inputs.first()) {
+        (1 | 2, Some(FnArg::Typed(arg))) => &arg.ty,
+        _ => {
+            return syn::Error::new_spanned(
+                &sig.inputs,
+                "tasks take one argument and an optional TaskContext",
+            )
+            .to_compile_error()
+            .into()
+        }
+    };
+    // the return type of `async fn`s already is the output of their future
+    let return_type = match (&output, &sig.output) {
+        (Some(ty), _) => quote!(#ty),
+        (None, ReturnType::Default) => quote!(()),
+        (None, ReturnType::Type(_, ty)) => {
+            let ty = output_type(ty);
+            quote!(#ty)
+        }
+    };
+    // points mismatches, e.g. from a type alias of `Result`, at the return type
+    let return_span = match &sig.output {
+        ReturnType::Default => sig.ident.span(),
+        ReturnType::Type(_, ty) => ty.span(),
+    };
+
+    let vis = &input_fn.vis;
+    let ident = &sig.ident;
+    let options_ident = format_ident!("{}_options", ident);
+    let task_ident = format_ident!("{}_task", ident);
+    let task_with_ref_ident = format_ident!("{}_task_with_ref", ident);
+    let task_name = match name {
+        Some(name) => quote!(#name),
+        None => quote!(&function_name),
+    };
+
+    let register_function = quote_spanned!(return_span=>
+        ::thepipelinetool_core::prelude::register_function::<#return_type, #arg_type, _, _>(#ident)
+    );
+
+    TokenStream::from(quote! {
+        #input_fn
+
+        #vis fn #options_ident() -> ::thepipelinetool_core::prelude::TaskOptions {
+            ::thepipelinetool_core::prelude::TaskOptions {
+                #(#options,)*
+                ..::std::default::Default::default()
+            }
+        }
+
+        #vis fn #task_ident(
+            template_args: #arg_type,
+        ) -> ::thepipelinetool_core::TaskRef<#return_type> {
+            let function_name = #register_function;
+            ::thepipelinetool_core::dev::_add_task_with_function_name::<#arg_type, #return_type>(
+                ::thepipelinetool_core::prelude::json!(template_args),
+                &#options_ident(),
+                #task_name,
+                &function_name,
+                false,
+            )
+        }
+
+        #vis fn #task_with_ref_ident(
+            task_ref: &::thepipelinetool_core::TaskRef<#arg_type>,
+        ) -> ::thepipelinetool_core::TaskRef<#return_type> {
+            let function_name = #register_function;
+            task_ref
+                >> ::thepipelinetool_core::dev::_add_task_with_function_name::<#arg_type, #return_type>(
+                    ::thepipelinetool_core::prelude::json!(task_ref),
+                    &#options_ident(),
+                    #task_name,
+                    &function_name,
+                    false,
+                )
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(input: DeriveInput) -> String {
+        match task_output_impl(&input) {
+            Ok(tokens) => tokens.to_string(),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_task_output_keys() {
+        let expanded = expand(syn::parse_quote! {
+            #[serde(rename_all = "camelCase")]
+            struct User {
+                user_name: String,
+                #[serde(rename = "years")]
+                age: u8,
+                #[serde(skip)]
+                password: String,
+                #[serde(skip_serializing, default)]
+                token: String,
+            }
+        });
+        assert!(expanded.contains(
+            "fn user_name (& self) -> :: thepipelinetool_core :: TaskRef < String > \
+             { self . _get_typed (\"userName\") }"
+        ));
+        assert!(expanded.contains("self . _get_typed (\"years\")"));
+        assert!(!expanded.contains("password"));
+        assert!(!expanded.contains("token"));
+    }
+
+    #[test]
+    fn test_task_output_rejects_flatten() {
+        let expanded = expand(syn::parse_quote! {
+            struct User {
+                #[serde(flatten)]
+                extra: Extra,
+            }
+        });
+        assert_eq!(expanded, "TaskOutput does not support serde(flatten)");
+    }
+}
@@ -4,17 +4,37 @@ use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde_json::Value;
 use thepipelinetool_task::{
-    task_options::TaskOptions, task_result::TaskResult, task_status::TaskStatus,
-    temp_queued_task::TempQueuedTask, Task,
+    connection::Connection,
+    log_line::LogLine,
+    task_callback::{CallbackEvent, QueuedCallback},
+    task_options::TaskOptions,
+    task_result::TaskResult,
+    task_status::TaskStatus,
+    temp_queued_task::TempQueuedTask,
+    Task,
 };
 
-use crate::run::Run;
+use crate::{
+    notifications::Notification,
+    pipeline_options::PipelineOptions,
+    result_store::ResultOffload,
+    run::{Run, RunEvent},
+};
 
 pub type UpstreamId = usize;
 pub type DownstreamId = usize;
 pub type OriginalKey = String;
 pub type ResultKey = String;
 
+/// Where a variable lives: shared by all pipelines, by all runs of the pipeline or only by a
+/// single run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VariableScope {
+    Global,
+    Pipeline,
+    Run(usize),
+}
+
 pub trait Backend {
     fn get_pipeline_path(&self) -> Result<String>;
     fn get_pipeline_name(&self) -> Result<String>;
@@ -32,38 +52,77 @@ pub trait Backend {
         pipeline_name: String,
         is_dynamic: bool,
     ) -> Result<()>;
-
-    fn get_log(&mut self, run_id: usize, task_id: usize, attempt: usize) -> Result<String>;
+    // callbacks are run by a worker rather than where the attempt was handled
+    fn enqueue_callbacks(&mut self, queued_callback: &QueuedCallback) -> Result<()>;
+    // popped callbacks are kept until they are removed once they ran, like popped tasks
+    fn pop_callback_queue(&mut self) -> Result<Option<QueuedCallback>>;
+    fn remove_from_callback_temp_queue(&self, queued_callback: &QueuedCallback) -> Result<()>;
+    // notifications are sent by a worker rather than while the result is handled
+    fn enqueue_notification(&mut self, notification: &Notification) -> Result<()>;
+    fn pop_notification_queue(&mut self) -> Result<Option<Notification>>;
+
+    // lines of the attempt after the first `offset` lines
+    fn get_log_lines(
+        &mut self,
+        run_id: usize,
+        task_id: usize,
+        attempt: usize,
+        offset: usize,
+    ) -> Result<Vec<LogLine>>;
     fn get_log_handle_closure(
         &mut self,
         run_id: usize,
         task_id: usize,
         attempt: usize,
-    ) -> Result<Box<dyn Fn(String) -> Result<()> + Send>>;
-    fn take_last_stdout_line(
+    ) -> Result<Box<dyn Fn(LogLine) -> Result<()> + Send>>;
+    // callbacks log apart from the attempt that triggered them
+    fn get_callback_log_lines(
         &mut self,
         run_id: usize,
         task_id: usize,
         attempt: usize,
-    ) -> Result<Box<dyn Fn() -> Result<String> + Send>>;
+        event: CallbackEvent,
+    ) -> Result<Vec<LogLine>>;
+    fn get_callback_log_handle_closure(
+        &mut self,
+        run_id: usize,
+        task_id: usize,
+        attempt: usize,
+        event: CallbackEvent,
+    ) -> Result<Box<dyn Fn(LogLine) -> Result<()> + Send>>;
 
     fn get_task_result(&mut self, run_id: usize, task_id: usize) -> Result<TaskResult>;
     fn insert_task_results(&mut self, run_id: usize, result: &TaskResult) -> Result<()>;
 
     fn get_task_status(&self, run_id: usize, task_id: usize) -> Result<TaskStatus>;
+    // also appends the transition to the run's events
     fn set_task_status(
         &mut self,
         run_id: usize,
         task_id: usize,
         task_status: TaskStatus,
+        reason: Option<&str>,
     ) -> Result<()>;
 
+    // who performed the changes of this backend, recorded in run events
+    fn get_actor(&self) -> String;
+    fn append_run_event(&mut self, run_id: usize, event: &RunEvent) -> Result<()>;
+    fn get_run_events(&self, run_id: usize) -> Result<Vec<RunEvent>>;
+    // records when the run completed, false if it was already recorded by another worker
+    fn set_run_end_date(&mut self, run_id: usize, end_date: DateTime<Utc>) -> Result<bool>;
+
     fn get_downstream(&self, run_id: usize, task_id: usize) -> Result<Vec<DownstreamId>>;
     fn get_upstream(&self, run_id: usize, task_id: usize) -> Result<Vec<UpstreamId>>;
 
     fn get_default_tasks(&self) -> Result<Vec<Task>>;
     fn get_all_tasks(&self, run_id: usize) -> Result<Vec<Task>>;
     fn get_default_edges(&self) -> Result<HashSet<(UpstreamId, DownstreamId)>>;
+    fn get_current_pipeline_hash(&self) -> Result<String>;
+    fn get_tasks_by_pipeline_hash(&self, pipeline_hash: &str) -> Result<Vec<Task>>;
+    fn get_edges_by_pipeline_hash(
+        &self,
+        pipeline_hash: &str,
+    ) -> Result<HashSet<(UpstreamId, DownstreamId)>>;
     fn get_task_by_id(&self, run_id: usize, task_id: usize) -> Result<Task>;
     fn get_template_args(&self, run_id: usize, task_id: usize) -> Result<Value>;
 
@@ -97,7 +156,32 @@ pub trait Backend {
         is_dynamic: bool,
     ) -> Result<usize>;
 
-    fn create_new_run(&mut self, scheduled_date_for_run: DateTime<Utc>) -> Result<Run>;
+    fn create_new_run(
+        &mut self,
+        scheduled_date_for_run: DateTime<Utc>,
+        pipeline_hash: &str,
+    ) -> Result<Run>;
+
+    fn get_variables(&self, scope: VariableScope) -> Result<HashMap<String, Value>>;
+    fn get_variable(&self, scope: VariableScope, key: &str) -> Result<Option<Value>>;
+    fn set_variable(&mut self, scope: VariableScope, key: &str, value: &Value) -> Result<()>;
+    fn delete_variable(&mut self, scope: VariableScope, key: &str) -> Result<()>;
+
+    fn get_secret_names(&self) -> Result<Vec<String>>;
+    fn get_secret(&self, name: &str) -> Result<Option<String>>;
+    fn set_secret(&mut self, name: &str, value: &str) -> Result<()>;
+    fn delete_secret(&mut self, name: &str) -> Result<()>;
+
+    fn get_result_offload(&self) -> Result<Option<ResultOffload>>;
+    fn get_pipeline_options(&self) -> Result<PipelineOptions>;
+
+    fn get_connection_ids(&self) -> Result<Vec<String>>;
+    fn get_connection(&self, id: &str) -> Result<Option<Connection>>;
+    fn set_connection(&mut self, id: &str, connection: &Connection) -> Result<()>;
+    fn delete_connection(&mut self, id: &str) -> Result<()>;
+
+    fn get_trigger_params(&self, run_id: usize) -> Result<Value>;
+    fn set_trigger_params(&mut self, run_id: usize, trigger_params: &Value) -> Result<()>;
 
     fn remove_edge(&mut self, run_id: usize, edge: (usize, usize)) -> Result<()>;
     fn insert_edge(&mut self, run_id: usize, edge: (usize, usize)) -> Result<()>;
@@ -105,6 +189,7 @@ pub trait Backend {
     fn append_new_task_and_set_status_to_pending(
         &mut self,
         run_id: usize,
+        key: &str,
         name: &str,
         function_name: &str,
         template_args: &Value,
@@ -113,5 +198,6 @@ pub trait Backend {
         is_dynamic: bool,
         is_branch: bool,
         use_trigger_params: bool,
+        connections: &[String],
     ) -> Result<usize>;
 }
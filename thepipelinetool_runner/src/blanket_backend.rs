@@ -3,28 +3,60 @@ use std::{
     ffi::OsStr,
 };
 
-use chrono::{DateTime, Utc};
+use chrono::Utc;
+use opentelemetry::{
+    trace::{Status, TraceContextExt, Tracer},
+    Context, KeyValue,
+};
 use serde_json::{json, Value};
 use thepipelinetool_task::{
-    queued_task::QueuedTask, task_ref_inner::TaskRefInner, task_result::TaskResult,
-    task_status::TaskStatus, temp_queued_task::TempQueuedTask, trigger_rule::TriggerRule, Task,
+    connection::Connection,
+    log_line::{render_log_lines, LogLine, LogStream},
+    queued_task::QueuedTask,
+    task_callback::{CallbackContext, CallbackEvent, QueuedCallback},
+    task_context::{TaskContext, TaskVariables},
+    task_kind::TaskKind,
+    task_options::TaskOptions,
+    task_ref_inner::TaskRefInner,
+    task_result::TaskResult,
+    task_status::TaskStatus,
+    temp_queued_task::TempQueuedTask,
+    trigger_rule::TriggerRule,
+    Task,
 };
 use thepipelinetool_utils::{
-    collector, function_name_as_string, UPSTREAM_TASK_ID_KEY, UPSTREAM_TASK_RESULT_KEY,
+    collector, function_name_as_string, value_from_file, value_to_file, UPSTREAM_TASK_ID_KEY,
+    UPSTREAM_TASK_RESULT_KEY,
 };
 
 use crate::{
-    run::{Run, RunStatus},
+    backend::VariableScope,
+    notifications::{Notification, NotificationConfig, NotificationEvent, NotificationSink},
+    run::{Run, RunEvent, RunStatus},
+    telemetry::{get_traceparent, get_tracer, record_error},
+    template_variables::{
+        redact_secrets, redact_secrets_in_value, render_fields_in_value,
+        resolve_variable_references, SECRET_REFERENCE_PREFIX, VARIABLE_REFERENCE_PREFIX,
+    },
     Backend,
 };
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
 pub trait BlanketBackend {
     fn trigger_rules_satisfied(&mut self, run_id: usize, task_id: usize) -> Result<bool>;
+    // all tasks between the teardown and its upstream setups are done, or all of its upstream
+    // tasks when it has no setup
+    fn teardown_ready(&mut self, run_id: usize, task_id: usize) -> Result<bool>;
 
     fn get_run_status(&mut self, run_id: usize) -> Result<RunStatus>;
+    // no task of the run is running or can still be scheduled
+    fn is_run_complete(&mut self, run_id: usize) -> Result<bool>;
+    // plain-text rendering of all lines of the attempt
+    fn get_log(&mut self, run_id: usize, task_id: usize, attempt: usize) -> Result<String>;
 
     fn is_task_done(&mut self, run_id: usize, task_id: usize) -> Result<bool>;
+    // all upstream tasks are done, so the trigger rule of the task can't change anymore
+    fn upstream_done(&mut self, run_id: usize, task_id: usize) -> Result<bool>;
     fn task_needs_running(&mut self, run_id: usize, task_id: usize) -> Result<bool>;
     fn enqueue_run(&mut self, run: &Run, trigger_params: Option<Value>) -> Result<()>;
     // TODO move tpt_path into OrderedQueuedTask?
@@ -33,19 +65,36 @@ pub trait BlanketBackend {
         -> Result<()>;
     fn run_task<D: AsRef<OsStr>>(
         &mut self,
-        run_id: usize,
+        queued_task: &QueuedTask,
         task: &Task,
-        attempt: usize,
         resolution_result: &Value,
+        secrets: &[String],
         tpt_path: D,
-        scheduled_date_for_run: DateTime<Utc>,
     ) -> Result<TaskResult>;
+    // resolves '{{ var.name }}' and '{{ secret.name }}' in `value`, collecting the secrets used
+    fn resolve_variables(&mut self, value: &Value, secrets: &mut Vec<String>) -> Result<Value>;
+    // only the task's own template args are resolved, upstream results and trigger params are
+    // substituted as they are
+    fn resolve_task_args(
+        &mut self,
+        run_id: usize,
+        task: &Task,
+        secrets: &mut Vec<String>,
+    ) -> Result<Value>;
     fn resolve_args(
         &mut self,
         run_id: usize,
         template_args: &Value,
         upstream_deps: &HashMap<(usize, String), String>,
     ) -> Result<Value>;
+    fn update_variables(
+        &mut self,
+        run_id: usize,
+        variables: &TaskVariables,
+        updated_variables: &TaskVariables,
+    ) -> Result<()>;
+    // reads a result written to the result store back into `result`, keeping `result_ref`
+    fn load_result(&self, task_result: &mut TaskResult) -> Result<()>;
 
     fn handle_task_result(
         &mut self,
@@ -53,16 +102,52 @@ pub trait BlanketBackend {
         queued_task: &QueuedTask,
         result: TaskResult,
     ) -> Result<()>;
+    // queues the callbacks of the task for `event`, if it has any
+    fn trigger_callbacks(
+        &mut self,
+        queued_task: &QueuedTask,
+        event: CallbackEvent,
+        result: &TaskResult,
+    ) -> Result<()>;
+    // runs the queued callbacks one after another, failures are only printed
+    fn run_callbacks<D: AsRef<OsStr>>(
+        &mut self,
+        queued_callback: &QueuedCallback,
+        tpt_path: D,
+    ) -> Result<()>;
+    // queues the notification when a sink is configured for its event
+    fn notify(
+        &mut self,
+        notifications: &[NotificationConfig],
+        notification: Notification,
+    ) -> Result<()>;
+    // sends a queued notification to every sink configured for its event, failed deliveries
+    // are recorded as run events
+    fn send_notification(&mut self, notification: &Notification) -> Result<()>;
 }
 
 impl<U: Backend + Send + Sync> BlanketBackend for U {
+    fn get_log(&mut self, run_id: usize, task_id: usize, attempt: usize) -> Result<String> {
+        Ok(render_log_lines(
+            &self.get_log_lines(run_id, task_id, attempt, 0)?,
+        ))
+    }
+
     fn get_run_status(&mut self, run_id: usize) -> Result<RunStatus> {
         let mut pending_count = 0;
         let tasks = self.get_all_tasks(run_id)?;
 
+        let mut task_count = 0;
+
         for task in &tasks {
             let status = self.get_task_status(run_id, task.id)?;
 
+            // a successful teardown doesn't decide the outcome of the run
+            if task.options.kind == TaskKind::Teardown && status == TaskStatus::Success {
+                continue;
+            }
+            task_count += 1;
+
             match status {
                 TaskStatus::Failure => return Ok(RunStatus::Failed),
                 TaskStatus::Pending | TaskStatus::RetryPending => {
@@ -71,7 +156,8 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
                 _ => {}
             };
         }
-        if pending_count == tasks.len() {
+        // a run with only successful teardowns left to count has succeeded
+        if task_count > 0 && pending_count == task_count {
             Ok(RunStatus::Pending)
         } else if pending_count > 0 {
             Ok(RunStatus::Running)
@@ -79,8 +165,65 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
             Ok(RunStatus::Success)
         }
     }
+    fn is_run_complete(&mut self, run_id: usize) -> Result<bool> {
+        for task in self.get_all_tasks(run_id)? {
+            match self.get_task_status(run_id, task.id)? {
+                TaskStatus::Running | TaskStatus::RetryPending => return Ok(false),
+                // pending tasks whose upstream tasks are all done but whose trigger rule isn't
+                // met will never run
+                TaskStatus::Pending => {
+                    if self.trigger_rules_satisfied(run_id, task.id)? {
+                        return Ok(false);
+                    }
+                }
+                TaskStatus::Success | TaskStatus::Failure | TaskStatus::Skipped => {}
+            }
+        }
+        Ok(true)
+    }
+
+    fn teardown_ready(&mut self, run_id: usize, task_id: usize) -> Result<bool> {
+        let mut upstream = HashSet::new();
+        let mut to_visit = self.get_upstream(run_id, task_id)?;
+        while let Some(curr) = to_visit.pop() {
+            if upstream.insert(curr) {
+                to_visit.append(&mut self.get_upstream(run_id, curr)?);
+            }
+        }
+
+        let mut setups = vec![];
+        for upstream_id in &upstream {
+            if self.get_task_by_id(run_id, *upstream_id)?.options.kind == TaskKind::Setup {
+                setups.push(*upstream_id);
+            }
+        }
+
+        let mut between = HashSet::new();
+        if setups.is_empty() {
+            between = upstream;
+        } else {
+            let mut to_visit = setups;
+            while let Some(curr) = to_visit.pop() {
+                if upstream.contains(&curr) && between.insert(curr) {
+                    to_visit.append(&mut self.get_downstream(run_id, curr)?);
+                }
+            }
+            between.extend(self.get_upstream(run_id, task_id)?);
+        }
+
+        for between_id in between {
+            if !self.is_task_done(run_id, between_id)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
     fn trigger_rules_satisfied(&mut self, run_id: usize, task_id: usize) -> Result<bool> {
         let task = self.get_task_by_id(run_id, task_id)?;
+        if task.options.kind == TaskKind::Teardown {
+            return self.teardown_ready(run_id, task_id);
+        }
 
         let required_upstream_ids: HashSet<usize> = HashSet::from_iter(
             self.get_dependencies(run_id, task_id)?
@@ -98,7 +241,7 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
         match task.options.trigger_rule {
             TriggerRule::AllSuccess => {
                 for upstream_id in self.get_upstream(run_id, task_id)? {
-                    if matches!(
+                    if !matches!(
                         self.get_task_status(run_id, upstream_id)?,
                         TaskStatus::Success
                     ) {
@@ -172,6 +315,20 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
         })
     }
 
+    fn upstream_done(&mut self, run_id: usize, task_id: usize) -> Result<bool> {
+        for upstream_id in self.get_upstream(run_id, task_id)? {
+            if !self.is_task_done(run_id, upstream_id)? {
+                return Ok(false);
+            }
+        }
+        for ((upstream_id, _), _) in self.get_dependencies(run_id, task_id)? {
+            if !self.is_task_done(run_id, upstream_id)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
     fn task_needs_running(&mut self, run_id: usize, task_id: usize) -> Result<bool> {
         Ok(matches!(
             self.get_task_status(run_id, task_id)?,
@@ -184,50 +341,64 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
         run: &Run,
         // run_id: usize,
         // pipeline_name: &str,
-        // pipeline_hash: &str,
         // scheduled_date_for_run: DateTime<Utc>,
         trigger_params: Option<Value>,
     ) -> Result<()> {
-        let default_tasks = self.get_default_tasks()?;
-        let trigger_params = trigger_params.unwrap_or(Value::Null);
-
-        for task in &default_tasks {
-            let _ = self.append_new_task_and_set_status_to_pending(
-                run.run_id,
-                &task.name,
-                &task.function,
-                if task.use_trigger_params {
-                    &trigger_params
-                } else {
-                    &task.template_args
-                },
-                &task.options,
-                task.lazy_expand,
-                task.is_dynamic,
-                task.is_branch,
-                task.use_trigger_params,
-            )?;
-            self.update_referenced_dependencies(run.run_id, task.id)?;
-        }
+        get_tracer().in_span("enqueue_run", |cx| {
+            cx.span().set_attributes([
+                KeyValue::new("pipeline", run.pipeline_name.clone()),
+                KeyValue::new("run_id", run.run_id as i64),
+            ]);
+            let enqueued = (|| -> Result<()> {
+                // use the pipeline version pinned to this run
+                let default_tasks = self.get_tasks_by_pipeline_hash(&run.pipeline_hash)?;
+                let trigger_params = trigger_params.unwrap_or(Value::Null);
+                self.set_trigger_params(run.run_id, &trigger_params)?;
+
+                for task in &default_tasks {
+                    let _ = self.append_new_task_and_set_status_to_pending(
+                        run.run_id,
+                        &task.key,
+                        &task.name,
+                        &task.function,
+                        if task.use_trigger_params {
+                            &trigger_params
+                        } else {
+                            &task.template_args
+                        },
+                        &task.options,
+                        task.lazy_expand,
+                        task.is_dynamic,
+                        task.is_branch,
+                        task.use_trigger_params,
+                        &task.connections,
+                    )?;
+                    self.update_referenced_dependencies(run.run_id, task.id)?;
+                }
 
-        for (upstream_id, downstream_id) in self.get_default_edges()? {
-            self.insert_edge(run.run_id, (upstream_id, downstream_id))?;
-        }
+                for (upstream_id, downstream_id) in
+                    self.get_edges_by_pipeline_hash(&run.pipeline_hash)?
+                {
+                    self.insert_edge(run.run_id, (upstream_id, downstream_id))?;
+                }
 
-        // only enqueue default tasks with no upstream dependencies
-        for task in default_tasks {
-            if self.get_task_depth(run.run_id, task.id)? == 0 {
-                self.enqueue_task(
-                    run.run_id,
-                    task.id,
-                    run.scheduled_date_for_run,
-                    run.pipeline_name.to_string(),
-                    false,
-                )?;
-            }
-        }
+                // only enqueue default tasks with no upstream dependencies
+                for task in default_tasks {
+                    if self.get_task_depth(run.run_id, task.id)? == 0 {
+                        self.enqueue_task(
+                            run.run_id,
+                            task.id,
+                            run.scheduled_date_for_run,
+                            run.pipeline_name.to_string(),
+                            false,
+                        )?;
+                    }
+                }
 
-        Ok(())
+                Ok(())
+            })();
+            record_error(&cx, enqueued)
+        })
     }
 
     fn handle_task_result(
@@ -252,6 +423,15 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
             };
         }
 
+        if let Some(result_offload) = self.get_result_offload()? {
+            result_offload.offload(
+                &format!(
+                    "{}/{run_id}/{}_{}.json",
+                    queued_task.pipeline_name, result.task_id, result.attempt
+                ),
+                &mut result,
+            )?;
+        }
         self.insert_task_results(run_id, &result)?;
 
         result.print_task_result(
@@ -272,7 +452,13 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
                     result.max_attempts
                 );
             }
-            self.set_task_status(run_id, result.task_id, TaskStatus::RetryPending)?;
+            self.set_task_status(
+                run_id,
+                result.task_id,
+                TaskStatus::RetryPending,
+                Some(&format!("attempt {} failed", result.attempt)),
+            )?;
+            self.trigger_callbacks(queued_task, CallbackEvent::OnRetry, &result)?;
             self.enqueue_task(
                 run_id,
                 result.task_id,
@@ -284,20 +470,43 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
         }
 
         if result.is_branch && result.success {
-            let skip_task = if branch_left {
-                result.task_id + 2
-            } else {
-                result.task_id + 1
+            // the left task is added before the right one
+            let mut targets = self.get_downstream(run_id, result.task_id)?;
+            targets.sort();
+            let skip_task = match (targets.as_slice(), branch_left) {
+                ([_, right], true) => *right,
+                ([left, _], false) => *left,
+                _ => {
+                    return Err(anyhow!(
+                        "branch task {} must have exactly two downstream tasks, found {}",
+                        result.task_id,
+                        targets.len()
+                    ))
+                }
             };
             let mut to_skip = vec![skip_task];
             to_skip.append(&mut self.get_downstream(run_id, skip_task)?);
 
             while let Some(curr) = to_skip.pop() {
+                // teardowns still run once the tasks before them are done
+                if self.get_task_by_id(run_id, curr)?.options.kind == TaskKind::Teardown {
+                    continue;
+                }
                 to_skip.append(&mut self.get_downstream(run_id, curr)?);
-                self.set_task_status(run_id, curr, TaskStatus::Skipped)?;
+                self.set_task_status(
+                    run_id,
+                    curr,
+                    TaskStatus::Skipped,
+                    Some(&format!("branch not taken by task {}", result.task_id)),
+                )?;
             }
         }
 
+        let reason = match &result.error {
+            Some(error) => Some(format!("{}: {}", error.kind, error.message)),
+            None if result.premature_failure => Some(result.premature_failure_error_str.clone()),
+            None => None,
+        };
         self.set_task_status(
             run_id,
             result.task_id,
@@ -306,6 +515,16 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
             } else {
                 TaskStatus::Failure
             },
+            reason.as_deref(),
+        )?;
+        self.trigger_callbacks(
+            queued_task,
+            if result.success {
+                CallbackEvent::OnSuccess
+            } else {
+                CallbackEvent::OnFailure
+            },
+            &result,
         )?;
 
         if !result.premature_failure && self.task_needs_running(run_id, result.task_id)? {
@@ -317,10 +536,29 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
                 false,
             )?;
         } else {
-            for downstream in self.get_downstream(run_id, result.task_id)? {
-                if !self.is_task_done(run_id, downstream)?
-                    && self.trigger_rules_satisfied(run_id, downstream)?
+            // teardowns further downstream may have been waiting on this task only
+            let mut teardowns = vec![];
+            let mut visited = HashSet::new();
+            let mut to_visit = self.get_downstream(run_id, result.task_id)?;
+            while let Some(curr) = to_visit.pop() {
+                if !visited.insert(curr) {
+                    continue;
+                }
+                if self.get_task_by_id(run_id, curr)?.options.kind == TaskKind::Teardown {
+                    teardowns.push(curr);
+                }
+                to_visit.append(&mut self.get_downstream(run_id, curr)?);
+            }
+
+            let mut to_check = self.get_downstream(run_id, result.task_id)?;
+            while let Some(downstream) = to_check.pop() {
+                let task = self.get_task_by_id(run_id, downstream)?;
+                if task.options.kind == TaskKind::Teardown
+                    || self.is_task_done(run_id, downstream)?
                 {
+                    continue;
+                }
+                if self.trigger_rules_satisfied(run_id, downstream)? {
                     self.enqueue_task(
                         run_id,
                         downstream,
@@ -328,28 +566,292 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
                         queued_task.pipeline_name.clone(),
                         false,
                     )?;
+                } else if self.upstream_done(run_id, downstream)? {
+                    // the trigger rule can't be met anymore, so tasks further downstream
+                    // may now run or be skipped as well
+                    self.set_task_status(
+                        run_id,
+                        downstream,
+                        TaskStatus::Skipped,
+                        Some(&format!(
+                            "trigger rule {:?} can't be met",
+                            task.options.trigger_rule
+                        )),
+                    )?;
+                    to_check.append(&mut self.get_downstream(run_id, downstream)?);
+                }
+            }
+
+            for teardown in teardowns {
+                if !self.is_task_done(run_id, teardown)?
+                    && self.trigger_rules_satisfied(run_id, teardown)?
+                {
+                    self.enqueue_task(
+                        run_id,
+                        teardown,
+                        queued_task.scheduled_date_for_run,
+                        queued_task.pipeline_name.clone(),
+                        false,
+                    )?;
+                }
+            }
+        }
+
+        let notifications = self.get_pipeline_options()?.notifications;
+        if !notifications.is_empty() {
+            self.notify(
+                &notifications,
+                Notification::from_task_result(
+                    if result.success {
+                        NotificationEvent::TaskSuccess
+                    } else {
+                        NotificationEvent::TaskFailure
+                    },
+                    queued_task,
+                    &result,
+                ),
+            )?;
+        }
+
+        // only the worker that records the end of the run sends its notification
+        if !self.is_run_complete(run_id)? || !self.set_run_end_date(run_id, Utc::now())? {
+            return Ok(());
+        }
+        if notifications.is_empty() {
+            return Ok(());
+        }
+        let notification = if self.get_run_status(run_id)? == RunStatus::Success {
+            Notification::new(
+                NotificationEvent::RunSuccess,
+                &queued_task.pipeline_name,
+                run_id,
+                queued_task.scheduled_date_for_run,
+            )
+        } else {
+            let mut failed_task_id = None;
+            for task in self.get_all_tasks(run_id)? {
+                if self.get_task_status(run_id, task.id)? == TaskStatus::Failure {
+                    failed_task_id = Some(task.id);
+                    break;
                 }
             }
+            match failed_task_id {
+                // run failures carry the first failed task and its log tail
+                Some(task_id) => Notification::from_task_result(
+                    NotificationEvent::RunFailure,
+                    queued_task,
+                    &self.get_task_result(run_id, task_id)?,
+                ),
+                // tasks were left pending because their trigger rules can't be met
+                None => Notification::new(
+                    NotificationEvent::RunFailure,
+                    &queued_task.pipeline_name,
+                    run_id,
+                    queued_task.scheduled_date_for_run,
+                ),
+            }
+        };
+        self.notify(&notifications, notification)
+    }
+
+    fn trigger_callbacks(
+        &mut self,
+        queued_task: &QueuedTask,
+        event: CallbackEvent,
+        result: &TaskResult,
+    ) -> Result<()> {
+        let task = self.get_task_by_id(queued_task.run_id, result.task_id)?;
+        if task.options.get_callbacks(event).is_empty() {
+            return Ok(());
+        }
+        self.enqueue_callbacks(&QueuedCallback {
+            queued_task: queued_task.clone(),
+            event,
+            task_result: result.clone(),
+        })
+    }
+
+    fn run_callbacks<D: AsRef<OsStr>>(
+        &mut self,
+        queued_callback: &QueuedCallback,
+        tpt_path: D,
+    ) -> Result<()> {
+        let QueuedCallback {
+            queued_task,
+            event,
+            task_result: result,
+        } = queued_callback;
+        let event = *event;
+        let run_id = queued_task.run_id;
+        let task = self.get_task_by_id(run_id, result.task_id)?;
+
+        let callback_context = json!(CallbackContext {
+            event,
+            run_id,
+            pipeline_name: queued_task.pipeline_name.clone(),
+            scheduled_date_for_run: queued_task.scheduled_date_for_run,
+            task_result: result.clone(),
+        });
+        let context = TaskContext {
+            run_id,
+            task_id: task.id,
+            task_key: task.key.clone(),
+            task_name: task.name.clone(),
+            attempt: result.attempt,
+            max_attempts: task.options.max_attempts,
+            pipeline_name: queued_task.pipeline_name.clone(),
+            scheduled_date: Some(queued_task.scheduled_date_for_run),
+            trigger_params: self.get_trigger_params(run_id)?,
+            variables_path: None,
+            connections: HashMap::new(),
+            traceparent: get_traceparent(&Context::current()),
+        };
+
+        for (index, callback) in task.options.get_callbacks(event).iter().enumerate() {
+            println!(
+                "\nrunning {event} callback '{}' of task {}\n",
+                callback.function, task.name
+            );
+            let args = match &callback.args {
+                Some(args) => render_fields_in_value(args, &callback_context),
+                None => callback_context.clone(),
+            };
+            // callbacks run once, without the timeout or retries of their task, and are named
+            // apart from it so their files don't overwrite the ones of the attempt
+            let callback_task = Task {
+                name: format!("{}_{event}_{index}", task.name),
+                function: callback.function.clone(),
+                options: TaskOptions::default(),
+                ..task.clone()
+            };
+            let mut log_stream = |stream| {
+                let handle =
+                    self.get_callback_log_handle_closure(run_id, task.id, result.attempt, event)?;
+                let attempt = result.attempt;
+                Ok::<_, anyhow::Error>(Box::new(move |line: String| {
+                    handle(LogLine::new(stream, attempt, &line))
+                })
+                    as Box<dyn Fn(String) -> Result<()> + Send>)
+            };
+            let callback_result = log_stream(LogStream::Stdout)
+                .and_then(|stdout| Ok((stdout, log_stream(LogStream::Stderr)?)))
+                .and_then(|(stdout, stderr)| {
+                    callback_task.execute(
+                        &args,
+                        result.attempt,
+                        stdout,
+                        stderr,
+                        self.get_pipeline_path()?,
+                        &tpt_path,
+                        &context,
+                    )
+                });
+
+            match callback_result {
+                Ok(callback_result) if callback_result.success => {}
+                Ok(_) => eprintln!(
+                    "{event} callback '{}' of task {} failed",
+                    callback.function, task.name
+                ),
+                Err(e) => eprintln!(
+                    "could not run {event} callback '{}' of task {}: {e}",
+                    callback.function, task.name
+                ),
+            }
+        }
+        println!(
+            "{}",
+            render_log_lines(&self.get_callback_log_lines(
+                run_id,
+                task.id,
+                result.attempt,
+                event
+            )?)
+        );
+        Ok(())
+    }
+
+    fn notify(
+        &mut self,
+        notifications: &[NotificationConfig],
+        notification: Notification,
+    ) -> Result<()> {
+        if notifications
+            .iter()
+            .any(|config| config.events.contains(&notification.event))
+        {
+            self.enqueue_notification(&notification)?;
+        }
+        Ok(())
+    }
+
+    fn send_notification(&mut self, notification: &Notification) -> Result<()> {
+        let notifications = self.get_pipeline_options()?.notifications;
+        let notifications = notifications
+            .iter()
+            .filter(|config| config.events.contains(&notification.event))
+            .collect::<Vec<_>>();
+        if notifications.is_empty() {
+            return Ok(());
+        }
+
+        let log_lines = match (notification.task_id, notification.attempt) {
+            (Some(task_id), Some(attempt)) => {
+                self.get_log_lines(notification.run_id, task_id, attempt, 0)?
+            }
+            _ => vec![],
+        };
+
+        for config in notifications {
+            let notification = notification
+                .clone()
+                .with_log_tail(&log_lines, config.log_tail_lines);
+            let message = notification.render(&config.template);
+
+            // sinks may reference secrets, e.g. '{{ secret.slack_webhook }}'
+            let sent = self
+                .resolve_variables(&json!(config.sink), &mut vec![])
+                .and_then(|sink| Ok(serde_json::from_value::<NotificationSink>(sink)?))
+                .and_then(|sink| sink.send(&notification, &message));
+
+            if let Err(e) = sent {
+                let reason = format!("failed to send {} notification: {e}", notification.event);
+                eprintln!("{reason} for run {}", notification.run_id);
+                self.append_run_event(
+                    notification.run_id,
+                    &RunEvent::new(
+                        &self.get_actor(),
+                        notification.task_id,
+                        None,
+                        "NotificationFailed",
+                        Some(&reason),
+                    ),
+                )?;
+            }
         }
         Ok(())
     }
 
     fn run_task<D: AsRef<OsStr>>(
         &mut self,
-        run_id: usize,
+        queued_task: &QueuedTask,
         task: &Task,
-        attempt: usize,
         resolution_result: &Value,
+        secrets: &[String],
         tpt_path: D,
-        scheduled_date_for_run: DateTime<Utc>,
     ) -> Result<TaskResult> {
+        let run_id = queued_task.run_id;
+        let attempt = queued_task.attempt;
+        let scheduled_date_for_run = queued_task.scheduled_date_for_run;
+
         if task.lazy_expand {
             let downstream = self.get_downstream(run_id, task.id)?;
 
             let mut lazy_ids = vec![];
-            for res in resolution_result.as_array().unwrap() {
+            for (i, res) in resolution_result.as_array().unwrap().iter().enumerate() {
                 let new_id = self.append_new_task_and_set_status_to_pending(
                     run_id,
+                    &format!("{}_{i}", task.key),
                     &task.name,
                     &task.function,
                     res,
@@ -358,6 +860,7 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
                     true,
                     false,
                     false,
+                    &task.connections,
                 )?;
 
                 lazy_ids.push(new_id);
@@ -369,6 +872,7 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
 
                 let collector_id = self.append_new_task_and_set_status_to_pending(
                     run_id,
+                    &format!("{}_collector", task.key),
                     &task.name,
                     &function_name,
                     &json!(lazy_ids
@@ -384,6 +888,7 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
                     true,
                     false,
                     false,
+                    &[],
                 )?;
                 self.update_referenced_dependencies(run_id, collector_id)?;
 
@@ -463,19 +968,184 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
                 is_branch: task.is_branch,
                 is_sensor: task.options.is_sensor,
                 exit_code: None,
+                error: None,
+                result_ref: None,
             });
         }
 
-        task.execute(
+        // connection passwords are redacted from logs like secrets
+        let mut secrets = secrets.to_vec();
+        let resolved = (|| -> Result<HashMap<String, Connection>> {
+            let mut connections = HashMap::new();
+            for id in &task.connections {
+                let connection = self
+                    .get_connection(id)?
+                    .ok_or_else(|| anyhow!("unknown connection '{id}'"))?;
+                secrets.push(connection.password.clone());
+                connections.insert(id.clone(), connection);
+            }
+            Ok(connections)
+        })();
+        let connections = match resolved {
+            Ok(resolved) => resolved,
+            Err(err) => {
+                return Ok(TaskResult::premature_error(
+                    task.id,
+                    attempt,
+                    task.options.max_attempts,
+                    task.name.clone(),
+                    task.function.clone(),
+                    err.to_string(),
+                    task.is_branch,
+                    task.options.is_sensor,
+                    None,
+                    None,
+                ))
+            }
+        };
+        // each line is stored with the stream it came from, secrets redacted
+        let log_stream = |handle: Box<dyn Fn(LogLine) -> Result<()> + Send>, stream| {
+            let secrets = secrets.clone();
+            Box::new(move |line: String| {
+                handle(LogLine::new(
+                    stream,
+                    attempt,
+                    &redact_secrets(&line, &secrets),
+                ))
+            }) as Box<dyn Fn(String) -> Result<()> + Send>
+        };
+
+        // tasks read and write variables through a snapshot file, see TaskVariables
+        let variables = TaskVariables {
+            pipeline: self.get_variables(VariableScope::Pipeline)?,
+            run: self.get_variables(VariableScope::Run(run_id))?,
+        };
+        // a private file with a unique name, removed when it is dropped
+        let variables_file = tempfile::Builder::new()
+            .prefix(&format!("tpt_variables_{run_id}_{}_{attempt}_", task.id))
+            .suffix(".json")
+            .tempfile()?;
+        let variables_path = variables_file.path().to_path_buf();
+        value_to_file(&variables, &variables_path);
+
+        let context = TaskContext {
+            run_id,
+            task_id: task.id,
+            task_key: task.key.clone(),
+            task_name: task.name.clone(),
+            attempt,
+            max_attempts: task.options.max_attempts,
+            pipeline_name: self.get_pipeline_name()?,
+            scheduled_date: Some(scheduled_date_for_run),
+            trigger_params: self.get_trigger_params(run_id)?,
+            variables_path: Some(variables_path.clone()),
+            connections,
+            traceparent: get_traceparent(&Context::current()),
+        };
+
+        let mut task_result = task.execute(
             resolution_result,
             attempt,
-            self.get_log_handle_closure(run_id, task.id, attempt)?,
-            self.get_log_handle_closure(run_id, task.id, attempt)?,
-            self.take_last_stdout_line(run_id, task.id, attempt)?,
+            log_stream(
+                self.get_log_handle_closure(run_id, task.id, attempt)?,
+                LogStream::Stdout,
+            ),
+            log_stream(
+                self.get_log_handle_closure(run_id, task.id, attempt)?,
+                LogStream::Stderr,
+            ),
             self.get_pipeline_path()?,
             tpt_path,
-            run_id,
-        )
+            &context,
+        )?;
+        task_result.resolved_args_str = redact_secrets(
+            &serde_json::to_string(&redact_secrets_in_value(resolution_result, &secrets))?,
+            &secrets,
+        );
+
+        // changes from failed attempts are discarded
+        if task_result.success {
+            let updated_variables: TaskVariables =
+                value_from_file(&variables_path).unwrap_or_default();
+            self.update_variables(run_id, &variables, &updated_variables)?;
+        }
+        drop(variables_file);
+
+        Ok(task_result)
+    }
+
+    fn load_result(&self, task_result: &mut TaskResult) -> Result<()> {
+        if let Some(result_ref) = &task_result.result_ref {
+            task_result.result = self
+                .get_result_offload()?
+                .ok_or_else(|| anyhow!("no result store configured to load '{result_ref}'"))?
+                .load(result_ref)?;
+        }
+        Ok(())
+    }
+
+    fn update_variables(
+        &mut self,
+        run_id: usize,
+        variables: &TaskVariables,
+        updated_variables: &TaskVariables,
+    ) -> Result<()> {
+        for (scope, variables, updated_variables) in [
+            (
+                VariableScope::Pipeline,
+                &variables.pipeline,
+                &updated_variables.pipeline,
+            ),
+            (
+                VariableScope::Run(run_id),
+                &variables.run,
+                &updated_variables.run,
+            ),
+        ] {
+            for (key, value) in updated_variables {
+                if variables.get(key) != Some(value) {
+                    self.set_variable(scope, key, value)?;
+                }
+            }
+            for key in variables.keys() {
+                if !updated_variables.contains_key(key) {
+                    self.delete_variable(scope, key)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn resolve_variables(&mut self, value: &Value, secrets: &mut Vec<String>) -> Result<Value> {
+        resolve_variable_references(value, &mut |reference: &str| {
+            if let Some(name) = reference.strip_prefix(SECRET_REFERENCE_PREFIX) {
+                let secret = self
+                    .get_secret(name)?
+                    .ok_or_else(|| anyhow!("unknown secret '{name}'"))?;
+                secrets.push(secret.clone());
+                Ok(Value::String(secret))
+            } else {
+                let name = &reference[VARIABLE_REFERENCE_PREFIX.len()..];
+                self.get_variable(VariableScope::Global, name)?
+                    .ok_or_else(|| anyhow!("unknown variable '{name}'"))
+            }
+        })
+    }
+
+    fn resolve_task_args(
+        &mut self,
+        run_id: usize,
+        task: &Task,
+        secrets: &mut Vec<String>,
+    ) -> Result<Value> {
+        // trigger params and the args of expanded tasks come from outside the pipeline
+        let template_args = if task.use_trigger_params || task.is_dynamic {
+            task.template_args.clone()
+        } else {
+            self.resolve_variables(&task.template_args, secrets)?
+        };
+        let dependency_keys = self.get_dependencies(run_id, task.id)?;
+        self.resolve_args(run_id, &template_args, &dependency_keys)
     }
 
     fn resolve_args(
@@ -490,7 +1160,8 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
                 continue;
             }
 
-            let task_result = self.get_task_result(run_id, *upstream_id)?;
+            let mut task_result = self.get_task_result(run_id, *upstream_id)?;
+            self.load_result(&mut task_result)?;
             results.insert(*upstream_id, task_result.result.clone());
             if !task_result.success {
                 return Err(anyhow::Error::msg(format!(
@@ -590,21 +1261,44 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
             temp_queued_task.queued_task.run_id,
             temp_queued_task.queued_task.task_id,
         )?;
-        let dependency_keys =
-            self.get_dependencies(temp_queued_task.queued_task.run_id, task.id)?;
-        let result = match self.resolve_args(
-            temp_queued_task.queued_task.run_id,
-            &task.template_args,
-            &dependency_keys,
-        ) {
-            Ok(resolution_result) => self.run_task(
-                temp_queued_task.queued_task.run_id,
-                &task,
-                temp_queued_task.queued_task.attempt,
-                &resolution_result,
-                tpt_path,
-                temp_queued_task.queued_task.scheduled_date_for_run,
-            )?,
+        let tracer = get_tracer();
+        let attributes = [
+            KeyValue::new(
+                "pipeline",
+                temp_queued_task.queued_task.pipeline_name.clone(),
+            ),
+            KeyValue::new("run_id", temp_queued_task.queued_task.run_id as i64),
+            KeyValue::new("task_id", task.id as i64),
+            KeyValue::new("task_name", task.name.clone()),
+            KeyValue::new("attempt", temp_queued_task.queued_task.attempt as i64),
+        ];
+        let mut secrets = vec![];
+        let resolved = tracer.in_span("resolve_args", |cx| {
+            cx.span().set_attributes(attributes.clone());
+            record_error(
+                &cx,
+                self.resolve_task_args(temp_queued_task.queued_task.run_id, &task, &mut secrets),
+            )
+        });
+        let result = match resolved {
+            Ok(resolution_result) => tracer.in_span("execute_task", |cx| {
+                cx.span().set_attributes(attributes.clone());
+                let result = self.run_task(
+                    &temp_queued_task.queued_task,
+                    &task,
+                    &resolution_result,
+                    &secrets,
+                    &tpt_path,
+                );
+                if let Ok(task_result) = &result {
+                    if !task_result.success {
+                        cx.span().set_status(Status::error(
+                            task_result.premature_failure_error_str.clone(),
+                        ));
+                    }
+                }
+                record_error(&cx, result)
+            })?,
             Err(resolution_result) => TaskResult::premature_error(
                 task.id,
                 temp_queued_task.queued_task.attempt,
@@ -618,12 +1312,17 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
                 None,
             ),
         };
-        self.handle_task_result(
-            temp_queued_task.queued_task.run_id,
-            &temp_queued_task.queued_task,
-            result,
-        )?;
-        Ok(())
+        tracer.in_span("handle_task_result", |cx| {
+            cx.span().set_attributes(attributes);
+            record_error(
+                &cx,
+                self.handle_task_result(
+                    temp_queued_task.queued_task.run_id,
+                    &temp_queued_task.queued_task,
+                    result,
+                ),
+            )
+        })
     }
 
     fn update_referenced_dependencies(
@@ -724,3 +1423,425 @@ impl<U: Backend + Send + Sync> BlanketBackend for U {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use thepipelinetool_task::task_callback::TaskCallback;
+    use thepipelinetool_utils::UPSTREAM_TASK_ID_KEY;
+
+    use super::*;
+    use crate::{in_memory_backend::InMemoryBackend, test_utils::add_task};
+
+    fn task_result(task_id: usize, success: bool, result: Value) -> TaskResult {
+        let mut task_result = TaskResult::premature_error(
+            task_id,
+            1,
+            1,
+            "".into(),
+            "".into(),
+            "".into(),
+            false,
+            false,
+            None,
+            None,
+        );
+        task_result.success = success;
+        task_result.premature_failure = false;
+        task_result.result = result;
+        task_result
+    }
+
+    fn set_result(backend: &mut InMemoryBackend, task_id: usize, success: bool, result: Value) {
+        backend
+            .insert_task_results(0, &task_result(task_id, success, result))
+            .unwrap();
+        backend
+            .set_task_status(
+                0,
+                task_id,
+                if success {
+                    TaskStatus::Success
+                } else {
+                    TaskStatus::Failure
+                },
+                None,
+            )
+            .unwrap();
+    }
+
+    fn handle_result(backend: &mut InMemoryBackend, result: TaskResult) {
+        let queued_task = QueuedTask {
+            task_id: result.task_id,
+            run_id: 0,
+            pipeline_name: "in_memory".into(),
+            scheduled_date_for_run: Utc::now(),
+            attempt: 1,
+            traceparent: None,
+        };
+        backend.handle_task_result(0, &queued_task, result).unwrap();
+    }
+
+    fn queued_task_ids(backend: &mut InMemoryBackend) -> Vec<usize> {
+        let mut task_ids = vec![];
+        while let Some(temp_queued_task) = backend.pop_priority_queue().unwrap() {
+            task_ids.push(temp_queued_task.queued_task.task_id);
+        }
+        task_ids.sort();
+        task_ids
+    }
+
+    fn kind(kind: TaskKind) -> TaskOptions {
+        TaskOptions {
+            kind,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_resolve_task_args_keeps_upstream_results_literal() {
+        let mut backend = InMemoryBackend::default();
+        backend.set_secret("db_password", "hunter2").unwrap();
+
+        let upstream_id = add_task(
+            &mut backend,
+            "upstream",
+            Value::Null,
+            &TaskOptions::default(),
+        );
+        let task_id = add_task(
+            &mut backend,
+            "task",
+            json!([{ UPSTREAM_TASK_ID_KEY: upstream_id }, "{{ secret.db_password }}"]),
+            &TaskOptions::default(),
+        );
+        set_result(
+            &mut backend,
+            upstream_id,
+            true,
+            json!("{{ secret.db_password }}"),
+        );
+
+        let mut secrets = vec![];
+        let task = backend.get_task_by_id(0, task_id).unwrap();
+        assert_eq!(
+            backend.resolve_task_args(0, &task, &mut secrets).unwrap(),
+            json!(["{{ secret.db_password }}", "hunter2"])
+        );
+        assert_eq!(secrets, vec!["hunter2".to_string()]);
+    }
+
+    #[test]
+    fn test_run_complete_with_unreachable_task() {
+        let mut backend = InMemoryBackend::default();
+        let upstream_id = add_task(
+            &mut backend,
+            "upstream",
+            Value::Null,
+            &TaskOptions::default(),
+        );
+        let task_id = add_task(
+            &mut backend,
+            "task",
+            Value::Null,
+            &TaskOptions {
+                trigger_rule: TriggerRule::AnySuccess,
+                ..Default::default()
+            },
+        );
+        backend.insert_edge(0, (upstream_id, task_id)).unwrap();
+        assert!(!backend.is_run_complete(0).unwrap());
+
+        set_result(&mut backend, upstream_id, false, Value::Null);
+        assert!(backend.is_run_complete(0).unwrap());
+        assert_eq!(backend.get_run_status(0).unwrap(), RunStatus::Failed);
+
+        // only the first worker claims the end of the run
+        assert!(backend.set_run_end_date(0, Utc::now()).unwrap());
+        assert!(!backend.set_run_end_date(0, Utc::now()).unwrap());
+    }
+
+    #[test]
+    fn test_teardown_ready() {
+        let mut backend = InMemoryBackend::default();
+        let before_id = add_task(&mut backend, "before", Value::Null, &TaskOptions::default());
+        let setup_id = add_task(&mut backend, "setup", Value::Null, &kind(TaskKind::Setup));
+        let work_id = add_task(&mut backend, "work", Value::Null, &TaskOptions::default());
+        let teardown_id = add_task(
+            &mut backend,
+            "teardown",
+            Value::Null,
+            &kind(TaskKind::Teardown),
+        );
+        for edge in [
+            (before_id, setup_id),
+            (setup_id, work_id),
+            (work_id, teardown_id),
+        ] {
+            backend.insert_edge(0, edge).unwrap();
+        }
+
+        set_result(&mut backend, before_id, true, Value::Null);
+        set_result(&mut backend, setup_id, true, Value::Null);
+        assert!(!backend.teardown_ready(0, teardown_id).unwrap());
+
+        // failed tasks between the setup and the teardown don't keep it from running
+        set_result(&mut backend, work_id, false, Value::Null);
+        assert!(backend.teardown_ready(0, teardown_id).unwrap());
+    }
+
+    #[test]
+    fn test_teardown_runs_after_unmet_trigger_rule() {
+        let mut backend = InMemoryBackend::default();
+        let setup_id = add_task(&mut backend, "setup", Value::Null, &kind(TaskKind::Setup));
+        let work_id = add_task(&mut backend, "work", Value::Null, &TaskOptions::default());
+        let on_success_id = add_task(
+            &mut backend,
+            "on_success",
+            Value::Null,
+            &TaskOptions {
+                trigger_rule: TriggerRule::AnySuccess,
+                ..Default::default()
+            },
+        );
+        let teardown_id = add_task(
+            &mut backend,
+            "teardown",
+            Value::Null,
+            &kind(TaskKind::Teardown),
+        );
+        for edge in [
+            (setup_id, work_id),
+            (work_id, on_success_id),
+            (on_success_id, teardown_id),
+        ] {
+            backend.insert_edge(0, edge).unwrap();
+        }
+        set_result(&mut backend, setup_id, true, Value::Null);
+
+        handle_result(&mut backend, task_result(work_id, false, Value::Null));
+        assert_eq!(
+            backend.get_task_status(0, on_success_id).unwrap(),
+            TaskStatus::Skipped
+        );
+        assert_eq!(queued_task_ids(&mut backend), vec![teardown_id]);
+    }
+
+    #[test]
+    fn test_trigger_rules() {
+        // upstream outcomes: all succeeded, one of each, all failed
+        for (trigger_rule, expected) in [
+            (TriggerRule::AllDone, [true, true, true]),
+            (TriggerRule::AnyDone, [true, true, true]),
+            (TriggerRule::AllSuccess, [true, false, false]),
+            (TriggerRule::AnySuccess, [true, true, false]),
+            (TriggerRule::AnyFailed, [false, true, true]),
+            (TriggerRule::AllFailed, [false, false, true]),
+        ] {
+            for (outcomes, expected) in [[true, true], [true, false], [false, false]]
+                .into_iter()
+                .zip(expected)
+            {
+                let mut backend = InMemoryBackend::default();
+                let upstream_ids: Vec<usize> = outcomes
+                    .iter()
+                    .map(|_| add_task(&mut backend, "up", Value::Null, &TaskOptions::default()))
+                    .collect();
+                let task_id = add_task(
+                    &mut backend,
+                    "task",
+                    Value::Null,
+                    &TaskOptions {
+                        trigger_rule,
+                        ..Default::default()
+                    },
+                );
+                for (upstream_id, success) in upstream_ids.into_iter().zip(outcomes) {
+                    backend.insert_edge(0, (upstream_id, task_id)).unwrap();
+                    set_result(&mut backend, upstream_id, success, Value::Null);
+                }
+                assert_eq!(
+                    backend.trigger_rules_satisfied(0, task_id).unwrap(),
+                    expected,
+                    "{trigger_rule:?} with upstream outcomes {outcomes:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_all_success_task_is_queued() {
+        let mut backend = InMemoryBackend::default();
+        let upstream_id = add_task(&mut backend, "up", Value::Null, &TaskOptions::default());
+        let task_id = add_task(
+            &mut backend,
+            "task",
+            Value::Null,
+            &TaskOptions {
+                trigger_rule: TriggerRule::AllSuccess,
+                ..Default::default()
+            },
+        );
+        backend.insert_edge(0, (upstream_id, task_id)).unwrap();
+
+        handle_result(&mut backend, task_result(upstream_id, true, Value::Null));
+        assert_eq!(
+            backend.get_task_status(0, task_id).unwrap(),
+            TaskStatus::Pending
+        );
+        assert_eq!(queued_task_ids(&mut backend), vec![task_id]);
+        assert!(!backend.is_run_complete(0).unwrap());
+    }
+
+    #[test]
+    fn test_branch_does_not_skip_teardowns() {
+        let mut backend = InMemoryBackend::default();
+        let branch_id = add_task(&mut backend, "branch", Value::Null, &TaskOptions::default());
+        let left_id = add_task(&mut backend, "left", Value::Null, &TaskOptions::default());
+        let right_id = add_task(&mut backend, "right", Value::Null, &TaskOptions::default());
+        let teardown_id = add_task(
+            &mut backend,
+            "teardown",
+            Value::Null,
+            &kind(TaskKind::Teardown),
+        );
+        for edge in [
+            (branch_id, left_id),
+            (branch_id, right_id),
+            (left_id, teardown_id),
+        ] {
+            backend.insert_edge(0, edge).unwrap();
+        }
+
+        let mut result = task_result(branch_id, true, json!({ "Right": null }));
+        result.is_branch = true;
+        handle_result(&mut backend, result);
+
+        assert_eq!(
+            backend.get_task_status(0, left_id).unwrap(),
+            TaskStatus::Skipped
+        );
+        assert_eq!(
+            backend.get_task_status(0, teardown_id).unwrap(),
+            TaskStatus::Pending
+        );
+        assert_eq!(queued_task_ids(&mut backend), vec![right_id, teardown_id]);
+    }
+
+    #[test]
+    fn test_run_status_ignores_successful_teardowns() {
+        let mut backend = InMemoryBackend::default();
+        let work_id = add_task(&mut backend, "work", Value::Null, &TaskOptions::default());
+        let teardown_id = add_task(
+            &mut backend,
+            "teardown",
+            Value::Null,
+            &kind(TaskKind::Teardown),
+        );
+        backend.insert_edge(0, (work_id, teardown_id)).unwrap();
+        assert_eq!(backend.get_run_status(0).unwrap(), RunStatus::Pending);
+
+        set_result(&mut backend, work_id, false, Value::Null);
+        set_result(&mut backend, teardown_id, true, Value::Null);
+        assert_eq!(backend.get_run_status(0).unwrap(), RunStatus::Failed);
+
+        // a failed teardown fails the run, a successful one leaves the outcome to the others
+        set_result(&mut backend, teardown_id, false, Value::Null);
+        set_result(&mut backend, work_id, true, Value::Null);
+        assert_eq!(backend.get_run_status(0).unwrap(), RunStatus::Failed);
+
+        set_result(&mut backend, teardown_id, true, Value::Null);
+        assert_eq!(backend.get_run_status(0).unwrap(), RunStatus::Success);
+
+        // a run of only successful teardowns has succeeded
+        let mut backend = InMemoryBackend::default();
+        let teardown_id = add_task(
+            &mut backend,
+            "teardown",
+            Value::Null,
+            &kind(TaskKind::Teardown),
+        );
+        set_result(&mut backend, teardown_id, true, Value::Null);
+        assert_eq!(backend.get_run_status(0).unwrap(), RunStatus::Success);
+    }
+
+    #[test]
+    fn test_callbacks_run_with_their_own_logs() {
+        // stands in for the pipeline binary, `sh <script> run function <function> <in> <out>`
+        let script_path = env::temp_dir().join(format!("tpt_callbacks_{}.sh", std::process::id()));
+        fs::write(&script_path, "echo \"$3 ran\"\necho null > \"$5\"\n").unwrap();
+        let mut backend = InMemoryBackend {
+            pipeline_path: script_path.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        let callback = |function: &str| TaskCallback {
+            function: function.into(),
+            args: None,
+        };
+        let task_id = add_task(
+            &mut backend,
+            "task",
+            Value::Null,
+            &TaskOptions {
+                max_attempts: 2,
+                on_retry: vec![callback("retry_callback")],
+                on_failure: vec![callback("failure_callback")],
+                ..Default::default()
+            },
+        );
+
+        for attempt in 1..=2 {
+            let mut result = task_result(task_id, false, Value::Null);
+            result.attempt = attempt;
+            result.max_attempts = 2;
+            handle_result(&mut backend, result);
+        }
+        // callbacks are only queued while the result is handled
+        assert!(backend
+            .get_callback_log_lines(0, task_id, 1, CallbackEvent::OnRetry)
+            .unwrap()
+            .is_empty());
+        while let Some(queued_callback) = backend.pop_callback_queue().unwrap() {
+            backend.run_callbacks(&queued_callback, "sh").unwrap();
+        }
+        fs::remove_file(&script_path).unwrap();
+
+        let log = |backend: &mut InMemoryBackend, attempt, event| {
+            render_log_lines(
+                &backend
+                    .get_callback_log_lines(0, task_id, attempt, event)
+                    .unwrap(),
+            )
+        };
+        assert!(log(&mut backend, 1, CallbackEvent::OnRetry).contains("retry_callback ran"));
+        assert!(log(&mut backend, 2, CallbackEvent::OnFailure).contains("failure_callback ran"));
+        assert!(log(&mut backend, 2, CallbackEvent::OnRetry).is_empty());
+        assert!(backend.get_log(0, task_id, 2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_failed_notifications_are_recorded() {
+        let mut backend = InMemoryBackend::default();
+        backend.options.notifications = vec![serde_json::from_value(json!({
+            "sink": { "type": "webhook", "url": "http://127.0.0.1:9" },
+            "events": ["run_failure"],
+        }))
+        .unwrap()];
+        let notifications = backend.options.notifications.clone();
+
+        // only events with a sink are queued
+        for event in [NotificationEvent::RunSuccess, NotificationEvent::RunFailure] {
+            backend
+                .notify(&notifications, Notification::new(event, "p", 0, Utc::now()))
+                .unwrap();
+        }
+        let notification = backend.pop_notification_queue().unwrap().unwrap();
+        assert_eq!(notification.event, NotificationEvent::RunFailure);
+        assert!(backend.pop_notification_queue().unwrap().is_none());
+
+        backend.send_notification(&notification).unwrap();
+        let events = backend.run_events.lock();
+        assert_eq!(events.last().unwrap().new_state, "NotificationFailed");
+    }
+}
@@ -1,27 +1,46 @@
 use std::{
-    collections::{BinaryHeap, HashMap, HashSet},
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    env,
     sync::Arc,
 };
 
 use crate::{
-    backend::{OriginalKey, ResultKey, UpstreamId},
-    run::Run,
+    backend::{OriginalKey, ResultKey, UpstreamId, VariableScope},
+    notifications::Notification,
+    pipeline::hash_pipeline,
+    pipeline_options::PipelineOptions,
+    result_store::{FileResultStore, ResultOffload, DEFAULT_RESULT_OFFLOAD_THRESHOLD},
+    run::{Run, RunEvent},
+    telemetry::get_traceparent,
     Backend,
 };
 use chrono::{DateTime, Utc};
+use opentelemetry::Context;
 use parking_lot::Mutex;
 use serde_json::Value;
 use thepipelinetool_task::{
-    ordered_queued_task::OrderedQueuedTask, queued_task::QueuedTask, task_options::TaskOptions,
-    task_result::TaskResult, task_status::TaskStatus, temp_queued_task::TempQueuedTask, Task,
+    connection::Connection,
+    log_line::LogLine,
+    ordered_queued_task::OrderedQueuedTask,
+    queued_task::QueuedTask,
+    task_callback::{CallbackEvent, QueuedCallback},
+    task_options::TaskOptions,
+    task_result::TaskResult,
+    task_status::TaskStatus,
+    temp_queued_task::TempQueuedTask,
+    Task,
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+
+// task id, attempt and the event the callbacks ran for
+type CallbackLogKey = (usize, usize, CallbackEvent);
 
 #[derive(Clone, Default)]
 pub struct InMemoryBackend {
     pub task_results: Arc<Mutex<HashMap<usize, TaskResult>>>,
-    pub task_logs: Arc<Mutex<HashMap<usize, Vec<String>>>>,
+    pub task_logs: Arc<Mutex<HashMap<usize, Vec<LogLine>>>>,
+    pub callback_logs: Arc<Mutex<HashMap<CallbackLogKey, Vec<LogLine>>>>,
     pub task_statuses: Arc<Mutex<HashMap<usize, TaskStatus>>>,
     pub attempts: Arc<Mutex<HashMap<String, usize>>>,
     pub dependencies: Arc<Mutex<HashMap<usize, HashMap<(UpstreamId, OriginalKey), ResultKey>>>>,
@@ -31,17 +50,88 @@ pub struct InMemoryBackend {
     pub task_depth: Arc<Mutex<HashMap<usize, usize>>>,
     pub priority_queue: Arc<Mutex<BinaryHeap<OrderedQueuedTask>>>,
     pub temp_queue: Arc<Mutex<HashSet<TempQueuedTask>>>,
+    pub callback_queue: Arc<Mutex<VecDeque<QueuedCallback>>>,
+    pub notification_queue: Arc<Mutex<VecDeque<Notification>>>,
+    pub trigger_params: Arc<Mutex<Value>>,
+    pub variables: Arc<Mutex<HashMap<VariableScope, HashMap<String, Value>>>>,
+    pub secrets: Arc<Mutex<HashMap<String, String>>>,
+    pub connections: Arc<Mutex<HashMap<String, Connection>>>,
+    pub run_events: Arc<Mutex<Vec<RunEvent>>>,
+    pub run_end_date: Arc<Mutex<Option<DateTime<Utc>>>>,
+    pub result_offload: Option<ResultOffload>,
     pub pipeline_path: String,
+    pub options: PipelineOptions,
 }
 
+const GLOBAL_VARIABLE_ENV_PREFIX: &str = "TPT_VAR_";
+const SECRET_ENV_PREFIX: &str = "TPT_SECRET_";
+const CONNECTION_ENV_PREFIX: &str = "TPT_CONNECTION_";
+const RESULT_STORE_PATH_ENV: &str = "TPT_RESULT_STORE_PATH";
+const RESULT_STORE_THRESHOLD_ENV: &str = "TPT_RESULT_STORE_THRESHOLD";
+
 impl InMemoryBackend {
-    pub fn new(pipline_path: &str, nodes: &[Task], edges: &HashSet<(usize, usize)>) -> Self {
-        Self {
+    pub fn new(
+        pipline_path: &str,
+        nodes: &[Task],
+        edges: &HashSet<(usize, usize)>,
+    ) -> Result<Self> {
+        // global variables, secrets and connections come from TPT_VAR_<name>,
+        // TPT_SECRET_<name> and TPT_CONNECTION_<id> (as JSON)
+        let mut global_variables = HashMap::new();
+        let mut secrets = HashMap::new();
+        let mut connections = HashMap::new();
+        for (key, value) in env::vars() {
+            if let Some(name) = key.strip_prefix(GLOBAL_VARIABLE_ENV_PREFIX) {
+                let value = serde_json::from_str(&value).unwrap_or(Value::String(value));
+                global_variables.insert(name.to_string(), value);
+            } else if let Some(name) = key.strip_prefix(SECRET_ENV_PREFIX) {
+                secrets.insert(name.to_string(), value);
+            } else if let Some(id) = key.strip_prefix(CONNECTION_ENV_PREFIX) {
+                let connection = serde_json::from_str(&value)
+                    .map_err(|e| anyhow!("invalid connection in '{key}': {e}"))?;
+                connections.insert(id.to_string(), connection);
+            }
+        }
+
+        Ok(Self {
             pipeline_path: pipline_path.to_string(),
             edges: Arc::new(Mutex::new(edges.clone())),
             default_tasks: Arc::new(Mutex::new(nodes.to_vec())),
+            variables: Arc::new(Mutex::new(HashMap::from([(
+                VariableScope::Global,
+                global_variables,
+            )]))),
+            secrets: Arc::new(Mutex::new(secrets)),
+            connections: Arc::new(Mutex::new(connections)),
+            // large results are written to TPT_RESULT_STORE_PATH when set
+            result_offload: env::var(RESULT_STORE_PATH_ENV)
+                .ok()
+                .map(|path| ResultOffload {
+                    store: Arc::new(FileResultStore { path: path.into() }),
+                    threshold: env::var(RESULT_STORE_THRESHOLD_ENV)
+                        .ok()
+                        .and_then(|t| t.parse().ok())
+                        .unwrap_or(DEFAULT_RESULT_OFFLOAD_THRESHOLD),
+                }),
             ..Default::default()
+        })
+    }
+
+    // only a single version of the pipeline is held in memory, runs without a hash use it too
+    fn assert_pipeline_version(&self, pipeline_hash: &str) -> Result<()> {
+        if !pipeline_hash.is_empty() && pipeline_hash != self.get_current_pipeline_hash()? {
+            return Err(anyhow!(
+                "could not find version '{}' of pipeline '{}'",
+                pipeline_hash,
+                self.get_pipeline_name()?
+            ));
         }
+        Ok(())
+    }
+
+    pub fn with_options(mut self, options: &PipelineOptions) -> Self {
+        self.options = options.clone();
+        self
     }
 }
 
@@ -71,14 +161,23 @@ impl Backend for InMemoryBackend {
         Ok(())
     }
 
-    fn get_log(&mut self, _run_id: usize, task_id: usize, _attempt: usize) -> Result<String> {
+    fn get_log_lines(
+        &mut self,
+        _run_id: usize,
+        task_id: usize,
+        attempt: usize,
+        offset: usize,
+    ) -> Result<Vec<LogLine>> {
         Ok(self
             .task_logs
             .lock()
             .get(&task_id)
             .unwrap_or(&vec![])
-            .clone()
-            .join(""))
+            .iter()
+            .filter(|line| line.attempt == attempt)
+            .skip(offset)
+            .cloned()
+            .collect())
     }
 
     fn get_log_handle_closure(
@@ -86,10 +185,43 @@ impl Backend for InMemoryBackend {
         _run_id: usize,
         task_id: usize,
         _attempt: usize,
-    ) -> Result<Box<dyn Fn(String) -> Result<()> + Send>> {
+    ) -> Result<Box<dyn Fn(LogLine) -> Result<()> + Send>> {
         let task_logs = self.task_logs.clone();
-        Ok(Box::new(move |s| {
-            task_logs.lock().entry(task_id).or_default().push(s);
+        Ok(Box::new(move |line| {
+            task_logs.lock().entry(task_id).or_default().push(line);
+            Ok(())
+        }))
+    }
+
+    fn get_callback_log_lines(
+        &mut self,
+        _run_id: usize,
+        task_id: usize,
+        attempt: usize,
+        event: CallbackEvent,
+    ) -> Result<Vec<LogLine>> {
+        Ok(self
+            .callback_logs
+            .lock()
+            .get(&(task_id, attempt, event))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn get_callback_log_handle_closure(
+        &mut self,
+        _run_id: usize,
+        task_id: usize,
+        attempt: usize,
+        event: CallbackEvent,
+    ) -> Result<Box<dyn Fn(LogLine) -> Result<()> + Send>> {
+        let callback_logs = self.callback_logs.clone();
+        Ok(Box::new(move |line| {
+            callback_logs
+                .lock()
+                .entry((task_id, attempt, event))
+                .or_default()
+                .push(line);
             Ok(())
         }))
     }
@@ -101,12 +233,118 @@ impl Backend for InMemoryBackend {
         Ok(())
     }
 
-    fn create_new_run(&mut self, scheduled_date_for_run: DateTime<Utc>) -> Result<Run> {
-        Ok(Run {
+    fn create_new_run(
+        &mut self,
+        scheduled_date_for_run: DateTime<Utc>,
+        pipeline_hash: &str,
+    ) -> Result<Run> {
+        let run = Run {
             run_id: 0,
             pipeline_name: self.get_pipeline_name()?,
             scheduled_date_for_run,
-        })
+            pipeline_hash: pipeline_hash.to_string(),
+        };
+        self.append_run_event(
+            run.run_id,
+            &RunEvent::new(
+                &self.get_actor(),
+                None,
+                None,
+                "Created",
+                Some(&format!("scheduled for {scheduled_date_for_run}")),
+            ),
+        )?;
+        Ok(run)
+    }
+
+    fn get_variables(&self, scope: VariableScope) -> Result<HashMap<String, Value>> {
+        Ok(self
+            .variables
+            .lock()
+            .get(&scope)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn get_variable(&self, scope: VariableScope, key: &str) -> Result<Option<Value>> {
+        Ok(self
+            .variables
+            .lock()
+            .get(&scope)
+            .and_then(|variables| variables.get(key).cloned()))
+    }
+
+    fn set_variable(&mut self, scope: VariableScope, key: &str, value: &Value) -> Result<()> {
+        self.variables
+            .lock()
+            .entry(scope)
+            .or_default()
+            .insert(key.to_string(), value.clone());
+        Ok(())
+    }
+
+    fn delete_variable(&mut self, scope: VariableScope, key: &str) -> Result<()> {
+        if let Some(variables) = self.variables.lock().get_mut(&scope) {
+            variables.remove(key);
+        }
+        Ok(())
+    }
+
+    fn get_secret_names(&self) -> Result<Vec<String>> {
+        Ok(self.secrets.lock().keys().cloned().collect())
+    }
+
+    fn get_secret(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.secrets.lock().get(name).cloned())
+    }
+
+    fn set_secret(&mut self, name: &str, value: &str) -> Result<()> {
+        self.secrets
+            .lock()
+            .insert(name.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn delete_secret(&mut self, name: &str) -> Result<()> {
+        self.secrets.lock().remove(name);
+        Ok(())
+    }
+
+    fn get_result_offload(&self) -> Result<Option<ResultOffload>> {
+        Ok(self.result_offload.clone())
+    }
+
+    fn get_pipeline_options(&self) -> Result<PipelineOptions> {
+        Ok(self.options.clone())
+    }
+
+    fn get_connection_ids(&self) -> Result<Vec<String>> {
+        Ok(self.connections.lock().keys().cloned().collect())
+    }
+
+    fn get_connection(&self, id: &str) -> Result<Option<Connection>> {
+        Ok(self.connections.lock().get(id).cloned())
+    }
+
+    fn set_connection(&mut self, id: &str, connection: &Connection) -> Result<()> {
+        self.connections
+            .lock()
+            .insert(id.to_string(), connection.clone());
+        Ok(())
+    }
+
+    fn delete_connection(&mut self, id: &str) -> Result<()> {
+        self.connections.lock().remove(id);
+        Ok(())
+    }
+
+    fn get_trigger_params(&self, _run_id: usize) -> Result<Value> {
+        Ok(self.trigger_params.lock().clone())
+    }
+
+    fn set_trigger_params(&mut self, _run_id: usize, trigger_params: &Value) -> Result<()> {
+        *self.trigger_params.lock() = trigger_params.clone();
+        Ok(())
     }
 
     fn get_task_result(&mut self, _run_id: usize, task_id: usize) -> Result<TaskResult> {
@@ -137,14 +375,49 @@ impl Backend for InMemoryBackend {
 
     fn set_task_status(
         &mut self,
-        _run_id: usize,
+        run_id: usize,
         task_id: usize,
         task_status: TaskStatus,
+        reason: Option<&str>,
     ) -> Result<()> {
-        self.task_statuses.lock().insert(task_id, task_status);
+        let old_status = self
+            .task_statuses
+            .lock()
+            .insert(task_id, task_status.clone());
+        self.append_run_event(
+            run_id,
+            &RunEvent::new(
+                &self.get_actor(),
+                Some(task_id),
+                old_status.map(|s| format!("{s:?}")),
+                format!("{task_status:?}"),
+                reason,
+            ),
+        )
+    }
+
+    fn get_actor(&self) -> String {
+        env::var("USER").unwrap_or("tpt".to_string())
+    }
+
+    fn append_run_event(&mut self, _run_id: usize, event: &RunEvent) -> Result<()> {
+        self.run_events.lock().push(event.clone());
         Ok(())
     }
 
+    fn get_run_events(&self, _run_id: usize) -> Result<Vec<RunEvent>> {
+        Ok(self.run_events.lock().clone())
+    }
+
+    fn set_run_end_date(&mut self, _run_id: usize, end_date: DateTime<Utc>) -> Result<bool> {
+        let mut run_end_date = self.run_end_date.lock();
+        if run_end_date.is_some() {
+            return Ok(false);
+        }
+        *run_end_date = Some(end_date);
+        Ok(true)
+    }
+
     fn get_dependencies(
         &mut self,
         _run_id: usize,
@@ -232,9 +505,28 @@ impl Backend for InMemoryBackend {
         Ok(self.edges.lock().clone())
     }
 
+    fn get_current_pipeline_hash(&self) -> Result<String> {
+        Ok(hash_pipeline(
+            &self.default_tasks.lock(),
+            &self.edges.lock(),
+            &self.options,
+        ))
+    }
+
+    fn get_tasks_by_pipeline_hash(&self, pipeline_hash: &str) -> Result<Vec<Task>> {
+        self.assert_pipeline_version(pipeline_hash)?;
+        self.get_default_tasks()
+    }
+
+    fn get_edges_by_pipeline_hash(&self, pipeline_hash: &str) -> Result<HashSet<(usize, usize)>> {
+        self.assert_pipeline_version(pipeline_hash)?;
+        self.get_default_edges()
+    }
+
     fn append_new_task_and_set_status_to_pending(
         &mut self,
         _run_id: usize,
+        key: &str,
         name: &str,
         function_name: &str,
         template_args: &Value,
@@ -243,11 +535,13 @@ impl Backend for InMemoryBackend {
         is_dynamic: bool,
         is_branch: bool,
         use_trigger_params: bool,
+        connections: &[String],
     ) -> Result<usize> {
         let mut nodes = self.nodes.lock();
         let new_id = nodes.len();
         nodes.push(Task {
             id: new_id,
+            key: key.to_owned(),
             name: name.to_owned(),
             function: function_name.to_owned(),
             template_args: template_args.to_owned(),
@@ -256,6 +550,7 @@ impl Backend for InMemoryBackend {
             is_dynamic,
             is_branch,
             use_trigger_params,
+            connections: connections.to_vec(),
         });
         Ok(new_id)
     }
@@ -310,11 +605,21 @@ impl Backend for InMemoryBackend {
         is_dynamic: bool,
     ) -> Result<()> {
         let depth = self.get_task_depth(run_id, task_id)?;
+        let attempt: usize = self.get_attempt_by_task_id(run_id, task_id, is_dynamic)?;
+        self.append_run_event(
+            run_id,
+            &RunEvent::new(
+                &self.get_actor(),
+                Some(task_id),
+                Some(format!("{:?}", self.get_task_status(run_id, task_id)?)),
+                "Queued",
+                Some(&format!("attempt {attempt}")),
+            ),
+        )?;
         let mut priority_queue = self.priority_queue.lock();
 
         // remove previous attempts (this is needed for lazy expand)
         priority_queue.retain(|x| x.queued_task.task_id != task_id);
-        let attempt: usize = self.get_attempt_by_task_id(run_id, task_id, is_dynamic)?;
 
         priority_queue.push(OrderedQueuedTask {
             score: depth,
@@ -324,21 +629,37 @@ impl Backend for InMemoryBackend {
                 pipeline_name,
                 scheduled_date_for_run,
                 attempt,
+                traceparent: get_traceparent(&Context::current()),
             },
         });
         Ok(())
     }
 
-    fn take_last_stdout_line(
-        &mut self,
-        _run_id: usize,
-        task_id: usize,
-        _attempt: usize,
-    ) -> Result<Box<dyn Fn() -> Result<String> + Send>> {
-        let task_logs = self.task_logs.clone();
-        Ok(Box::new(move || {
-            Ok(task_logs.lock().entry(task_id).or_default().pop().unwrap())
-        }))
+    fn enqueue_callbacks(&mut self, queued_callback: &QueuedCallback) -> Result<()> {
+        self.callback_queue
+            .lock()
+            .push_back(queued_callback.clone());
+        Ok(())
+    }
+
+    fn pop_callback_queue(&mut self) -> Result<Option<QueuedCallback>> {
+        Ok(self.callback_queue.lock().pop_front())
+    }
+
+    // callbacks run in the thread that popped them, so there is nothing to redeliver
+    fn remove_from_callback_temp_queue(&self, _queued_callback: &QueuedCallback) -> Result<()> {
+        Ok(())
+    }
+
+    fn enqueue_notification(&mut self, notification: &Notification) -> Result<()> {
+        self.notification_queue
+            .lock()
+            .push_back(notification.clone());
+        Ok(())
+    }
+
+    fn pop_notification_queue(&mut self) -> Result<Option<Notification>> {
+        Ok(self.notification_queue.lock().pop_front())
     }
 
     fn get_queue_length(&self) -> Result<usize> {
@@ -358,3 +679,123 @@ impl Backend for InMemoryBackend {
         Ok(self.pipeline_path.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{blanket_backend::BlanketBackend, test_utils::add_task};
+    use thepipelinetool_task::log_line::LogStream;
+
+    fn states(backend: &InMemoryBackend) -> Vec<(Option<usize>, Option<String>, String)> {
+        backend
+            .get_run_events(0)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.task_id, e.old_state, e.new_state))
+            .collect()
+    }
+
+    #[test]
+    fn test_task_status_events() {
+        let mut backend = InMemoryBackend::new("", &[], &HashSet::new()).unwrap();
+        let task_id = add_task(&mut backend, "a", Value::Null, &TaskOptions::default());
+        backend
+            .set_task_status(0, task_id, TaskStatus::Running, None)
+            .unwrap();
+        backend
+            .set_task_status(0, task_id, TaskStatus::Success, Some("done"))
+            .unwrap();
+
+        assert_eq!(
+            states(&backend),
+            vec![
+                (Some(task_id), None, "Running".into()),
+                (Some(task_id), Some("Running".into()), "Success".into()),
+            ]
+        );
+        let events = backend.get_run_events(0).unwrap();
+        assert_eq!(events[1].reason.as_deref(), Some("done"));
+        assert_eq!(events[1].actor, backend.get_actor());
+    }
+
+    #[test]
+    fn test_enqueue_task_event() {
+        let mut backend = InMemoryBackend::new("", &[], &HashSet::new()).unwrap();
+        let task_id = add_task(&mut backend, "a", Value::Null, &TaskOptions::default());
+        backend
+            .enqueue_task(0, task_id, Utc::now(), "in_memory".into(), false)
+            .unwrap();
+
+        let events = backend.get_run_events(0).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].task_id, Some(task_id));
+        assert_eq!(events[0].old_state.as_deref(), Some("Pending"));
+        assert_eq!(events[0].new_state, "Queued");
+        assert_eq!(events[0].reason.as_deref(), Some("attempt 1"));
+    }
+
+    #[test]
+    fn test_log_lines_offset() {
+        let mut backend = InMemoryBackend::new("", &[], &HashSet::new()).unwrap();
+        let handle = backend.get_log_handle_closure(0, 0, 1).unwrap();
+        for (attempt, message) in [(1, "a"), (2, "x"), (1, "b"), (1, "c")] {
+            handle(LogLine::new(LogStream::Stdout, attempt, message)).unwrap();
+        }
+
+        let messages = |backend: &mut InMemoryBackend, attempt, offset| {
+            backend
+                .get_log_lines(0, 0, attempt, offset)
+                .unwrap()
+                .into_iter()
+                .map(|line| line.message)
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(messages(&mut backend, 1, 0), ["a", "b", "c"]);
+        assert_eq!(messages(&mut backend, 1, 2), ["c"]);
+        assert!(messages(&mut backend, 1, 5).is_empty());
+        assert_eq!(messages(&mut backend, 2, 0), ["x"]);
+    }
+
+    #[test]
+    fn test_trigger_events() {
+        let tasks = [Task::dummy(0, "a")];
+        let edges = HashSet::new();
+        let mut backend = InMemoryBackend::new("", &tasks, &edges).unwrap();
+        let run = backend
+            .create_new_run(
+                Utc::now(),
+                &hash_pipeline(&tasks, &edges, &PipelineOptions::default()),
+            )
+            .unwrap();
+        backend.enqueue_run(&run, None).unwrap();
+
+        assert_eq!(
+            states(&backend),
+            vec![
+                (None, None, "Created".into()),
+                (Some(0), Some("Pending".into()), "Queued".into()),
+            ]
+        );
+        assert!(backend
+            .get_run_events(0)
+            .unwrap()
+            .iter()
+            .all(|e| e.claimed_actor.is_none()));
+    }
+
+    #[test]
+    fn test_run_pinned_to_version() {
+        let tasks = [Task::dummy(0, "a")];
+        let edges = HashSet::new();
+        let mut backend = InMemoryBackend::new("", &tasks, &edges).unwrap();
+        let pipeline_hash = backend.get_current_pipeline_hash().unwrap();
+        let run = backend.create_new_run(Utc::now(), &pipeline_hash).unwrap();
+        assert_eq!(run.pipeline_hash, pipeline_hash);
+        backend.enqueue_run(&run, None).unwrap();
+        assert_eq!(backend.get_all_tasks(0).unwrap().len(), 1);
+
+        // a run pinned to another version must not run the current one
+        let run = backend.create_new_run(Utc::now(), "0000000").unwrap();
+        assert!(backend.enqueue_run(&run, None).is_err());
+    }
+}
@@ -5,9 +5,16 @@ use backend::Backend;
 pub mod backend;
 pub mod blanket_backend;
 pub mod in_memory_backend;
+pub mod notifications;
 pub mod pipeline;
+pub mod pipeline_diff;
 pub mod pipeline_options;
+pub mod result_store;
 pub mod run;
+pub mod telemetry;
+pub mod template_variables;
+#[cfg(test)]
+mod test_utils;
 
 const DEFAULT_TPT_X_COMMAND: &str = "tpt_executor";
 
@@ -0,0 +1,298 @@
+use std::{collections::HashMap, fmt::Display, time::Duration};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, Message, SmtpTransport,
+    Transport,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thepipelinetool_task::{log_line::LogLine, queued_task::QueuedTask, task_result::TaskResult};
+
+use crate::template_variables::render_fields;
+
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+    TaskSuccess,
+    TaskFailure,
+    RunSuccess,
+    RunFailure,
+    SlaMiss,
+}
+
+impl Display for NotificationEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            NotificationEvent::TaskSuccess => "task success",
+            NotificationEvent::TaskFailure => "task failure",
+            NotificationEvent::RunSuccess => "run success",
+            NotificationEvent::RunFailure => "run failure",
+            NotificationEvent::SlaMiss => "SLA miss",
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    Tls,
+    #[default]
+    Starttls,
+    None,
+}
+
+// string fields may reference secrets, e.g. `webhook_url: "{{ secret.slack_webhook }}"`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotificationSink {
+    // POSTs the notification as JSON, with the rendered text in `message`
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+    },
+    // Slack-compatible incoming webhook
+    Slack {
+        webhook_url: String,
+    },
+    Email {
+        smtp_host: String,
+        #[serde(default)]
+        smtp_port: Option<u16>,
+        #[serde(default)]
+        security: SmtpSecurity,
+        #[serde(default)]
+        username: Option<String>,
+        #[serde(default)]
+        password: Option<String>,
+        from: String,
+        to: Vec<String>,
+    },
+}
+
+fn default_events() -> Vec<NotificationEvent> {
+    vec![
+        NotificationEvent::TaskFailure,
+        NotificationEvent::RunFailure,
+        NotificationEvent::SlaMiss,
+    ]
+}
+
+fn default_log_tail_lines() -> usize {
+    20
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NotificationConfig {
+    pub sink: NotificationSink,
+
+    #[serde(default = "default_events")]
+    pub events: Vec<NotificationEvent>,
+
+    // `{{ field }}` is replaced by the field of the `Notification`, e.g. `{{ log_tail }}`
+    #[serde(default)]
+    pub template: Option<String>,
+
+    #[serde(default = "default_log_tail_lines")]
+    pub log_tail_lines: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Notification {
+    pub event: NotificationEvent,
+    pub pipeline_name: String,
+    pub run_id: usize,
+    pub scheduled_date_for_run: DateTime<Utc>,
+    pub task_id: Option<usize>,
+    pub task_name: Option<String>,
+    pub attempt: Option<usize>,
+    pub error: Option<String>,
+    // last lines of the task's log, filled in per sink
+    pub log_tail: String,
+}
+
+impl Notification {
+    pub fn new(
+        event: NotificationEvent,
+        pipeline_name: &str,
+        run_id: usize,
+        scheduled_date_for_run: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            event,
+            pipeline_name: pipeline_name.to_string(),
+            run_id,
+            scheduled_date_for_run,
+            task_id: None,
+            task_name: None,
+            attempt: None,
+            error: None,
+            log_tail: "".into(),
+        }
+    }
+
+    pub fn from_task_result(
+        event: NotificationEvent,
+        queued_task: &QueuedTask,
+        result: &TaskResult,
+    ) -> Self {
+        Self {
+            task_id: Some(result.task_id),
+            task_name: Some(result.name.clone()),
+            attempt: Some(result.attempt),
+            error: match &result.error {
+                Some(error) => Some(format!("{}: {}", error.kind, error.message)),
+                None if result.premature_failure => {
+                    Some(result.premature_failure_error_str.clone())
+                }
+                None => None,
+            },
+            ..Self::new(
+                event,
+                &queued_task.pipeline_name,
+                queued_task.run_id,
+                queued_task.scheduled_date_for_run,
+            )
+        }
+    }
+
+    pub fn with_log_tail(mut self, log_lines: &[LogLine], lines: usize) -> Self {
+        self.log_tail = log_lines[log_lines.len().saturating_sub(lines)..]
+            .iter()
+            .map(|line| line.message.as_str())
+            .collect::<Vec<&str>>()
+            .join("\n");
+        self
+    }
+
+    fn default_message(&self) -> String {
+        let mut message = format!(
+            "{}: {} for run {} scheduled for {}",
+            self.pipeline_name, self.event, self.run_id, self.scheduled_date_for_run
+        );
+        if let (Some(task_name), Some(task_id)) = (&self.task_name, self.task_id) {
+            message.push_str(&format!("\ntask: {task_name} ({task_id})"));
+            if let Some(attempt) = self.attempt {
+                message.push_str(&format!(", attempt {attempt}"));
+            }
+        }
+        if let Some(error) = &self.error {
+            message.push_str(&format!("\nerror: {error}"));
+        }
+        if !self.log_tail.is_empty() {
+            message.push_str(&format!("\nlog tail:\n{}", self.log_tail));
+        }
+        message
+    }
+
+    pub fn render(&self, template: &Option<String>) -> String {
+        match template {
+            Some(template) => render_fields(template, &json!(self)),
+            None => self.default_message(),
+        }
+    }
+}
+
+impl NotificationSink {
+    pub fn send(&self, notification: &Notification, message: &str) -> Result<()> {
+        match self {
+            NotificationSink::Webhook { url, headers } => {
+                let mut body = json!(notification);
+                body["message"] = json!(message);
+                let mut request = ureq::post(url).timeout(SEND_TIMEOUT);
+                for (name, value) in headers {
+                    request = request.set(name, value);
+                }
+                request
+                    .set("Content-Type", "application/json")
+                    .send_string(&body.to_string())?;
+            }
+            NotificationSink::Slack { webhook_url } => {
+                ureq::post(webhook_url)
+                    .timeout(SEND_TIMEOUT)
+                    .set("Content-Type", "application/json")
+                    .send_string(&json!({ "text": message }).to_string())?;
+            }
+            NotificationSink::Email {
+                smtp_host,
+                smtp_port,
+                security,
+                username,
+                password,
+                from,
+                to,
+            } => {
+                let mut email = Message::builder()
+                    .from(from.parse::<Mailbox>()?)
+                    // the first line doubles as the subject
+                    .subject(message.lines().next().unwrap_or_default());
+                for to in to {
+                    email = email.to(to.parse::<Mailbox>()?);
+                }
+                let email = email.body(message.to_string())?;
+
+                let mut transport = match security {
+                    SmtpSecurity::Tls => SmtpTransport::relay(smtp_host)?,
+                    SmtpSecurity::Starttls => SmtpTransport::starttls_relay(smtp_host)?,
+                    SmtpSecurity::None => SmtpTransport::builder_dangerous(smtp_host),
+                }
+                .timeout(Some(SEND_TIMEOUT));
+                if let Some(smtp_port) = smtp_port {
+                    transport = transport.port(*smtp_port);
+                }
+                match (username, password) {
+                    (Some(username), Some(password)) => {
+                        transport = transport
+                            .credentials(Credentials::new(username.clone(), password.clone()));
+                    }
+                    (None, None) => {}
+                    _ => return Err(anyhow!("smtp username and password must be set together")),
+                }
+                transport.build().send(&email)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut notification = Notification::new(
+            NotificationEvent::TaskFailure,
+            "simple",
+            3,
+            Utc.with_ymd_and_hms(2024, 1, 1, 7, 0, 0).unwrap(),
+        );
+        notification.task_id = Some(1);
+        notification.task_name = Some("load".into());
+        notification.attempt = Some(2);
+        notification.error = Some("Panic: boom".into());
+        notification.log_tail = "line 1\nline 2".into();
+
+        assert_eq!(
+            notification.render(&None),
+            "simple: task failure for run 3 scheduled for 2024-01-01 07:00:00 UTC\n\
+             task: load (1), attempt 2\n\
+             error: Panic: boom\n\
+             log tail:\n\
+             line 1\n\
+             line 2"
+        );
+        assert_eq!(
+            notification.render(&Some(
+                "{{ pipeline_name }}/{{task_name}} #{{ attempt }} {{ missing }}{{ event }}".into()
+            )),
+            "simple/load #2 task_failure"
+        );
+    }
+}
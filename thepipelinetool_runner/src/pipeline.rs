@@ -1,7 +1,11 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
 use thepipelinetool_task::Task;
+use thepipelinetool_utils::UPSTREAM_TASK_ID_KEY;
 
 use crate::pipeline_options::PipelineOptions;
 
@@ -12,3 +16,157 @@ pub struct Pipeline {
     pub tasks: Vec<Task>,
     pub edges: HashSet<(usize, usize)>,
 }
+
+impl Pipeline {
+    pub fn hash(&self) -> String {
+        hash_pipeline(&self.tasks, &self.edges, &self.options)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct PipelineVersion {
+    pub pipeline_hash: String,
+    pub uploaded_date: DateTime<Utc>,
+}
+
+// replace upstream ids in template args with the given labels (e.g. task keys)
+pub(crate) fn replace_upstream_ids(value: &Value, labels: &HashMap<usize, String>) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| {
+                    let v = match (k.as_str(), v.as_u64()) {
+                        (UPSTREAM_TASK_ID_KEY, Some(upstream_id)) => {
+                            match labels.get(&(upstream_id as usize)) {
+                                Some(label) => Value::String(label.clone()),
+                                None => v.clone(),
+                            }
+                        }
+                        _ => replace_upstream_ids(v, labels),
+                    };
+                    (k.clone(), v)
+                })
+                .collect(),
+        ),
+        Value::Array(array) => Value::Array(
+            array
+                .iter()
+                .map(|v| replace_upstream_ids(v, labels))
+                .collect(),
+        ),
+        _ => value.clone(),
+    }
+}
+
+// tasks are hashed by key so that the hash does not depend on the order tasks were added in,
+// tasks without a key fall back to their id. the hash covers the options too and uses sha256
+// over json with sorted keys, so it stays the same across builds and toolchains
+pub fn hash_pipeline(
+    tasks: &[Task],
+    edges: &HashSet<(usize, usize)>,
+    options: &PipelineOptions,
+) -> String {
+    let keys: HashMap<usize, String> = tasks
+        .iter()
+        .map(|t| {
+            (
+                t.id,
+                if t.key.is_empty() {
+                    format!("#{}", t.id)
+                } else {
+                    t.key.clone()
+                },
+            )
+        })
+        .collect();
+
+    let mut tasks = tasks
+        .iter()
+        .map(|t| {
+            let mut task = serde_json::to_value(t).unwrap();
+            task.as_object_mut().unwrap().remove("id");
+            task["template_args"] = replace_upstream_ids(&t.template_args, &keys);
+            (keys[&t.id].clone(), task)
+        })
+        .collect::<Vec<(String, Value)>>();
+    tasks.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut edges = edges
+        .iter()
+        .map(|(up, down)| (&keys[up], &keys[down]))
+        .collect::<Vec<(&String, &String)>>();
+    edges.sort();
+
+    let digest = Sha256::digest(
+        json!({
+            "tasks": tasks,
+            "edges": edges,
+            "options": options,
+        })
+        .to_string(),
+    );
+    to_base62(u64::from_be_bytes(digest[..8].try_into().unwrap()))
+}
+
+const BASE62: &[char] = &[
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i',
+    'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', 'A', 'B',
+    'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U',
+    'V', 'W', 'X', 'Y', 'Z',
+];
+
+fn to_base62(mut num: u64) -> String {
+    let mut chars = vec![];
+
+    while num > 0 {
+        chars.push(BASE62[(num % 62) as usize]);
+        num /= 62;
+    }
+
+    chars.reverse();
+
+    while chars.len() < 7 {
+        chars.push('0');
+    }
+
+    chars.truncate(7); // Ensure length is 7
+    chars.iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_keyless_pipeline() {
+        let options = PipelineOptions::default();
+        let tasks = vec![Task::dummy(0, ""), Task::dummy(1, ""), Task::dummy(2, "")];
+        assert_ne!(
+            hash_pipeline(&tasks, &HashSet::from([(0, 1)]), &options),
+            hash_pipeline(&tasks, &HashSet::from([(1, 2)]), &options)
+        );
+
+        let keyed = vec![Task::dummy(0, "b"), Task::dummy(1, "a")];
+        let reordered = vec![Task::dummy(0, "a"), Task::dummy(1, "b")];
+        assert_eq!(
+            hash_pipeline(&keyed, &HashSet::from([(0, 1)]), &options),
+            hash_pipeline(&reordered, &HashSet::from([(1, 0)]), &options)
+        );
+    }
+
+    #[test]
+    fn test_hash_is_stable_and_covers_options() {
+        let tasks = vec![Task {
+            name: "t".into(),
+            function: "t".into(),
+            ..Task::dummy(0, "a")
+        }];
+        let mut options = PipelineOptions::default();
+        // a changed hash for the same pipeline makes every stored version look new
+        let hash = hash_pipeline(&tasks, &HashSet::new(), &options);
+        assert_eq!(hash, "eWdW6tx");
+
+        options.schedule = Some("0 0 * * *".into());
+        assert_ne!(hash_pipeline(&tasks, &HashSet::new(), &options), hash);
+    }
+}
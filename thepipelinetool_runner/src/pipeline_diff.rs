@@ -0,0 +1,469 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thepipelinetool_task::Task;
+
+use crate::pipeline::{replace_upstream_ids, Pipeline};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ValueChange {
+    pub field: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaskDiff {
+    pub task: String,
+    pub changed_fields: Vec<ValueChange>,
+    pub changed_options: Vec<ValueChange>,
+    pub template_args: Option<ValueChange>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PipelineDiff {
+    pub added_tasks: Vec<String>,
+    pub removed_tasks: Vec<String>,
+    pub renamed_tasks: Vec<(String, String)>,
+    pub changed_tasks: Vec<TaskDiff>,
+    pub added_edges: Vec<(String, String)>,
+    pub removed_edges: Vec<(String, String)>,
+    pub changed_options: Vec<ValueChange>,
+}
+
+impl PipelineDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_tasks.is_empty()
+            && self.removed_tasks.is_empty()
+            && self.renamed_tasks.is_empty()
+            && self.changed_tasks.is_empty()
+            && self.added_edges.is_empty()
+            && self.removed_edges.is_empty()
+            && self.changed_options.is_empty()
+    }
+}
+
+// tasks are matched by key, or for pipelines without keys by name and then order
+fn get_task_labels(tasks: &[Task]) -> HashMap<usize, String> {
+    if tasks.iter().all(|t| !t.key.is_empty()) {
+        return tasks.iter().map(|t| (t.id, t.key.clone())).collect();
+    }
+
+    let mut name_counts: HashMap<&str, usize> = HashMap::new();
+    for task in tasks {
+        *name_counts.entry(&task.name).or_default() += 1;
+    }
+
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    let mut labels = HashMap::new();
+    for task in tasks {
+        let index = seen.entry(&task.name).or_default();
+        labels.insert(
+            task.id,
+            if name_counts[task.name.as_str()] > 1 {
+                format!("{}[{}]", task.name, index)
+            } else {
+                task.name.clone()
+            },
+        );
+        *index += 1;
+    }
+    labels
+}
+
+fn get_label(labels: &HashMap<usize, String>, id: usize) -> Result<&String> {
+    labels
+        .get(&id)
+        .ok_or_else(|| anyhow!("pipeline has an edge to missing task {id}"))
+}
+
+fn diff_values(old: &Value, new: &Value, prefix: &str) -> Vec<ValueChange> {
+    let empty = serde_json::Map::new();
+    let old = old.as_object().unwrap_or(&empty);
+    let new = new.as_object().unwrap_or(&empty);
+
+    let mut fields: Vec<&String> = old.keys().chain(new.keys()).collect();
+    fields.sort();
+    fields.dedup();
+
+    fields
+        .into_iter()
+        .filter(|field| old.get(*field) != new.get(*field))
+        .map(|field| ValueChange {
+            field: format!("{prefix}{field}"),
+            old: old.get(field).cloned().unwrap_or(Value::Null),
+            new: new.get(field).cloned().unwrap_or(Value::Null),
+        })
+        .collect()
+}
+
+fn get_task_definition(task: &Task, labels: &HashMap<usize, String>) -> Value {
+    let mut definition = serde_json::to_value(task).unwrap();
+    let definition_map = definition.as_object_mut().unwrap();
+    definition_map.remove("id");
+    definition_map.remove("key");
+    definition_map.remove("name");
+    definition_map.insert(
+        "template_args".into(),
+        replace_upstream_ids(&task.template_args, labels),
+    );
+    definition
+}
+
+fn diff_tasks(
+    label: &str,
+    old: &Task,
+    new: &Task,
+    old_labels: &HashMap<usize, String>,
+    new_labels: &HashMap<usize, String>,
+) -> Option<TaskDiff> {
+    let mut old_definition = get_task_definition(old, old_labels);
+    let mut new_definition = get_task_definition(new, new_labels);
+    if old_definition == new_definition {
+        return None;
+    }
+
+    let old_template_args = old_definition["template_args"].take();
+    let new_template_args = new_definition["template_args"].take();
+    let old_options = old_definition["options"].take();
+    let new_options = new_definition["options"].take();
+
+    Some(TaskDiff {
+        task: label.to_string(),
+        changed_fields: diff_values(&old_definition, &new_definition, "")
+            .into_iter()
+            .filter(|c| c.field != "template_args" && c.field != "options")
+            .collect(),
+        changed_options: diff_values(&old_options, &new_options, "options."),
+        template_args: if old_template_args != new_template_args {
+            Some(ValueChange {
+                field: "template_args".into(),
+                old: old_template_args,
+                new: new_template_args,
+            })
+        } else {
+            None
+        },
+    })
+}
+
+pub fn diff_pipelines(old: &Pipeline, new: &Pipeline) -> Result<PipelineDiff> {
+    let old_labels = get_task_labels(&old.tasks);
+    let new_labels = get_task_labels(&new.tasks);
+    let old_tasks: HashMap<&str, &Task> = old
+        .tasks
+        .iter()
+        .map(|t| Ok((get_label(&old_labels, t.id)?.as_str(), t)))
+        .collect::<Result<_>>()?;
+    let new_tasks: HashMap<&str, &Task> = new
+        .tasks
+        .iter()
+        .map(|t| Ok((get_label(&new_labels, t.id)?.as_str(), t)))
+        .collect::<Result<_>>()?;
+    let mut diff = PipelineDiff::default();
+
+    let mut removed: Vec<&Task> = vec![];
+    for task in &old.tasks {
+        let label = get_label(&old_labels, task.id)?.as_str();
+        match new_tasks.get(label) {
+            Some(new_task) => {
+                if let Some(task_diff) = diff_tasks(label, task, new_task, &old_labels, &new_labels)
+                {
+                    diff.changed_tasks.push(task_diff);
+                }
+            }
+            None => removed.push(task),
+        }
+    }
+
+    let mut added: Vec<&Task> = vec![];
+    for task in &new.tasks {
+        if !old_tasks.contains_key(get_label(&new_labels, task.id)?.as_str()) {
+            added.push(task);
+        }
+    }
+
+    // a removed and an added task with the same definition are treated as a rename
+    for old_task in removed {
+        let old_definition = get_task_definition(old_task, &old_labels);
+        match added
+            .iter()
+            .position(|t| get_task_definition(t, &new_labels) == old_definition)
+        {
+            Some(index) => {
+                let new_task = added.remove(index);
+                diff.renamed_tasks.push((
+                    get_label(&old_labels, old_task.id)?.clone(),
+                    get_label(&new_labels, new_task.id)?.clone(),
+                ));
+            }
+            None => diff
+                .removed_tasks
+                .push(get_label(&old_labels, old_task.id)?.clone()),
+        }
+    }
+    diff.added_tasks = added
+        .iter()
+        .map(|t| Ok(get_label(&new_labels, t.id)?.clone()))
+        .collect::<Result<_>>()?;
+
+    let renamed: HashMap<&String, &String> = diff
+        .renamed_tasks
+        .iter()
+        .map(|(old_label, new_label)| (old_label, new_label))
+        .collect();
+    let old_edges: HashSet<(String, String)> = old
+        .edges
+        .iter()
+        .map(|(up, down)| {
+            let (up, down) = (get_label(&old_labels, *up)?, get_label(&old_labels, *down)?);
+            Ok((
+                renamed.get(up).copied().unwrap_or(up).clone(),
+                renamed.get(down).copied().unwrap_or(down).clone(),
+            ))
+        })
+        .collect::<Result<_>>()?;
+    let new_edges: HashSet<(String, String)> = new
+        .edges
+        .iter()
+        .map(|(up, down)| {
+            Ok((
+                get_label(&new_labels, *up)?.clone(),
+                get_label(&new_labels, *down)?.clone(),
+            ))
+        })
+        .collect::<Result<_>>()?;
+
+    diff.added_edges = new_edges.difference(&old_edges).cloned().collect();
+    diff.removed_edges = old_edges.difference(&new_edges).cloned().collect();
+    diff.added_edges.sort();
+    diff.removed_edges.sort();
+
+    diff.changed_options = diff_values(
+        &serde_json::to_value(&old.options).unwrap(),
+        &serde_json::to_value(&new.options).unwrap(),
+        "",
+    );
+
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use thepipelinetool_utils::UPSTREAM_TASK_ID_KEY;
+
+    use super::*;
+    use crate::pipeline_options::PipelineOptions;
+
+    fn pipeline(tasks: Vec<Task>, edges: &[(usize, usize)]) -> Pipeline {
+        Pipeline {
+            path: "".into(),
+            options: PipelineOptions::default(),
+            tasks,
+            edges: edges.iter().copied().collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff_added_removed_and_changed_tasks() {
+        let old = pipeline(
+            vec![
+                Task {
+                    function: "f".into(),
+                    ..Task::dummy(0, "a")
+                },
+                Task {
+                    function: "f".into(),
+                    ..Task::dummy(1, "b")
+                },
+            ],
+            &[(0, 1)],
+        );
+        let mut a = Task {
+            function: "g".into(),
+            ..Task::dummy(0, "a")
+        };
+        a.options.max_attempts = 3;
+        a.template_args = json!({ "x": 1 });
+        let new = pipeline(
+            vec![
+                a,
+                Task {
+                    function: "h".into(),
+                    ..Task::dummy(1, "c")
+                },
+            ],
+            &[(0, 1)],
+        );
+
+        let diff = diff_pipelines(&old, &new).unwrap();
+        assert_eq!(diff.added_tasks, vec!["c".to_string()]);
+        assert_eq!(diff.removed_tasks, vec!["b".to_string()]);
+        assert!(diff.renamed_tasks.is_empty());
+        assert_eq!(diff.added_edges, vec![("a".into(), "c".into())]);
+        assert_eq!(diff.removed_edges, vec![("a".into(), "b".into())]);
+
+        assert_eq!(diff.changed_tasks.len(), 1);
+        let task_diff = &diff.changed_tasks[0];
+        assert_eq!(task_diff.task, "a");
+        assert_eq!(
+            task_diff.changed_fields,
+            vec![ValueChange {
+                field: "function".into(),
+                old: json!("f"),
+                new: json!("g"),
+            }]
+        );
+        assert_eq!(
+            task_diff.changed_options,
+            vec![ValueChange {
+                field: "options.max_attempts".into(),
+                old: json!(1),
+                new: json!(3),
+            }]
+        );
+        assert_eq!(
+            task_diff.template_args,
+            Some(ValueChange {
+                field: "template_args".into(),
+                old: Value::Null,
+                new: json!({ "x": 1 }),
+            })
+        );
+    }
+
+    #[test]
+    fn test_diff_renamed_task() {
+        // c references the renamed task, so its template args change with the label
+        let mut c = Task {
+            function: "g".into(),
+            ..Task::dummy(2, "c")
+        };
+        c.template_args = json!({ UPSTREAM_TASK_ID_KEY: 1 });
+        let old = pipeline(
+            vec![
+                Task {
+                    function: "f".into(),
+                    ..Task::dummy(0, "a")
+                },
+                Task {
+                    function: "f".into(),
+                    ..Task::dummy(1, "b")
+                },
+                c.clone(),
+            ],
+            &[(0, 1), (1, 2)],
+        );
+        let new = pipeline(
+            vec![
+                Task {
+                    function: "f".into(),
+                    ..Task::dummy(0, "a")
+                },
+                Task {
+                    function: "f".into(),
+                    ..Task::dummy(1, "b2")
+                },
+                c,
+            ],
+            &[(0, 1), (1, 2)],
+        );
+
+        let diff = diff_pipelines(&old, &new).unwrap();
+        assert_eq!(diff.renamed_tasks, vec![("b".into(), "b2".into())]);
+        assert!(diff.added_tasks.is_empty());
+        assert!(diff.removed_tasks.is_empty());
+        assert!(diff.added_edges.is_empty());
+        assert!(diff.removed_edges.is_empty());
+        assert_eq!(diff.changed_tasks.len(), 1);
+        assert_eq!(diff.changed_tasks[0].task, "c");
+        assert!(diff.changed_tasks[0].changed_fields.is_empty());
+        assert!(diff.changed_tasks[0].template_args.is_some());
+        assert!(!diff.is_empty());
+    }
+
+    #[test]
+    fn test_task_labels_fall_back_to_name() {
+        let tasks = vec![
+            Task {
+                key: "".into(),
+                function: "f".into(),
+                ..Task::dummy(0, "x")
+            },
+            Task {
+                key: "".into(),
+                function: "f".into(),
+                ..Task::dummy(1, "y")
+            },
+            Task {
+                function: "f".into(),
+                ..Task::dummy(2, "y")
+            },
+        ];
+        assert_eq!(
+            get_task_labels(&tasks),
+            HashMap::from([
+                (0, "x".to_string()),
+                (1, "y[0]".to_string()),
+                (2, "y[1]".to_string()),
+            ])
+        );
+
+        let old = pipeline(tasks.clone(), &[(0, 1), (0, 2)]);
+        let mut new_tasks = tasks;
+        new_tasks[2].function = "g".into();
+        let diff = diff_pipelines(&old, &pipeline(new_tasks, &[(0, 1), (0, 2)])).unwrap();
+        assert_eq!(diff.changed_tasks.len(), 1);
+        assert_eq!(diff.changed_tasks[0].task, "y[1]");
+        assert!(diff.added_edges.is_empty() && diff.removed_edges.is_empty());
+    }
+
+    #[test]
+    fn test_diff_unchanged_pipeline() {
+        let old = pipeline(
+            vec![Task {
+                function: "f".into(),
+                ..Task::dummy(0, "a")
+            }],
+            &[],
+        );
+        let mut new = pipeline(
+            vec![Task {
+                function: "f".into(),
+                ..Task::dummy(0, "a")
+            }],
+            &[],
+        );
+        assert!(diff_pipelines(&old, &new).unwrap().is_empty());
+
+        new.options.max_attempts = 5;
+        let diff = diff_pipelines(&old, &new).unwrap();
+        assert_eq!(diff.changed_options.len(), 1);
+        assert_eq!(diff.changed_options[0].field, "max_attempts");
+    }
+
+    #[test]
+    fn test_diff_edge_to_missing_task() {
+        let old = pipeline(
+            vec![Task {
+                function: "f".into(),
+                ..Task::dummy(0, "a")
+            }],
+            &[],
+        );
+        let new = pipeline(
+            vec![Task {
+                function: "f".into(),
+                ..Task::dummy(0, "a")
+            }],
+            &[(0, 3)],
+        );
+        assert_eq!(
+            diff_pipelines(&old, &new).unwrap_err().to_string(),
+            "pipeline has an edge to missing task 3"
+        );
+    }
+}
@@ -5,6 +5,8 @@ use chrono::{DateTime, NaiveDateTime, Utc};
 use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 
+use crate::notifications::NotificationConfig;
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PipelineOptions {
     #[serde(default)]
@@ -27,6 +29,21 @@ pub struct PipelineOptions {
 
     #[serde(default)]
     pub timezone: Option<Tz>,
+
+    // the server deletes finished runs beyond the newest `retention_runs` and those scheduled
+    // more than `retention_days` ago
+    #[serde(default)]
+    pub retention_runs: Option<usize>,
+
+    #[serde(default)]
+    pub retention_days: Option<u64>,
+
+    #[serde(default)]
+    pub notifications: Vec<NotificationConfig>,
+
+    // runs are expected to be done this long after their scheduled date
+    #[serde(default)]
+    pub sla: Option<Duration>,
 }
 
 impl Default for PipelineOptions {
@@ -39,6 +56,10 @@ impl Default for PipelineOptions {
             timeout: None,
             catchup_date: None,
             timezone: None,
+            retention_runs: None,
+            retention_days: None,
+            notifications: vec![],
+            sla: None,
         }
     }
 }
@@ -51,6 +72,10 @@ impl PipelineOptions {
     pub fn get_end_date_with_timezone(&self) -> Option<DateTime<Utc>> {
         naive_datetime_to_datetime_with_timezone(&self.end_date, &self.timezone)
     }
+
+    pub fn get_sla_deadline(&self, scheduled_date_for_run: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        Some(scheduled_date_for_run + chrono::Duration::from_std(self.sla?).ok()?)
+    }
 }
 
 fn naive_datetime_to_datetime_with_timezone(
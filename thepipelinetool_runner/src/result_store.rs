@@ -0,0 +1,123 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use thepipelinetool_task::task_result::TaskResult;
+
+pub const DEFAULT_RESULT_OFFLOAD_THRESHOLD: usize = 1024 * 1024;
+
+/// Object storage for results too large to keep in the backend.
+pub trait ResultStore: Send + Sync {
+    /// Writes `bytes` under `key` and returns the reference later passed to `get`.
+    fn put(&self, key: &str, bytes: Vec<u8>) -> Result<String>;
+    fn get(&self, reference: &str) -> Result<Vec<u8>>;
+    fn delete(&self, reference: &str) -> Result<()>;
+}
+
+/// Stores results as files below `path`, referenced as `file://{path}/{key}`.
+pub struct FileResultStore {
+    pub path: PathBuf,
+}
+
+impl ResultStore for FileResultStore {
+    fn put(&self, key: &str, bytes: Vec<u8>) -> Result<String> {
+        let path = self.path.join(key);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, bytes)?;
+        Ok(format!("file://{}", path.display()))
+    }
+
+    fn get(&self, reference: &str) -> Result<Vec<u8>> {
+        let path = reference
+            .strip_prefix("file://")
+            .ok_or_else(|| anyhow!("unsupported result reference '{reference}'"))?;
+        Ok(fs::read(path)?)
+    }
+
+    fn delete(&self, reference: &str) -> Result<()> {
+        let path = reference
+            .strip_prefix("file://")
+            .ok_or_else(|| anyhow!("unsupported result reference '{reference}'"))?;
+        Ok(fs::remove_file(path)?)
+    }
+}
+
+/// Results whose JSON is larger than `threshold` bytes are written to `store`, the `TaskResult`
+/// only keeps the reference in `result_ref`.
+#[derive(Clone)]
+pub struct ResultOffload {
+    pub store: Arc<dyn ResultStore>,
+    pub threshold: usize,
+}
+
+impl ResultOffload {
+    pub fn offload(&self, key: &str, task_result: &mut TaskResult) -> Result<()> {
+        let bytes = serde_json::to_vec(&task_result.result)?;
+        if bytes.len() > self.threshold {
+            task_result.result_ref = Some(self.store.put(key, bytes)?);
+            task_result.result = Value::Null;
+        }
+        Ok(())
+    }
+
+    pub fn load(&self, reference: &str) -> Result<Value> {
+        Ok(serde_json::from_slice(&self.store.get(reference)?)?)
+    }
+
+    pub fn delete(&self, reference: &str) -> Result<()> {
+        self.store.delete(reference)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_offload_above_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let result_offload = ResultOffload {
+            store: Arc::new(FileResultStore {
+                path: dir.path().to_path_buf(),
+            }),
+            threshold: 16,
+        };
+        let mut small = TaskResult::premature_error(
+            0,
+            1,
+            1,
+            "small".into(),
+            "small".into(),
+            "".into(),
+            false,
+            false,
+            None,
+            None,
+        );
+        small.result = json!("ok");
+        let mut large = small.clone();
+        large.result = json!(vec![1; 100]);
+
+        result_offload.offload("run/0.json", &mut small).unwrap();
+        result_offload.offload("run/1.json", &mut large).unwrap();
+
+        assert_eq!(small.result, json!("ok"));
+        assert!(small.result_ref.is_none());
+        assert_eq!(large.result, Value::Null);
+        assert_eq!(
+            result_offload
+                .load(large.result_ref.as_ref().unwrap())
+                .unwrap(),
+            json!(vec![1; 100])
+        );
+
+        result_offload
+            .delete(large.result_ref.as_ref().unwrap())
+            .unwrap();
+        assert!(result_offload
+            .load(large.result_ref.as_ref().unwrap())
+            .is_err());
+    }
+}
@@ -6,6 +6,9 @@ pub struct Run {
     pub run_id: usize,
     pub pipeline_name: String,
     pub scheduled_date_for_run: DateTime<Utc>,
+
+    #[serde(default)]
+    pub pipeline_hash: String,
 }
 
 impl Run {
@@ -14,6 +17,7 @@ impl Run {
             run_id: 0,
             pipeline_name: "dummy".to_string(),
             scheduled_date_for_run: Utc::now(),
+            pipeline_hash: "".to_string(),
         }
     }
 }
@@ -26,3 +30,49 @@ pub enum RunStatus {
     Running,
     RetryPending,
 }
+
+/// A state transition of a run or one of its tasks, appended to the run's event log.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RunEvent {
+    pub timestamp: DateTime<Utc>,
+    pub actor: String,
+    // not set for events of the run itself
+    #[serde(default)]
+    pub task_id: Option<usize>,
+    #[serde(default)]
+    pub old_state: Option<String>,
+    pub new_state: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+    // who the caller says it is, e.g. with the X-Actor header, not verified
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claimed_actor: Option<String>,
+}
+
+impl RunEvent {
+    pub fn new(
+        actor: &str,
+        task_id: Option<usize>,
+        old_state: Option<String>,
+        new_state: impl ToString,
+        reason: Option<&str>,
+    ) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            actor: actor.to_string(),
+            task_id,
+            old_state,
+            new_state: new_state.to_string(),
+            reason: reason.map(|r| r.to_string()),
+            claimed_actor: None,
+        }
+    }
+}
+
+// one JSON object per line
+pub fn render_run_events_jsonl(events: &[RunEvent]) -> String {
+    events
+        .iter()
+        .map(|event| serde_json::to_string(event).unwrap() + "\n")
+        .collect()
+}
@@ -0,0 +1,81 @@
+use std::env;
+
+use anyhow::Result;
+use opentelemetry::{
+    global::{self, BoxedTracer},
+    trace::{SpanContext, SpanId, Status, TraceContextExt, TraceFlags, TraceId, TraceState},
+    Context,
+};
+use thepipelinetool_task::task_context::TRACEPARENT_ENV;
+
+// spans are dropped unless the process installed a tracer provider, e.g. the server binaries
+pub fn get_tracer() -> BoxedTracer {
+    global::tracer("thepipelinetool")
+}
+
+/// W3C trace context of the span active in `cx`, e.g.
+/// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`.
+pub fn get_traceparent(cx: &Context) -> Option<String> {
+    let span = cx.span();
+    let span_context = span.span_context();
+    if !span_context.is_valid() {
+        return None;
+    }
+    Some(format!(
+        "00-{:032x}-{:016x}-{:02x}",
+        span_context.trace_id(),
+        span_context.span_id(),
+        span_context.trace_flags()
+    ))
+}
+
+// spans started in the returned context continue the trace of another process
+pub fn context_from_traceparent(traceparent: &str) -> Context {
+    let parts: Vec<&str> = traceparent.trim().split('-').collect();
+    let ["00", trace_id, span_id, flags] = parts[..] else {
+        return Context::new();
+    };
+    match (
+        TraceId::from_hex(trace_id),
+        SpanId::from_hex(span_id),
+        u8::from_str_radix(flags, 16),
+    ) {
+        (Ok(trace_id), Ok(span_id), Ok(flags)) => {
+            Context::new().with_remote_span_context(SpanContext::new(
+                trace_id,
+                span_id,
+                TraceFlags::new(flags),
+                true,
+                TraceState::default(),
+            ))
+        }
+        _ => Context::new(),
+    }
+}
+
+// marks the span active in `cx` as failed when `result` is an error
+pub fn record_error<T>(cx: &Context, result: Result<T>) -> Result<T> {
+    if let Err(e) = &result {
+        cx.span().set_status(Status::error(e.to_string()));
+    }
+    result
+}
+
+pub fn context_from_env() -> Context {
+    env::var(TRACEPARENT_ENV).map_or_else(|_| Context::new(), |tp| context_from_traceparent(&tp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traceparent_round_trip() {
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let cx = context_from_traceparent(traceparent);
+
+        assert!(cx.span().span_context().is_remote());
+        assert_eq!(get_traceparent(&cx).as_deref(), Some(traceparent));
+        assert_eq!(get_traceparent(&context_from_traceparent("garbage")), None);
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::Result;
+use serde_json::Value;
+
+pub const VARIABLE_REFERENCE_PREFIX: &str = "var.";
+pub const SECRET_REFERENCE_PREFIX: &str = "secret.";
+pub const REDACTED: &str = "***";
+
+// '{{ var.name }}' and '{{ secret.name }}' are resolved when the task runs instead of
+// referencing an upstream task
+pub fn is_variable_reference(reference: &str) -> bool {
+    reference.starts_with(VARIABLE_REFERENCE_PREFIX)
+        || reference.starts_with(SECRET_REFERENCE_PREFIX)
+}
+
+fn resolve_string<F>(original_string: &str, lookup: &mut F) -> Result<Value>
+where
+    F: FnMut(&str) -> Result<Value>,
+{
+    let mut resolved = String::new();
+    let mut temp_string = original_string;
+
+    while let Some(left) = temp_string.find("{{") {
+        let Some(right) = temp_string[left..].find("}}").map(|right| left + right) else {
+            break;
+        };
+        let reference = temp_string[(left + 2)..right].trim();
+        resolved.push_str(&temp_string[..left]);
+
+        if is_variable_reference(reference) {
+            let value = lookup(reference)?;
+
+            // keep the type of the value if it makes up the whole string
+            if resolved.is_empty() && temp_string[(right + 2)..].is_empty() {
+                return Ok(value);
+            }
+            match value {
+                Value::String(value) => resolved.push_str(&value),
+                value => resolved.push_str(&value.to_string()),
+            }
+        } else {
+            resolved.push_str(&temp_string[left..(right + 2)]);
+        }
+        temp_string = &temp_string[(right + 2)..];
+    }
+    resolved.push_str(temp_string);
+
+    Ok(Value::String(resolved))
+}
+
+/// Replaces variable and secret references in every string of `value` using `lookup`, which
+/// receives the full reference, e.g. `var.name`.
+pub fn resolve_variable_references<F>(value: &Value, lookup: &mut F) -> Result<Value>
+where
+    F: FnMut(&str) -> Result<Value>,
+{
+    Ok(match value {
+        Value::String(string) => resolve_string(string, lookup)?,
+        Value::Array(array) => Value::Array(
+            array
+                .iter()
+                .map(|v| resolve_variable_references(v, lookup))
+                .collect::<Result<_>>()?,
+        ),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| Ok((k.clone(), resolve_variable_references(v, lookup)?)))
+                .collect::<Result<_>>()?,
+        ),
+        value => value.clone(),
+    })
+}
+
+/// Replaces `{{ field }}` in `template` with the field of `fields`, nested fields are separated
+/// by dots, e.g. `{{ task_result.name }}`. Missing fields are left empty.
+pub fn render_fields(template: &str, fields: &Value) -> String {
+    let mut rendered = String::new();
+    let mut rest = template;
+
+    while let Some(left) = rest.find("{{") {
+        let Some(right) = rest[left..].find("}}").map(|right| left + right) else {
+            break;
+        };
+        rendered.push_str(&rest[..left]);
+        let pointer = format!("/{}", rest[(left + 2)..right].trim().replace('.', "/"));
+        match fields.pointer(&pointer) {
+            Some(Value::String(value)) => rendered.push_str(value),
+            Some(Value::Null) | None => {}
+            Some(value) => rendered.push_str(&value.to_string()),
+        }
+        rest = &rest[(right + 2)..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+// applies `render_fields` to every string of `value`
+pub fn render_fields_in_value(value: &Value, fields: &Value) -> Value {
+    match value {
+        Value::String(string) => Value::String(render_fields(string, fields)),
+        Value::Array(array) => Value::Array(
+            array
+                .iter()
+                .map(|v| render_fields_in_value(v, fields))
+                .collect(),
+        ),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), render_fields_in_value(v, fields)))
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
+
+pub fn redact_secrets(string: &str, secrets: &[String]) -> String {
+    secrets
+        .iter()
+        .filter(|secret| !secret.is_empty())
+        .fold(string.to_string(), |string, secret| {
+            string.replace(secret.as_str(), REDACTED)
+        })
+}
+
+// applies `redact_secrets` to every string of `value` before it is serialized, where secrets
+// containing quotes, backslashes or control characters would be escaped and missed
+pub fn redact_secrets_in_value(value: &Value, secrets: &[String]) -> Value {
+    match value {
+        Value::String(string) => Value::String(redact_secrets(string, secrets)),
+        Value::Array(array) => Value::Array(
+            array
+                .iter()
+                .map(|v| redact_secrets_in_value(v, secrets))
+                .collect(),
+        ),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| {
+                    (
+                        redact_secrets(k, secrets),
+                        redact_secrets_in_value(v, secrets),
+                    )
+                })
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_redact_secrets_in_value() {
+        let secrets = vec!["se\"cr\\et\n".to_string()];
+        let value = json!({ "pw": "se\"cr\\et\n", "url": "db://user:se\"cr\\et\n@host" });
+
+        let redacted = serde_json::to_string(&redact_secrets_in_value(&value, &secrets)).unwrap();
+        assert_eq!(
+            redacted,
+            format!(r#"{{"pw":"{REDACTED}","url":"db://user:{REDACTED}@host"}}"#)
+        );
+        // matching the serialized string misses the escaped secret
+        assert!(redact_secrets(&value.to_string(), &secrets).contains("cr"));
+    }
+}
@@ -0,0 +1,32 @@
+use serde_json::Value;
+use thepipelinetool_task::task_options::TaskOptions;
+
+use crate::{
+    backend::Backend, blanket_backend::BlanketBackend, in_memory_backend::InMemoryBackend,
+};
+
+// appends a task named `name` to run 0
+pub fn add_task(
+    backend: &mut InMemoryBackend,
+    name: &str,
+    template_args: Value,
+    options: &TaskOptions,
+) -> usize {
+    let task_id = backend
+        .append_new_task_and_set_status_to_pending(
+            0,
+            name,
+            name,
+            name,
+            &template_args,
+            options,
+            false,
+            false,
+            false,
+            false,
+            &[],
+        )
+        .unwrap();
+    backend.update_referenced_dependencies(0, task_id).unwrap();
+    task_id
+}
@@ -0,0 +1,13 @@
+use std::io::{self, Read};
+
+use anyhow::Result;
+use thepipelinetool_server::secrets::encrypt_secret;
+
+// reads a secret from stdin and prints it encrypted with SECRET_KEY for SECRET_PROVIDER=encrypted_file
+fn main() -> Result<()> {
+    let mut value = String::new();
+    io::stdin().read_to_string(&mut value)?;
+
+    println!("ENC[{}]", encrypt_secret(value.trim_end_matches('\n'))?);
+    Ok(())
+}
@@ -1,21 +1,56 @@
 use std::env;
 
-use anyhow::Result;
-use thepipelinetool_core::dev::TempQueuedTask;
+use anyhow::{anyhow, Result};
+use thepipelinetool_core::dev::{QueuedCallback, TempQueuedTask};
 use thepipelinetool_runner::backend::Backend;
 use thepipelinetool_runner::blanket_backend::BlanketBackend;
-use thepipelinetool_server::{env::get_tpt_command, get_redis_pool, redis_backend::RedisBackend};
+use thepipelinetool_runner::telemetry::context_from_env;
+use thepipelinetool_server::{
+    env::get_tpt_command, get_redis_pool, redis_backend::RedisBackend, telemetry::init_telemetry,
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = env::args().collect::<Vec<String>>();
-    let temp_queued_task: TempQueuedTask = serde_json::from_str(&args[1])?;
+    let tracer_provider = init_telemetry("tpt_executor")?;
 
+    let worked = match (args.get(1).map(String::as_str), args.get(2)) {
+        // `tpt_executor callback <queued callback>` runs the callbacks of a handled attempt
+        (Some("callback"), Some(queued_callback)) => run_callbacks(queued_callback),
+        (Some("callback"), None) => Err(anyhow!("usage: tpt_executor callback <queued callback>")),
+        (Some(temp_queued_task), _) => work(temp_queued_task),
+        (None, _) => Err(anyhow!("usage: tpt_executor <temp queued task>")),
+    };
+
+    if let Some(tracer_provider) = tracer_provider {
+        tokio::task::spawn_blocking(move || tracer_provider.shutdown()).await??;
+    }
+    worked
+}
+
+fn run_callbacks(queued_callback: &str) -> Result<()> {
+    let queued_callback: QueuedCallback = serde_json::from_str(queued_callback)?;
+    let mut backend = RedisBackend::from(
+        &queued_callback.queued_task.pipeline_name,
+        get_redis_pool()?,
+    )
+    .with_actor("worker");
+    let _guard = context_from_env().attach();
+    backend
+        .run_callbacks(&queued_callback, get_tpt_command())
+        .and_then(|_| backend.remove_from_callback_temp_queue(&queued_callback))
+}
+
+fn work(temp_queued_task: &str) -> Result<()> {
+    let temp_queued_task: TempQueuedTask = serde_json::from_str(temp_queued_task)?;
     let mut backend = RedisBackend::from(
         &temp_queued_task.queued_task.pipeline_name,
         get_redis_pool()?,
-    );
-    backend.work(&temp_queued_task, get_tpt_command())?;
-    backend.remove_from_temp_queue(&temp_queued_task)?;
-    Ok(())
+    )
+    .with_actor("worker");
+    // spans of this attempt continue the trace of the worker that popped the task
+    let _guard = context_from_env().attach();
+    backend
+        .work(&temp_queued_task, get_tpt_command())
+        .and_then(|_| backend.remove_from_temp_queue(&temp_queued_task))
 }
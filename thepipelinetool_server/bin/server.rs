@@ -1,8 +1,12 @@
 use axum::{http::Method, Router};
+use std::net::SocketAddr;
 use std::path::PathBuf;
 // use thepipelinetool_server::catchup::catchup;
 use thepipelinetool_server::check_timeout::check_timeout;
 use thepipelinetool_server::env::tpt_installed;
+use thepipelinetool_server::retention::cleanup;
+use thepipelinetool_server::sla::check_sla;
+use thepipelinetool_server::telemetry::init_telemetry;
 use thepipelinetool_server::{get_redis_pool, routes::*, scheduler::scheduler};
 use tokio::net::TcpListener;
 use tower_http::compression::CompressionLayer;
@@ -22,6 +26,8 @@ async fn main() -> Result<()> {
 
     assert!(tpt_installed()?);
 
+    let _tracer_provider = init_telemetry("tpt_server")?;
+
     println!("connecting to redis...");
     let pool = get_redis_pool()?;
 
@@ -37,9 +43,22 @@ async fn main() -> Result<()> {
         tokio::spawn(async move { check_timeout(pool).await });
     }
 
+    println!("spawning check_sla...");
+    {
+        let pool = pool.clone();
+        tokio::spawn(async move { check_sla(pool).await });
+    }
+
+    println!("spawning cleanup...");
+    {
+        let pool = pool.clone();
+        tokio::spawn(async move { cleanup(pool).await });
+    }
+
     let app = Router::new()
         .nest_service("/", ServeDir::new(PathBuf::from("static")))
         .route("/ping", get(ping))
+        .route("/metrics", get(get_metrics))
         .route("/pipelines", get(get_pipelines))
         .route("/runs/:pipeline_name", get(get_runs))
         .route("/runs/next/:pipeline_name", get(get_next_run))
@@ -47,11 +66,34 @@ async fn main() -> Result<()> {
         .route("/runs/recent/:pipeline_name", get(get_recent_runs)) // TODO change to recent results?
         .route("/runs/all/:pipeline_name", get(get_runs_with_tasks))
         .route("/trigger/:pipeline_name", get(trigger).post(trigger_params))
+        .route(
+            "/trigger/:pipeline_name/:pipeline_hash",
+            get(trigger_version).post(trigger_version_params),
+        )
+        .route("/versions/:pipeline_name", get(get_pipeline_versions))
+        .route(
+            "/versions/:pipeline_name/:pipeline_hash",
+            get(get_pipeline_version),
+        )
+        .route(
+            "/versions/diff/:pipeline_name/:old_hash/:new_hash",
+            get(get_pipeline_versions_diff),
+        )
         .route("/statuses/:run_id", get(get_run_status))
+        .route("/events/:run_id", get(get_run_events))
+        .route("/sla_misses/:pipeline_name", get(get_sla_misses))
         .route("/statuses/:run_id/:task_id", get(get_task_status))
         .route("/results/:run_id/:task_id", get(get_task_result))
         .route("/results/all/:run_id/:task_id", get(get_all_results))
         .route("/logs/:run_id/:task_id/:attempt", get(get_task_log))
+        .route(
+            "/logs/:run_id/:task_id/:attempt/stream",
+            get(stream_task_log),
+        )
+        .route(
+            "/logs/:run_id/:task_id/:attempt/callbacks/:event",
+            get(get_callback_log),
+        )
         .route("/tasks/:run_id", get(get_all_tasks_by_run_id))
         .route("/tasks/:run_id/:task_id", get(get_task_by_id))
         .route("/tasks/default/:pipeline_name", get(get_default_tasks))
@@ -59,9 +101,37 @@ async fn main() -> Result<()> {
         .route("/graphs/:run_id", get(get_run_graph))
         .route("/graphs/default/:pipeline_name", get(get_default_graph))
         .route("/upload/:pipeline_name", post(upload_pipeline))
+        .route("/variables/:pipeline_name", get(get_variables))
+        .route(
+            "/variables/:pipeline_name/:key",
+            get(get_variable).post(set_variable).delete(delete_variable),
+        )
+        .route("/global_variables", get(get_global_variables))
+        .route(
+            "/global_variables/:key",
+            get(get_global_variable)
+                .post(set_global_variable)
+                .delete(delete_global_variable),
+        )
+        .route("/secrets", get(get_secret_names))
+        .route("/secrets/:name", post(set_secret).delete(delete_secret))
+        .route("/connections", get(get_connection_ids))
+        .route(
+            "/connections/:id",
+            get(get_connection)
+                .post(set_connection)
+                .delete(delete_connection),
+        )
+        .route("/run_variables/:run_id", get(get_run_variables))
+        .route(
+            "/run_variables/:run_id/:key",
+            get(get_run_variable)
+                .post(set_run_variable)
+                .delete(delete_run_variable),
+        )
         .layer(
             CorsLayer::new()
-                .allow_methods([Method::GET, Method::POST])
+                .allow_methods([Method::GET, Method::POST, Method::DELETE])
                 .allow_origin(Any),
         )
         .layer(TraceLayer::new_for_http())
@@ -74,7 +144,11 @@ async fn main() -> Result<()> {
 
     let listener = TcpListener::bind(bind_address).await?;
 
-    axum::serve(listener, app.into_make_service()).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -1,18 +1,72 @@
-use std::{process::Command, time::Duration};
+use std::{
+    fmt::Write,
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, SystemTime},
+};
 // use thepipelinetool_runner::run;
 use anyhow::Result;
-use thepipelinetool_runner::{backend::Backend, get_tpt_executor_command};
+use axum::{http::header, response::IntoResponse, routing::get, Router};
+use deadpool_redis::Pool;
+use opentelemetry::{
+    trace::{TraceContextExt, Tracer},
+    Context, KeyValue,
+};
+use thepipelinetool_core::dev::TRACEPARENT_ENV;
+use thepipelinetool_runner::{
+    backend::Backend,
+    blanket_backend::BlanketBackend,
+    get_tpt_executor_command,
+    telemetry::{context_from_traceparent, get_traceparent, get_tracer},
+};
 use thepipelinetool_server::{
     env::{
         get_executor_image, get_executor_type, get_max_parallelism, get_redis_url,
-        get_worker_loop_interval,
+        get_worker_loop_interval, get_worker_metrics_port,
     },
     get_redis_pool,
+    metrics::write_header,
     redis_backend::RedisBackend,
+    routes::METRICS_CONTENT_TYPE,
+    telemetry::init_telemetry,
     Executor,
 };
 use thepipelinetool_utils::spawn;
-use tokio::time::sleep;
+use tokio::{net::TcpListener, time::sleep};
+
+// tasks handed to an executor by this worker
+static SPAWNED_TASKS: AtomicUsize = AtomicUsize::new(0);
+
+// the pipeline metrics are served by the server, these only cover this worker
+async fn get_metrics() -> impl IntoResponse {
+    let mut body = String::new();
+    write_header(
+        &mut body,
+        "tpt_worker_max_parallelism",
+        "gauge",
+        "Tasks this worker runs at most at the same time.",
+    );
+    writeln!(
+        body,
+        "tpt_worker_max_parallelism {}",
+        get_max_parallelism().unwrap_or_default()
+    )
+    .unwrap();
+    write_header(
+        &mut body,
+        "tpt_worker_spawned_tasks_total",
+        "counter",
+        "Tasks this worker handed to an executor.",
+    );
+    writeln!(
+        body,
+        "tpt_worker_spawned_tasks_total {}",
+        SPAWNED_TASKS.load(Ordering::Relaxed)
+    )
+    .unwrap();
+
+    ([(header::CONTENT_TYPE, METRICS_CONTENT_TYPE)], body)
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -21,20 +75,36 @@ async fn main() -> Result<()> {
 
     env_logger::init();
 
+    let _tracer_provider = init_telemetry("tpt_worker")?;
+
     let max_parallelism = get_max_parallelism()?;
     let executor = get_executor_type()?;
-    let backend = RedisBackend::dummy(get_redis_pool()?);
+    let pool = get_redis_pool()?;
+    let backend = RedisBackend::dummy(pool.clone());
     let loop_interval = Duration::from_millis(get_worker_loop_interval()?);
 
     println!("Running tpt worker with '{:?}' executor type", executor);
     println!("Connected to redis at {}", get_redis_url());
 
+    if let Some(port) = get_worker_metrics_port()? {
+        let metrics_address = format!("0.0.0.0:{port}");
+        match TcpListener::bind(&metrics_address).await {
+            Ok(listener) => {
+                println!("Serving worker metrics on {metrics_address}");
+                let app = Router::new().route("/metrics", get(get_metrics));
+                tokio::spawn(async move { axum::serve(listener, app.into_make_service()).await });
+            }
+            Err(e) => eprintln!("could not serve worker metrics on {metrics_address}: {e}"),
+        }
+    }
+
     loop {
         let mut backend = backend.clone();
 
         sleep(loop_interval).await;
 
-        tokio::spawn(async move { work(max_parallelism, executor, &mut backend).await });
+        let pool = pool.clone();
+        tokio::spawn(async move { work(max_parallelism, executor, &mut backend, pool).await });
     }
 }
 
@@ -42,54 +112,115 @@ async fn work(
     max_parallelism: usize,
     executor: Executor,
     backend: &mut RedisBackend,
+    pool: Pool,
 ) -> Result<()> {
+    // notifications don't take up an executor, sending them blocks on the sinks
+    while let Some(notification) = backend.pop_notification_queue()? {
+        let mut backend =
+            RedisBackend::from(&notification.pipeline_name, pool.clone()).with_actor("worker");
+        tokio::task::spawn_blocking(move || backend.send_notification(&notification));
+    }
+
     if backend.get_running_tasks_count().await? < max_parallelism {
+        // callbacks of handled attempts go before new tasks
+        if let Some(queued_callback) = backend.pop_callback_queue()? {
+            return spawn_executor(
+                executor,
+                &[
+                    "callback".to_string(),
+                    serde_json::to_string(&queued_callback)?,
+                ],
+                None,
+            );
+        }
+
+        let popped = SystemTime::now();
         let temp_queued_task = backend.pop_priority_queue()?;
         if temp_queued_task.is_none() {
             return Ok(());
         }
 
         let temp_queued_task = temp_queued_task.expect("");
-        match executor {
-            Executor::Local => {
-                let mut cmd = Command::new(get_tpt_executor_command());
-                cmd.arg(serde_json::to_string(&temp_queued_task).unwrap());
-                let _ = spawn(
-                    cmd,
-                    None,
-                    Box::new(|x| {
-                        print!("{x}");
-                        Ok(())
-                    }),
-                    Box::new(|x| {
-                        eprint!("{x}");
-                        Ok(())
-                    }),
-                );
+        SPAWNED_TASKS.fetch_add(1, Ordering::Relaxed);
+
+        // the executor continues the trace of the span that enqueued the task
+        let queued_task = &temp_queued_task.queued_task;
+        let tracer = get_tracer();
+        let parent_cx = queued_task
+            .traceparent
+            .as_deref()
+            .map_or_else(Context::new, context_from_traceparent);
+        let cx = parent_cx.with_span(
+            tracer
+                .span_builder("pop_task")
+                .with_start_time(popped)
+                .with_attributes([
+                    KeyValue::new("pipeline", queued_task.pipeline_name.clone()),
+                    KeyValue::new("run_id", queued_task.run_id as i64),
+                    KeyValue::new("task_id", queued_task.task_id as i64),
+                    KeyValue::new("attempt", queued_task.attempt as i64),
+                ])
+                .start_with_context(&tracer, &parent_cx),
+        );
+        let traceparent = get_traceparent(&cx);
+
+        spawn_executor(
+            executor,
+            &[serde_json::to_string(&temp_queued_task).unwrap()],
+            traceparent.as_deref(),
+        )?;
+        cx.span().end();
+    }
+    Ok(())
+}
+
+// runs `tpt_executor` with `args`, without waiting for it to exit
+fn spawn_executor(executor: Executor, args: &[String], traceparent: Option<&str>) -> Result<()> {
+    match executor {
+        Executor::Local => {
+            let mut cmd = Command::new(get_tpt_executor_command());
+            if let Some(traceparent) = traceparent {
+                cmd.env(TRACEPARENT_ENV, traceparent);
             }
-            Executor::Docker => {
-                let mut cmd = Command::new("docker");
-                cmd.args(&["run", "-e"]);
-                cmd.arg(format!("REDIS_URL={}", get_redis_url()));
-                cmd.arg("--network=thepipelinetool_default");
-                cmd.arg(get_executor_image()?);
-                cmd.arg(serde_json::to_string(&temp_queued_task).unwrap());
-
-                let _ = spawn(
-                    cmd,
-                    None,
-                    Box::new(|x| {
-                        print!("{x}");
-                        Ok(())
-                    }),
-                    Box::new(|x| {
-                        eprint!("{x}");
-                        Ok(())
-                    }),
-                );
+            cmd.args(args);
+            let _ = spawn(
+                cmd,
+                None,
+                Box::new(|x| {
+                    print!("{x}");
+                    Ok(())
+                }),
+                Box::new(|x| {
+                    eprint!("{x}");
+                    Ok(())
+                }),
+            );
+        }
+        Executor::Docker => {
+            let mut cmd = Command::new("docker");
+            cmd.args(&["run", "-e"]);
+            cmd.arg(format!("REDIS_URL={}", get_redis_url()));
+            if let Some(traceparent) = traceparent {
+                cmd.args(["-e", &format!("{TRACEPARENT_ENV}={traceparent}")]);
             }
-            Executor::Kubernetes => todo!(),
+            cmd.arg("--network=thepipelinetool_default");
+            cmd.arg(get_executor_image()?);
+            cmd.args(args);
+
+            let _ = spawn(
+                cmd,
+                None,
+                Box::new(|x| {
+                    print!("{x}");
+                    Ok(())
+                }),
+                Box::new(|x| {
+                    eprint!("{x}");
+                    Ok(())
+                }),
+            );
         }
+        Executor::Kubernetes => todo!(),
     }
     Ok(())
 }
@@ -9,13 +9,22 @@ use tokio::time::sleep;
 
 use anyhow::Result;
 
-use crate::{env::get_check_timeout_loop_interval, redis_backend::RedisBackend};
+use crate::{
+    env::{get_callback_timeout, get_check_timeout_loop_interval},
+    redis_backend::RedisBackend,
+};
 
 pub async fn check_timeout(pool: Pool) -> Result<()> {
-    let mut dummy = RedisBackend::dummy(pool.clone());
+    let dummy = RedisBackend::dummy(pool.clone());
     let loop_interval = Duration::new(get_check_timeout_loop_interval()?, 0);
+    let callback_timeout = Duration::new(get_callback_timeout()?, 0);
 
     loop {
+        if let Err(e) = RedisBackend::requeue_stale_callbacks(callback_timeout, pool.clone()).await
+        {
+            eprintln!("could not requeue stale callbacks: {e:?}");
+        }
+
         for temp_queued_task in dummy.get_temp_queue().await? {
             let task = dummy.get_task_by_id(
                 temp_queued_task.queued_task.run_id,
@@ -24,22 +33,24 @@ pub async fn check_timeout(pool: Pool) -> Result<()> {
             if let Some(timeout) = task.options.timeout {
                 let now = Utc::now();
                 if (now - temp_queued_task.popped_date).to_std()? > timeout {
-                    dummy.handle_task_result(
-                        temp_queued_task.queued_task.run_id,
-                        &temp_queued_task.queued_task,
-                        TaskResult::premature_error(
-                            task.id,
-                            temp_queued_task.queued_task.attempt,
-                            task.options.max_attempts,
-                            task.name.clone(),
-                            task.function.clone(),
-                            "timed out".to_string(),
-                            task.is_branch,
-                            task.options.is_sensor,
-                            Some(temp_queued_task.popped_date),
-                            Some(now),
-                        ),
-                    )?;
+                    RedisBackend::from(&temp_queued_task.queued_task.pipeline_name, pool.clone())
+                        .with_actor("check_timeout")
+                        .handle_task_result(
+                            temp_queued_task.queued_task.run_id,
+                            &temp_queued_task.queued_task,
+                            TaskResult::premature_error(
+                                task.id,
+                                temp_queued_task.queued_task.attempt,
+                                task.options.max_attempts,
+                                task.name.clone(),
+                                task.function.clone(),
+                                "timed out".to_string(),
+                                task.is_branch,
+                                task.options.is_sensor,
+                                Some(temp_queued_task.popped_date),
+                                Some(now),
+                            ),
+                        )?;
                 }
             }
         }
@@ -1,11 +1,28 @@
-use std::{env, process::Command};
+use std::{
+    env,
+    path::PathBuf,
+    process::Command,
+    sync::{Arc, OnceLock},
+};
 
+use deadpool_redis::Pool;
 use serde_json::json;
-use thepipelinetool_runner::get_tpt_executor_command;
+use thepipelinetool_runner::{
+    get_tpt_executor_command,
+    result_store::{FileResultStore, ResultOffload, ResultStore, DEFAULT_RESULT_OFFLOAD_THRESHOLD},
+};
 use thepipelinetool_utils::get_default_max_parallelism;
 
-use crate::Executor;
-use anyhow::Result;
+use crate::{
+    result_store::S3ResultStore,
+    secret_provider::{
+        DotenvSecretProvider, EncryptedFileSecretProvider, RedisSecretProvider, SecretProvider,
+        SopsSecretProvider, VaultSecretProvider,
+    },
+    Executor,
+};
+use anyhow::{anyhow, Result};
+use base64::prelude::*;
 
 pub fn tpt_installed() -> Result<bool> {
     Ok(!matches!(
@@ -72,18 +89,178 @@ pub fn get_check_timeout_loop_interval() -> Result<u64> {
         .parse::<u64>()?)
 }
 
+// callbacks still running after this many seconds are run again
+pub fn get_callback_timeout() -> Result<u64> {
+    Ok(env::var("CALLBACK_TIMEOUT")
+        .unwrap_or(600.to_string())
+        .parse::<u64>()?)
+}
+
+pub fn get_check_sla_loop_interval() -> Result<u64> {
+    Ok(env::var("CHECK_SLA_LOOP_INTERVAL")
+        .unwrap_or(60.to_string())
+        .parse::<u64>()?)
+}
+
 pub fn get_scheduler_loop_interval() -> Result<u64> {
     Ok(env::var("SCHEDULER_LOOP_INTERVAL")
         .unwrap_or(5.to_string())
         .parse::<u64>()?)
 }
 
+pub fn get_retention_loop_interval() -> Result<u64> {
+    Ok(env::var("RETENTION_LOOP_INTERVAL")
+        .unwrap_or(3600.to_string())
+        .parse::<u64>()?)
+}
+
+// runs are only deleted when ARCHIVE_PATH is unset, otherwise they are archived there first
+pub fn get_archive_path() -> Option<PathBuf> {
+    env::var("ARCHIVE_PATH").ok().map(PathBuf::from)
+}
+
+pub const DEFAULT_LOG_STREAM_IDLE_TIMEOUT: u64 = 600;
+
+pub fn get_log_stream_idle_timeout() -> Result<u64> {
+    Ok(env::var("LOG_STREAM_IDLE_TIMEOUT")
+        .unwrap_or(DEFAULT_LOG_STREAM_IDLE_TIMEOUT.to_string())
+        .parse::<u64>()?)
+}
+
 pub fn get_worker_loop_interval() -> Result<u64> {
     Ok(env::var("WORKER_LOOP_INTERVAL")
         .unwrap_or(1.to_string())
         .parse::<u64>()?)
 }
 
+// spans are only exported when an OTLP collector is configured
+pub fn get_otlp_endpoint() -> Option<String> {
+    env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok()
+}
+
+// workers only serve metrics when a port is set, so several can run on one host
+pub fn get_worker_metrics_port() -> Result<Option<u16>> {
+    Ok(match env::var("WORKER_METRICS_PORT") {
+        Ok(port) => Some(port.parse::<u16>()?),
+        Err(_) => None,
+    })
+}
+
 pub fn get_executor_image() -> Result<String> {
     Ok(env::var("EXECUTOR_IMAGE").unwrap_or("executor".to_string()))
 }
+
+// base64 encoded 32 byte key used to encrypt secrets stored in redis
+pub fn get_secret_key() -> Result<Vec<u8>> {
+    let key = BASE64_STANDARD.decode(env::var("SECRET_KEY").map_err(|_| {
+        anyhow!("SECRET_KEY must be set to a base64 encoded 32 byte key to use secrets")
+    })?)?;
+    if key.len() != 32 {
+        return Err(anyhow!("SECRET_KEY must be 32 bytes, found {}", key.len()));
+    }
+    Ok(key)
+}
+
+fn get_secrets_file() -> Result<PathBuf> {
+    Ok(env::var("SECRETS_FILE")
+        .map_err(|_| {
+            anyhow!(
+                "SECRETS_FILE must be set for SECRET_PROVIDER '{}'",
+                get_secret_provider_type()
+            )
+        })?
+        .into())
+}
+
+fn get_secret_provider_type() -> String {
+    env::var("SECRET_PROVIDER").unwrap_or("redis".to_string())
+}
+
+static SECRET_PROVIDER: OnceLock<Arc<dyn SecretProvider>> = OnceLock::new();
+
+// secrets are stored in redis unless SECRET_PROVIDER is one of dotenv, encrypted_file, sops or
+// vault, the provider is built on first use
+pub fn get_secret_provider(pool: Pool) -> Result<Arc<dyn SecretProvider>> {
+    if let Some(provider) = SECRET_PROVIDER.get() {
+        return Ok(provider.clone());
+    }
+    let provider = create_secret_provider(pool)?;
+    Ok(SECRET_PROVIDER.get_or_init(|| provider).clone())
+}
+
+fn create_secret_provider(pool: Pool) -> Result<Arc<dyn SecretProvider>> {
+    Ok(match get_secret_provider_type().as_str() {
+        "redis" => Arc::new(RedisSecretProvider { pool }),
+        "dotenv" => Arc::new(DotenvSecretProvider {
+            path: get_secrets_file()?,
+        }),
+        "encrypted_file" => Arc::new(EncryptedFileSecretProvider {
+            path: get_secrets_file()?,
+            key: get_secret_key()?,
+        }),
+        // same variable sops reads the age identities from
+        "sops" => Arc::new(SopsSecretProvider {
+            path: get_secrets_file()?,
+            identity_file: env::var("SOPS_AGE_KEY_FILE")
+                .map_err(|_| anyhow!("SOPS_AGE_KEY_FILE must be set for SECRET_PROVIDER 'sops'"))?
+                .into(),
+        }),
+        "vault" => Arc::new(VaultSecretProvider {
+            address: env::var("VAULT_ADDR")
+                .map_err(|_| anyhow!("VAULT_ADDR must be set for SECRET_PROVIDER 'vault'"))?,
+            token: env::var("VAULT_TOKEN")
+                .map_err(|_| anyhow!("VAULT_TOKEN must be set for SECRET_PROVIDER 'vault'"))?,
+            mount: env::var("VAULT_MOUNT").unwrap_or("secret".to_string()),
+            path: env::var("VAULT_PATH").unwrap_or("tpt".to_string()),
+        }),
+        other => return Err(anyhow!("unknown SECRET_PROVIDER '{other}'")),
+    })
+}
+
+fn get_s3_var(name: &str) -> Result<String> {
+    env::var(name).map_err(|_| anyhow!("{name} must be set for RESULT_STORE 's3'"))
+}
+
+static RESULT_OFFLOAD: OnceLock<Option<ResultOffload>> = OnceLock::new();
+
+// results stay in redis unless RESULT_STORE is filesystem or s3, in which case results larger
+// than RESULT_STORE_THRESHOLD bytes are offloaded, the settings are read on first use
+pub fn get_result_offload() -> Result<Option<ResultOffload>> {
+    if let Some(result_offload) = RESULT_OFFLOAD.get() {
+        return Ok(result_offload.clone());
+    }
+    let result_offload = create_result_offload()?;
+    Ok(RESULT_OFFLOAD.get_or_init(|| result_offload).clone())
+}
+
+fn create_result_offload() -> Result<Option<ResultOffload>> {
+    let store: Arc<dyn ResultStore> = match env::var("RESULT_STORE")
+        .unwrap_or("redis".to_string())
+        .as_str()
+    {
+        "redis" => return Ok(None),
+        "filesystem" => Arc::new(FileResultStore {
+            path: env::var("RESULT_STORE_PATH")
+                .map_err(|_| {
+                    anyhow!("RESULT_STORE_PATH must be set for RESULT_STORE 'filesystem'")
+                })?
+                .into(),
+        }),
+        "s3" => Arc::new(S3ResultStore {
+            endpoint: get_s3_var("S3_ENDPOINT")?,
+            bucket: get_s3_var("S3_BUCKET")?,
+            region: env::var("S3_REGION").unwrap_or("us-east-1".to_string()),
+            access_key_id: get_s3_var("S3_ACCESS_KEY_ID")?,
+            secret_access_key: get_s3_var("S3_SECRET_ACCESS_KEY")?,
+        }),
+        other => return Err(anyhow!("unknown RESULT_STORE '{other}'")),
+    };
+
+    Ok(Some(ResultOffload {
+        store,
+        threshold: match env::var("RESULT_STORE_THRESHOLD") {
+            Ok(threshold) => threshold.parse()?,
+            Err(_) => DEFAULT_RESULT_OFFLOAD_THRESHOLD,
+        },
+    }))
+}
@@ -12,9 +12,16 @@ use anyhow::Result;
 
 pub mod check_timeout;
 pub mod env;
+pub mod metrics;
 pub mod redis_backend;
+pub mod result_store;
+pub mod retention;
 pub mod routes;
 pub mod scheduler;
+pub mod secret_provider;
+pub mod secrets;
+pub mod sla;
+pub mod telemetry;
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum Executor {
@@ -36,7 +43,12 @@ pub async fn _get_all_task_results(
     task_id: usize,
     pool: Pool,
 ) -> Result<Vec<TaskResult>> {
-    RedisBackend::get_all_results(run_id, task_id, pool).await
+    let backend = RedisBackend::dummy(pool.clone());
+    let mut task_results = RedisBackend::get_all_results(run_id, task_id, pool).await?;
+    for task_result in &mut task_results {
+        backend.load_result(task_result)?;
+    }
+    Ok(task_results)
 }
 
 pub fn _get_task_status(run_id: usize, task_id: usize, pool: Pool) -> Result<TaskStatus> {
@@ -48,7 +60,10 @@ pub fn _get_run_status(run_id: usize, pool: Pool) -> Result<RunStatus> {
 }
 
 pub fn _get_task_result(run_id: usize, task_id: usize, pool: Pool) -> Result<TaskResult> {
-    RedisBackend::dummy(pool).get_task_result(run_id, task_id)
+    let mut backend = RedisBackend::dummy(pool);
+    let mut task_result = backend.get_task_result(run_id, task_id)?;
+    backend.load_result(&mut task_result)?;
+    Ok(task_result)
 }
 
 pub fn get_redis_pool() -> Result<Pool> {
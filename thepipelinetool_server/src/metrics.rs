@@ -0,0 +1,240 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::Result;
+use deadpool_redis::Pool;
+use thepipelinetool_runner::backend::Backend;
+
+use crate::redis_backend::RedisBackend;
+
+// upper bounds in seconds, the +Inf bucket is implied
+pub const TASK_DURATION_BUCKETS: [f64; 10] =
+    [0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0, 3600.0];
+
+/// Metrics recorded for each attempt of the tasks of one pipeline, keyed by task name.
+#[derive(Default, Debug)]
+pub struct TaskMetrics {
+    // cumulative counts for each bucket of TASK_DURATION_BUCKETS followed by +Inf
+    pub duration_buckets: BTreeMap<String, Vec<u64>>,
+    pub duration_sums: BTreeMap<String, f64>,
+    pub retries: BTreeMap<String, u64>,
+    pub failures: BTreeMap<String, u64>,
+}
+
+#[derive(Default, Debug)]
+pub struct MetricsSnapshot {
+    pub queue_length: usize,
+    pub temp_queue_size: usize,
+    // by pipeline
+    pub running_tasks: BTreeMap<String, usize>,
+    pub tasks: BTreeMap<String, TaskMetrics>,
+    pub scheduler_lags: BTreeMap<String, f64>,
+}
+
+pub async fn collect_metrics(pool: Pool) -> Result<MetricsSnapshot> {
+    let backend = RedisBackend::dummy(pool.clone());
+    let temp_queue = backend.get_temp_queue().await?;
+    let mut running_tasks = BTreeMap::new();
+    for temp_queued_task in &temp_queue {
+        *running_tasks
+            .entry(temp_queued_task.queued_task.pipeline_name.clone())
+            .or_default() += 1;
+    }
+
+    let mut tasks = BTreeMap::new();
+    for pipeline_name in RedisBackend::get_pipelines(pool.clone()).await? {
+        let task_metrics = RedisBackend::get_task_metrics(&pipeline_name, pool.clone()).await?;
+        tasks.insert(pipeline_name, task_metrics);
+    }
+
+    Ok(MetricsSnapshot {
+        queue_length: backend.get_queue_length()?,
+        temp_queue_size: temp_queue.len(),
+        running_tasks,
+        tasks,
+        scheduler_lags: RedisBackend::get_scheduler_lags(pool).await?,
+    })
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+pub fn format_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return "".into();
+    }
+    format!(
+        "{{{}}}",
+        labels
+            .iter()
+            .map(|(k, v)| format!("{k}=\"{}\"", escape_label(v)))
+            .collect::<Vec<String>>()
+            .join(",")
+    )
+}
+
+pub fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# HELP {name} {help}").unwrap();
+    writeln!(out, "# TYPE {name} {kind}").unwrap();
+}
+
+// Prometheus text exposition format
+impl MetricsSnapshot {
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        write_header(
+            &mut out,
+            "tpt_queue_length",
+            "gauge",
+            "Tasks waiting in the queue.",
+        );
+        writeln!(out, "tpt_queue_length {}", self.queue_length).unwrap();
+
+        write_header(
+            &mut out,
+            "tpt_temp_queue_size",
+            "gauge",
+            "Tasks popped from the queue by workers and not yet finished.",
+        );
+        writeln!(out, "tpt_temp_queue_size {}", self.temp_queue_size).unwrap();
+
+        write_header(
+            &mut out,
+            "tpt_running_tasks",
+            "gauge",
+            "Tasks currently running.",
+        );
+        for (pipeline, count) in &self.running_tasks {
+            writeln!(
+                out,
+                "tpt_running_tasks{} {count}",
+                format_labels(&[("pipeline", pipeline)])
+            )
+            .unwrap();
+        }
+
+        write_header(
+            &mut out,
+            "tpt_task_duration_seconds",
+            "histogram",
+            "Duration of task attempts.",
+        );
+        for (pipeline, task_metrics) in &self.tasks {
+            for (task, buckets) in &task_metrics.duration_buckets {
+                for (i, count) in buckets.iter().enumerate() {
+                    let le = TASK_DURATION_BUCKETS
+                        .get(i)
+                        .map_or("+Inf".to_string(), |bucket| bucket.to_string());
+                    writeln!(
+                        out,
+                        "tpt_task_duration_seconds_bucket{} {count}",
+                        format_labels(&[("pipeline", pipeline), ("task", task), ("le", &le)])
+                    )
+                    .unwrap();
+                }
+                let labels = format_labels(&[("pipeline", pipeline), ("task", task)]);
+                writeln!(
+                    out,
+                    "tpt_task_duration_seconds_sum{labels} {}",
+                    task_metrics.duration_sums.get(task).unwrap_or(&0.0)
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "tpt_task_duration_seconds_count{labels} {}",
+                    buckets.last().unwrap_or(&0)
+                )
+                .unwrap();
+            }
+        }
+
+        for (name, help, counters) in [
+            (
+                "tpt_task_retries_total",
+                "Attempts after the first one.",
+                self.tasks
+                    .iter()
+                    .map(|(p, m)| (p, &m.retries))
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                "tpt_task_failures_total",
+                "Failed task attempts.",
+                self.tasks
+                    .iter()
+                    .map(|(p, m)| (p, &m.failures))
+                    .collect::<Vec<_>>(),
+            ),
+        ] {
+            write_header(&mut out, name, "counter", help);
+            for (pipeline, counts) in counters {
+                for (task, count) in counts {
+                    writeln!(
+                        out,
+                        "{name}{} {count}",
+                        format_labels(&[("pipeline", pipeline), ("task", task)])
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        write_header(
+            &mut out,
+            "tpt_scheduler_lag_seconds",
+            "gauge",
+            "Delay between the scheduled date and the creation of the last scheduled run.",
+        );
+        for (pipeline, lag) in &self.scheduler_lags {
+            writeln!(
+                out,
+                "tpt_scheduler_lag_seconds{} {lag}",
+                format_labels(&[("pipeline", pipeline)])
+            )
+            .unwrap();
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut task_metrics = TaskMetrics::default();
+        task_metrics
+            .duration_buckets
+            .insert("say \"hi\"".into(), vec![0, 1, 1, 2, 2, 2, 2, 2, 2, 2, 3]);
+        task_metrics
+            .duration_sums
+            .insert("say \"hi\"".into(), 7200.75);
+        task_metrics.retries.insert("say \"hi\"".into(), 1);
+
+        let rendered = MetricsSnapshot {
+            queue_length: 4,
+            tasks: BTreeMap::from([("simple".into(), task_metrics)]),
+            ..Default::default()
+        }
+        .render();
+
+        assert!(rendered.contains("tpt_queue_length 4\n"));
+        assert!(rendered.contains(
+            "tpt_task_duration_seconds_bucket{pipeline=\"simple\",task=\"say \\\"hi\\\"\",le=\"0.5\"} 1\n"
+        ));
+        assert!(rendered.contains(
+            "tpt_task_duration_seconds_bucket{pipeline=\"simple\",task=\"say \\\"hi\\\"\",le=\"+Inf\"} 3\n"
+        ));
+        assert!(rendered.contains(
+            "tpt_task_duration_seconds_count{pipeline=\"simple\",task=\"say \\\"hi\\\"\"} 3\n"
+        ));
+        assert!(rendered
+            .contains("tpt_task_retries_total{pipeline=\"simple\",task=\"say \\\"hi\\\"\"} 1\n"));
+    }
+}
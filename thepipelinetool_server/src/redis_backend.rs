@@ -1,9 +1,19 @@
-use deadpool_redis::{redis::cmd, Pool};
+use deadpool_redis::{
+    redis::{cmd, pipe},
+    Pool,
+};
 use log::debug;
-use std::collections::{HashMap, HashSet};
-use thepipelinetool_runner::run::Run;
+use opentelemetry::Context;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+use thepipelinetool_runner::run::{Run, RunEvent};
 use thepipelinetool_runner::{
-    backend::Backend, pipeline::Pipeline, pipeline_options::PipelineOptions,
+    backend::{Backend, VariableScope},
+    notifications::Notification,
+    pipeline::{Pipeline, PipelineVersion},
+    pipeline_options::PipelineOptions,
+    result_store::ResultOffload,
+    telemetry::get_traceparent,
 };
 
 use anyhow::{anyhow, Result};
@@ -11,6 +21,13 @@ use chrono::{DateTime, Utc};
 use thepipelinetool_core::dev::*;
 use timed::timed;
 
+use crate::{
+    env::{get_result_offload, get_secret_provider},
+    metrics::{TaskMetrics, TASK_DURATION_BUCKETS},
+    secrets::{decrypt_secret, encrypt_secret},
+    sla::SlaMiss,
+};
+
 const TASK_STATUS_KEY: &str = "ts";
 const TASK_RESULTS_KEY: &str = "trs";
 const RUNS_KEY: &str = "runs";
@@ -19,6 +36,7 @@ const SCHEDULED_DATES_KEY: &str = "ld";
 const DEPTH_KEY: &str = "d";
 const TASK_RESULT_KEY: &str = "tr";
 const LOG_KEY: &str = "l";
+const CALLBACK_LOG_KEY: &str = "cl";
 const TASK_ATTEMPT_KEY: &str = "a";
 const DEPENDENCY_KEYS_KEY: &str = "dk";
 const EDGES_KEY: &str = "e";
@@ -26,11 +44,33 @@ const TASKS_KEY: &str = "tks";
 const TASK_ID_KEY: &str = "ti";
 const TASK_KEY: &str = "t";
 const TEMPLATE_ARGS_KEY: &str = "ta";
+const TRIGGER_PARAMS_KEY: &str = "tp";
+const VARIABLES_KEY: &str = "v";
+const RUN_VARIABLES_KEY: &str = "rv";
+const GLOBAL_VARIABLES_KEY: &str = "gv";
+const CONNECTIONS_KEY: &str = "c";
 const DEFAULT_TASKS_KEY: &str = "dt";
 const DEFAULT_EDGES_KEY: &str = "de";
 const DEFAULT_OPTIONS_KEY: &str = "do";
 const PIPELINES_KEY: &str = "p";
 const PIPELINE_PATH_KEY: &str = "pp";
+const PIPELINE_VERSION_KEY: &str = "pv";
+const PIPELINE_VERSIONS_KEY: &str = "pvs";
+const CURRENT_PIPELINE_HASH_KEY: &str = "ph";
+const RUN_EVENTS_KEY: &str = "ev";
+const RUN_END_DATE_KEY: &str = "re";
+const TASK_DURATIONS_KEY: &str = "md";
+const TASK_RETRIES_KEY: &str = "mr";
+const TASK_FAILURES_KEY: &str = "mf";
+const SCHEDULER_LAG_KEY: &str = "ml";
+const SLA_MISSES_KEY: &str = "sm";
+const SLA_UNSETTLED_KEY: &str = "su";
+const SLA_SINCE_KEY: &str = "ss";
+const CALLBACK_QUEUE_KEY: &str = "cbq";
+const CALLBACK_TEMP_QUEUE_KEY: &str = "cbtq";
+const CALLBACK_SEEN_KEY: &str = "cbs";
+const NOTIFICATION_QUEUE_KEY: &str = "nq";
+const QUEUED_TRACEPARENTS_KEY: &str = "qtp";
 
 macro_rules! block_on {
     // Textual definition.
@@ -43,20 +83,40 @@ macro_rules! block_on {
 pub struct RedisBackend {
     name: Option<String>,
     pool: Pool,
+    actor: Option<String>,
+    claimed_actor: Option<String>,
 }
 
 impl RedisBackend {
     pub fn dummy(pool: Pool) -> Self {
-        Self { name: None, pool }
+        Self {
+            name: None,
+            pool,
+            actor: None,
+            claimed_actor: None,
+        }
     }
 
     pub fn from(pipeline_name: &str, pool: Pool) -> Self {
         Self {
             name: Some(pipeline_name.to_string()),
             pool,
+            actor: None,
+            claimed_actor: None,
         }
     }
 
+    pub fn with_actor(mut self, actor: &str) -> Self {
+        self.actor = Some(actor.to_string());
+        self
+    }
+
+    // recorded next to the actor in the events appended by this backend
+    pub fn with_claimed_actor(mut self, claimed_actor: Option<&str>) -> Self {
+        self.claimed_actor = claimed_actor.map(|c| c.to_string());
+        self
+    }
+
     #[timed(duration(printer = "debug!"))]
     pub async fn get_pipelines(pool: Pool) -> Result<HashSet<String>> {
         let mut conn = pool.get().await.expect("DB connection failed");
@@ -106,9 +166,83 @@ impl RedisBackend {
             .query_async::<_, ()>(&mut conn)
             .await?;
 
+        // versions are immutable, re-uploading a known version only makes it current again
+        let pipeline_hash = pipeline.hash();
+        let is_new_version = cmd("SET")
+            .arg(format!(
+                "{PIPELINE_VERSION_KEY}:{pipeline_name}:{pipeline_hash}"
+            ))
+            .arg(serde_json::to_string(&pipeline)?)
+            .arg("NX")
+            .query_async::<_, Option<String>>(&mut conn)
+            .await?
+            .is_some();
+
+        if is_new_version {
+            cmd("RPUSH")
+                .arg(format!("{PIPELINE_VERSIONS_KEY}:{pipeline_name}"))
+                .arg(serde_json::to_string(&PipelineVersion {
+                    pipeline_hash: pipeline_hash.clone(),
+                    uploaded_date: Utc::now(),
+                })?)
+                .query_async::<_, ()>(&mut conn)
+                .await?;
+        }
+
+        cmd("SET")
+            .arg(format!("{CURRENT_PIPELINE_HASH_KEY}:{pipeline_name}"))
+            .arg(pipeline_hash)
+            .query_async::<_, String>(&mut conn)
+            .await?;
+
         Ok(())
     }
 
+    #[timed(duration(printer = "debug!"))]
+    pub async fn get_pipeline_versions(
+        pipeline_name: &str,
+        pool: Pool,
+    ) -> Result<Vec<PipelineVersion>> {
+        let mut conn = pool.get().await.expect("DB connection failed");
+        let members = cmd("LRANGE")
+            .arg(format!("{PIPELINE_VERSIONS_KEY}:{pipeline_name}"))
+            .arg(0)
+            .arg(-1)
+            .query_async::<_, Vec<String>>(&mut conn)
+            .await?;
+
+        let mut v = vec![];
+
+        for s in members {
+            v.push(serde_json::from_str(&s)?);
+        }
+        Ok(v)
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    pub async fn get_pipeline_version(
+        pipeline_name: &str,
+        pipeline_hash: &str,
+        pool: Pool,
+    ) -> Result<Pipeline> {
+        let mut conn = pool.get().await.expect("DB connection failed");
+        let pipeline = cmd("GET")
+            .arg(format!(
+                "{PIPELINE_VERSION_KEY}:{pipeline_name}:{pipeline_hash}"
+            ))
+            .query_async::<_, Option<String>>(&mut conn)
+            .await?;
+
+        if let Some(pipeline) = pipeline {
+            Ok(serde_json::from_str(&pipeline)?)
+        } else {
+            Err(anyhow!(format!(
+                "could not find version '{}' of pipeline '{}'",
+                pipeline_hash, pipeline_name
+            )))
+        }
+    }
+
     #[timed(duration(printer = "debug!"))]
     pub async fn get_options(&self) -> Result<PipelineOptions> {
         let mut conn = self.pool.get().await.expect("DB connection failed");
@@ -160,6 +294,18 @@ impl RedisBackend {
         Ok(v)
     }
 
+    // the latest attempt enqueued, dynamic and static enqueues are counted apart
+    #[timed(duration(printer = "debug!"))]
+    pub async fn get_current_attempt(run_id: usize, task_id: usize, pool: Pool) -> Result<usize> {
+        let mut conn = pool.get().await.expect("DB connection failed");
+        let attempts = cmd("MGET")
+            .arg(format!("{TASK_ATTEMPT_KEY}:{run_id}:{task_id}:false"))
+            .arg(format!("{TASK_ATTEMPT_KEY}:{run_id}:{task_id}:true"))
+            .query_async::<_, Vec<Option<usize>>>(&mut conn)
+            .await?;
+        Ok(attempts.into_iter().flatten().max().unwrap_or(0))
+    }
+
     #[timed(duration(printer = "debug!"))]
     pub async fn get_runs(pipeline_name: &str, pool: Pool) -> Result<Vec<Run>> {
         let mut conn = pool.get().await.expect("DB connection failed");
@@ -258,13 +404,459 @@ impl RedisBackend {
     }
 
     // #[timed(duration(printer = "debug!"))]
+    // popped tasks and callbacks, both take up an executor
     pub async fn get_running_tasks_count(&self) -> Result<usize> {
         let mut conn = self.pool.get().await.expect("DB connection failed");
-        Ok(cmd("SCARD")
+        let (tasks, callbacks) = pipe()
+            .cmd("SCARD")
             .arg("tmpqueue")
-            .query_async::<_, usize>(&mut conn)
+            .cmd("LLEN")
+            .arg(CALLBACK_TEMP_QUEUE_KEY)
+            .query_async::<_, (usize, usize)>(&mut conn)
+            .await?;
+        Ok(tasks + callbacks)
+    }
+
+    // callbacks popped longer than `timeout` ago are queued again, their executor is assumed to
+    // have died. when they were popped is taken from the first check that saw them
+    #[timed(duration(printer = "debug!"))]
+    pub async fn requeue_stale_callbacks(timeout: Duration, pool: Pool) -> Result<()> {
+        let mut conn = pool.get().await.expect("DB connection failed");
+        let now = Utc::now().timestamp();
+        let members = cmd("LRANGE")
+            .arg(CALLBACK_TEMP_QUEUE_KEY)
+            .arg(0)
+            .arg(-1)
+            .query_async::<_, Vec<String>>(&mut conn)
+            .await?;
+        for member in members {
+            let (_, seen) = pipe()
+                .cmd("HSETNX")
+                .arg(CALLBACK_SEEN_KEY)
+                .arg(&member)
+                .arg(now)
+                .cmd("HGET")
+                .arg(CALLBACK_SEEN_KEY)
+                .arg(&member)
+                .query_async::<_, (bool, i64)>(&mut conn)
+                .await?;
+            if now - seen < timeout.as_secs() as i64 {
+                continue;
+            }
+            // the next callback to be popped
+            pipe()
+                .atomic()
+                .cmd("LREM")
+                .arg(CALLBACK_TEMP_QUEUE_KEY)
+                .arg(1)
+                .arg(&member)
+                .ignore()
+                .cmd("HDEL")
+                .arg(CALLBACK_SEEN_KEY)
+                .arg(&member)
+                .ignore()
+                .cmd("RPUSH")
+                .arg(CALLBACK_QUEUE_KEY)
+                .arg(&member)
+                .ignore()
+                .query_async::<_, ()>(&mut conn)
+                .await?;
+        }
+        Ok(())
+    }
+
+    // histogram buckets are stored cumulatively as '{task}:{bucket index}' next to '{task}:sum',
+    // by task key since names repeat, e.g. for expanded tasks
+    async fn record_task_metrics(
+        pipeline_name: &str,
+        task: &str,
+        result: &TaskResult,
+        conn: &mut deadpool_redis::Connection,
+    ) -> Result<()> {
+        let duration = match (result.started, result.ended) {
+            (Some(started), Some(ended)) => (ended - started).num_milliseconds() as f64 / 1000.0,
+            _ => result.elapsed as f64,
+        };
+        let durations_key = format!("{TASK_DURATIONS_KEY}:{pipeline_name}");
+
+        let mut pipe = pipe();
+        for (i, bucket) in TASK_DURATION_BUCKETS.iter().enumerate() {
+            if duration <= *bucket {
+                pipe.cmd("HINCRBY")
+                    .arg(&durations_key)
+                    .arg(format!("{task}:{i}"))
+                    .arg(1)
+                    .ignore();
+            }
+        }
+        pipe.cmd("HINCRBY")
+            .arg(&durations_key)
+            .arg(format!("{task}:{}", TASK_DURATION_BUCKETS.len()))
+            .arg(1)
+            .ignore()
+            .cmd("HINCRBYFLOAT")
+            .arg(&durations_key)
+            .arg(format!("{task}:sum"))
+            .arg(duration)
+            .ignore();
+        if result.attempt > 1 {
+            pipe.cmd("HINCRBY")
+                .arg(format!("{TASK_RETRIES_KEY}:{pipeline_name}"))
+                .arg(task)
+                .arg(1)
+                .ignore();
+        }
+        if !result.success {
+            pipe.cmd("HINCRBY")
+                .arg(format!("{TASK_FAILURES_KEY}:{pipeline_name}"))
+                .arg(task)
+                .arg(1)
+                .ignore();
+        }
+        pipe.query_async::<_, ()>(conn).await?;
+        Ok(())
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    pub async fn get_task_metrics(pipeline_name: &str, pool: Pool) -> Result<TaskMetrics> {
+        let mut conn = pool.get().await.expect("DB connection failed");
+        let mut task_metrics = TaskMetrics::default();
+
+        let durations: HashMap<String, String> = cmd("HGETALL")
+            .arg(format!("{TASK_DURATIONS_KEY}:{pipeline_name}"))
+            .query_async(&mut conn)
+            .await?;
+        for (field, value) in durations {
+            let Some((task, suffix)) = field.rsplit_once(':') else {
+                continue;
+            };
+            if suffix == "sum" {
+                task_metrics
+                    .duration_sums
+                    .insert(task.to_string(), value.parse()?);
+            } else {
+                let buckets = task_metrics
+                    .duration_buckets
+                    .entry(task.to_string())
+                    .or_insert(vec![0; TASK_DURATION_BUCKETS.len() + 1]);
+                buckets[suffix.parse::<usize>()?] = value.parse()?;
+            }
+        }
+
+        task_metrics.retries = cmd("HGETALL")
+            .arg(format!("{TASK_RETRIES_KEY}:{pipeline_name}"))
+            .query_async(&mut conn)
+            .await?;
+        task_metrics.failures = cmd("HGETALL")
+            .arg(format!("{TASK_FAILURES_KEY}:{pipeline_name}"))
+            .query_async(&mut conn)
+            .await?;
+        Ok(task_metrics)
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    pub async fn set_scheduler_lag(pipeline_name: &str, lag: f64, pool: Pool) -> Result<()> {
+        let mut conn = pool.get().await.expect("DB connection failed");
+        cmd("HSET")
+            .arg(SCHEDULER_LAG_KEY)
+            .arg(pipeline_name)
+            .arg(lag)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    pub async fn get_scheduler_lags(pool: Pool) -> Result<BTreeMap<String, f64>> {
+        let mut conn = pool.get().await.expect("DB connection failed");
+        Ok(cmd("HGETALL")
+            .arg(SCHEDULER_LAG_KEY)
+            .query_async(&mut conn)
+            .await?)
+    }
+
+    // misses are keyed by task, or `run` for the run itself, so each is only recorded once
+    #[timed(duration(printer = "debug!"))]
+    pub async fn record_sla_miss(sla_miss: &SlaMiss, pool: Pool) -> Result<bool> {
+        let mut conn = pool.get().await.expect("DB connection failed");
+        Ok(cmd("HSETNX")
+            .arg(format!("{SLA_MISSES_KEY}:{}", sla_miss.run_id))
+            .arg(
+                sla_miss
+                    .task_id
+                    .map_or("run".to_string(), |t| t.to_string()),
+            )
+            .arg(serde_json::to_string(sla_miss)?)
+            .query_async::<_, bool>(&mut conn)
             .await?)
     }
+
+    #[timed(duration(printer = "debug!"))]
+    pub async fn get_sla_misses(pipeline_name: &str, pool: Pool) -> Result<Vec<SlaMiss>> {
+        let runs = RedisBackend::get_runs(pipeline_name, pool.clone()).await?;
+        if runs.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut conn = pool.get().await.expect("DB connection failed");
+        let mut pipe = pipe();
+        for run in &runs {
+            pipe.cmd("HVALS")
+                .arg(format!("{SLA_MISSES_KEY}:{}", run.run_id));
+        }
+        let members = pipe.query_async::<_, Vec<Vec<String>>>(&mut conn).await?;
+
+        let mut v = vec![];
+
+        for s in members.iter().flatten() {
+            v.push(serde_json::from_str::<SlaMiss>(s)?);
+        }
+        v.sort_by_key(|sla_miss| sla_miss.detected);
+        Ok(v)
+    }
+
+    // runs whose SLA has not been met or missed yet, scored by their scheduled date
+    #[timed(duration(printer = "debug!"))]
+    pub async fn get_unsettled_sla_runs(
+        pipeline_name: &str,
+        scheduled_until: DateTime<Utc>,
+        pool: Pool,
+    ) -> Result<Vec<(usize, DateTime<Utc>)>> {
+        let mut conn = pool.get().await.expect("DB connection failed");
+        let runs = cmd("ZRANGEBYSCORE")
+            .arg(format!("{SLA_UNSETTLED_KEY}:{pipeline_name}"))
+            .arg("-inf")
+            .arg(scheduled_until.timestamp_millis())
+            .arg("WITHSCORES")
+            .query_async::<_, Vec<(usize, i64)>>(&mut conn)
+            .await?;
+        Ok(runs
+            .into_iter()
+            .filter_map(|(run_id, scheduled)| {
+                Some((run_id, DateTime::from_timestamp_millis(scheduled)?))
+            })
+            .collect())
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    pub async fn settle_sla(pipeline_name: &str, run_id: usize, pool: Pool) -> Result<()> {
+        let mut conn = pool.get().await.expect("DB connection failed");
+        cmd("ZREM")
+            .arg(format!("{SLA_UNSETTLED_KEY}:{pipeline_name}"))
+            .arg(run_id)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    // when the SLA of the pipeline was first checked, runs due before then are not checked
+    #[timed(duration(printer = "debug!"))]
+    pub async fn get_sla_since(
+        pipeline_name: &str,
+        now: DateTime<Utc>,
+        pool: Pool,
+    ) -> Result<DateTime<Utc>> {
+        let mut conn = pool.get().await.expect("DB connection failed");
+        let key = format!("{SLA_SINCE_KEY}:{pipeline_name}");
+        cmd("SET")
+            .arg(&key)
+            .arg(serde_json::to_string(&now)?)
+            .arg("NX")
+            .query_async::<_, Option<String>>(&mut conn)
+            .await?;
+        Ok(serde_json::from_str(
+            &cmd("GET")
+                .arg(&key)
+                .query_async::<_, String>(&mut conn)
+                .await?,
+        )?)
+    }
+
+    // removing the SLA resets when it was first checked
+    #[timed(duration(printer = "debug!"))]
+    pub async fn delete_sla_since(pipeline_name: &str, pool: Pool) -> Result<()> {
+        let mut conn = pool.get().await.expect("DB connection failed");
+        cmd("DEL")
+            .arg(format!("{SLA_SINCE_KEY}:{pipeline_name}"))
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    pub async fn get_run_end_date(run_id: usize, pool: Pool) -> Result<Option<DateTime<Utc>>> {
+        let mut conn = pool.get().await.expect("DB connection failed");
+        let end_date = cmd("GET")
+            .arg(format!("{RUN_END_DATE_KEY}:{run_id}"))
+            .query_async::<_, Option<String>>(&mut conn)
+            .await?;
+        Ok(match end_date {
+            Some(end_date) => Some(serde_json::from_str(&end_date)?),
+            None => None,
+        })
+    }
+
+    // every key holding state of the run, scheduled dates are kept so the run is not rescheduled
+    #[timed(duration(printer = "debug!"))]
+    pub async fn get_run_keys(run_id: usize, pool: Pool) -> Result<Vec<String>> {
+        let mut conn = pool.get().await.expect("DB connection failed");
+        let mut keys: Vec<String> = [
+            EDGES_KEY,
+            TASKS_KEY,
+            TASK_ID_KEY,
+            TRIGGER_PARAMS_KEY,
+            RUN_VARIABLES_KEY,
+            RUN_EVENTS_KEY,
+            RUN_END_DATE_KEY,
+            SLA_MISSES_KEY,
+            QUEUED_TRACEPARENTS_KEY,
+        ]
+        .iter()
+        .map(|prefix| format!("{prefix}:{run_id}"))
+        .collect();
+
+        let task_ids = cmd("SMEMBERS")
+            .arg(format!("{TASKS_KEY}:{run_id}"))
+            .query_async::<_, Vec<String>>(&mut conn)
+            .await?
+            .iter()
+            .map(|member| Ok(serde_json::from_str::<Task>(member)?.id))
+            .collect::<Result<Vec<usize>>>()?;
+        if task_ids.is_empty() {
+            return Ok(keys);
+        }
+
+        // attempts of dynamic and static enqueues are counted apart
+        let attempt_keys: Vec<String> = task_ids
+            .iter()
+            .flat_map(|task_id| {
+                [false, true]
+                    .map(|is_dynamic| format!("{TASK_ATTEMPT_KEY}:{run_id}:{task_id}:{is_dynamic}"))
+            })
+            .collect();
+        let attempts = cmd("MGET")
+            .arg(&attempt_keys)
+            .query_async::<_, Vec<Option<usize>>>(&mut conn)
+            .await?;
+
+        for (task_id, attempts) in task_ids.iter().zip(attempts.chunks(2)) {
+            for prefix in [
+                TASK_STATUS_KEY,
+                TASK_RESULTS_KEY,
+                TASK_RESULT_KEY,
+                DEPTH_KEY,
+                DEPENDENCY_KEYS_KEY,
+                TASK_KEY,
+                TEMPLATE_ARGS_KEY,
+            ] {
+                keys.push(format!("{prefix}:{run_id}:{task_id}"));
+            }
+            for attempt in 1..=attempts.iter().flatten().max().copied().unwrap_or(0) {
+                keys.push(format!("{LOG_KEY}:{run_id}:{task_id}:{attempt}"));
+                for event in [
+                    CallbackEvent::OnSuccess,
+                    CallbackEvent::OnFailure,
+                    CallbackEvent::OnRetry,
+                ] {
+                    keys.push(format!(
+                        "{CALLBACK_LOG_KEY}:{run_id}:{task_id}:{attempt}:{event}"
+                    ));
+                }
+            }
+        }
+        keys.extend(attempt_keys);
+        Ok(keys)
+    }
+
+    // removes the run and all of its keys in a single transaction, offloaded results are deleted
+    // from the result store once the transaction committed
+    #[timed(duration(printer = "debug!"))]
+    pub async fn delete_run(run: &Run, pool: Pool) -> Result<()> {
+        let result_offload = get_result_offload()?;
+        let mut result_refs = vec![];
+        if result_offload.is_some() {
+            let backend = RedisBackend::from(&run.pipeline_name, pool.clone());
+            for task in backend.get_all_tasks(run.run_id)? {
+                for task_result in
+                    RedisBackend::get_all_results(run.run_id, task.id, pool.clone()).await?
+                {
+                    result_refs.extend(task_result.result_ref);
+                }
+            }
+        }
+        let keys = RedisBackend::get_run_keys(run.run_id, pool.clone()).await?;
+        let mut conn = pool.get().await.expect("DB connection failed");
+        let runs_key = format!("{RUNS_KEY}:{}", run.pipeline_name);
+
+        // runs stored by older versions may serialize differently, remove the stored member
+        let member = cmd("LRANGE")
+            .arg(&runs_key)
+            .arg(0)
+            .arg(-1)
+            .query_async::<_, Vec<String>>(&mut conn)
+            .await?
+            .into_iter()
+            .find(|member| {
+                serde_json::from_str::<Run>(member).is_ok_and(|r| r.run_id == run.run_id)
+            })
+            .ok_or_else(|| anyhow!("could not find run '{}'", run.run_id))?;
+
+        pipe()
+            .atomic()
+            .cmd("DEL")
+            .arg(keys)
+            .ignore()
+            .cmd("LREM")
+            .arg(runs_key)
+            .arg(1)
+            .arg(member)
+            .ignore()
+            .cmd("ZREM")
+            .arg(format!("{SLA_UNSETTLED_KEY}:{}", run.pipeline_name))
+            .arg(run.run_id)
+            .ignore()
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+
+        if let Some(result_offload) = result_offload {
+            for result_ref in result_refs {
+                if let Err(e) = result_offload.delete(&result_ref) {
+                    eprintln!("could not delete offloaded result {result_ref}: {e:?}");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RedisBackend {
+    // run ids are unique across pipelines, so run variables don't need the pipeline name
+    fn get_variables_key(&self, scope: VariableScope) -> Result<String> {
+        Ok(match scope {
+            VariableScope::Global => GLOBAL_VARIABLES_KEY.to_string(),
+            VariableScope::Pipeline => format!("{VARIABLES_KEY}:{}", self.get_pipeline_name()?),
+            VariableScope::Run(run_id) => format!("{RUN_VARIABLES_KEY}:{run_id}"),
+        })
+    }
+}
+
+// the trace context the task was enqueued with, kept apart from the queue member
+async fn take_queued_traceparent(
+    queued_task: &QueuedTask,
+    conn: &mut deadpool_redis::Connection,
+) -> Result<Option<String>> {
+    let key = format!("{QUEUED_TRACEPARENTS_KEY}:{}", queued_task.run_id);
+    let field = format!("{}:{}", queued_task.task_id, queued_task.attempt);
+    let traceparent = cmd("HGET")
+        .arg(&key)
+        .arg(&field)
+        .query_async::<_, Option<String>>(conn)
+        .await?;
+    cmd("HDEL")
+        .arg(&key)
+        .arg(&field)
+        .query_async::<_, ()>(conn)
+        .await?;
+    Ok(traceparent)
 }
 
 impl Backend for RedisBackend {
@@ -310,17 +902,26 @@ impl Backend for RedisBackend {
     }
 
     #[timed(duration(printer = "debug!"))]
-    fn get_log(&mut self, run_id: usize, task_id: usize, attempt: usize) -> Result<String> {
+    fn get_log_lines(
+        &mut self,
+        run_id: usize,
+        task_id: usize,
+        attempt: usize,
+        offset: usize,
+    ) -> Result<Vec<LogLine>> {
         block_on!({
             let mut conn = self.pool.get().await.expect("DB connection failed");
             let members = cmd("LRANGE")
                 .arg(format!("{LOG_KEY}:{run_id}:{task_id}:{attempt}"))
-                .arg(0)
+                .arg(offset)
                 .arg(-1)
                 .query_async::<_, Vec<String>>(&mut conn)
                 .await?;
 
-            Ok(members.join("\n"))
+            Ok(members
+                .iter()
+                .map(|member| LogLine::parse(attempt, member))
+                .collect())
         })
     }
 
@@ -330,14 +931,66 @@ impl Backend for RedisBackend {
         run_id: usize,
         task_id: usize,
         attempt: usize,
-    ) -> Result<Box<dyn Fn(String) -> Result<()> + Send>> {
+    ) -> Result<Box<dyn Fn(LogLine) -> Result<()> + Send>> {
         let pool = self.pool.clone();
-        Ok(Box::new(move |s| {
+        Ok(Box::new(move |line| {
             tokio::runtime::Runtime::new()?.block_on(async {
                 let mut conn = pool.get().await.expect("DB connection failed");
                 cmd("RPUSH")
                     .arg(format!("{LOG_KEY}:{run_id}:{task_id}:{attempt}"))
-                    .arg(s)
+                    .arg(serde_json::to_string(&line)?)
+                    .query_async::<_, usize>(&mut conn)
+                    .await?;
+
+                Ok(())
+            })
+        }))
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn get_callback_log_lines(
+        &mut self,
+        run_id: usize,
+        task_id: usize,
+        attempt: usize,
+        event: CallbackEvent,
+    ) -> Result<Vec<LogLine>> {
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            let members = cmd("LRANGE")
+                .arg(format!(
+                    "{CALLBACK_LOG_KEY}:{run_id}:{task_id}:{attempt}:{event}"
+                ))
+                .arg(0)
+                .arg(-1)
+                .query_async::<_, Vec<String>>(&mut conn)
+                .await?;
+
+            let mut v = vec![];
+
+            for s in members {
+                v.push(serde_json::from_str(&s)?);
+            }
+            Ok(v)
+        })
+    }
+
+    fn get_callback_log_handle_closure(
+        &mut self,
+        run_id: usize,
+        task_id: usize,
+        attempt: usize,
+        event: CallbackEvent,
+    ) -> Result<Box<dyn Fn(LogLine) -> Result<()> + Send>> {
+        let pool = self.pool.clone();
+        Ok(Box::new(move |line| {
+            tokio::runtime::Runtime::new()?.block_on(async {
+                let mut conn = pool.get().await.expect("DB connection failed");
+                cmd("RPUSH")
+                    .arg(format!(
+                        "{CALLBACK_LOG_KEY}:{run_id}:{task_id}:{attempt}:{event}"
+                    ))
+                    .arg(serde_json::to_string(&line)?)
                     .query_async::<_, usize>(&mut conn)
                     .await?;
 
@@ -397,21 +1050,96 @@ impl Backend for RedisBackend {
         run_id: usize,
         task_id: usize,
         task_status: TaskStatus,
+        reason: Option<&str>,
     ) -> Result<()> {
-        block_on!({
+        let old_status = block_on!({
             let mut conn = self.pool.get().await.expect("DB connection failed");
-            cmd("SET")
+            // GETSET rather than SET ... GET, which needs redis 6.2
+            cmd("GETSET")
                 .arg(format!("{TASK_STATUS_KEY}:{run_id}:{task_id}"))
                 .arg(serde_json::to_string(&task_status)?)
-                .query_async::<_, String>(&mut conn)
-                .await?;
+                .query_async::<_, Option<String>>(&mut conn)
+                .await?
+                .map(|s| serde_json::from_str::<TaskStatus>(&s))
+                .transpose()
+                .map_err(anyhow::Error::from)
+        })?;
+
+        self.append_run_event(
+            run_id,
+            &RunEvent::new(
+                &self.get_actor(),
+                Some(task_id),
+                old_status.map(|s| format!("{s:?}")),
+                format!("{task_status:?}"),
+                reason,
+            ),
+        )
+    }
+
+    // processes without an actor are the server itself
+    fn get_actor(&self) -> String {
+        self.actor.clone().unwrap_or("server".to_string())
+    }
 
+    #[timed(duration(printer = "debug!"))]
+    fn append_run_event(&mut self, run_id: usize, event: &RunEvent) -> Result<()> {
+        let mut event = event.clone();
+        if event.claimed_actor.is_none() {
+            event.claimed_actor = self.claimed_actor.clone();
+        }
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            cmd("RPUSH")
+                .arg(format!("{RUN_EVENTS_KEY}:{run_id}"))
+                .arg(serde_json::to_string(&event)?)
+                .query_async::<_, ()>(&mut conn)
+                .await?;
             Ok(())
         })
     }
 
     #[timed(duration(printer = "debug!"))]
-    fn create_new_run(&mut self, scheduled_date_for_run: DateTime<Utc>) -> Result<Run> {
+    fn get_run_events(&self, run_id: usize) -> Result<Vec<RunEvent>> {
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            let members = cmd("LRANGE")
+                .arg(format!("{RUN_EVENTS_KEY}:{run_id}"))
+                .arg(0)
+                .arg(-1)
+                .query_async::<_, Vec<String>>(&mut conn)
+                .await?;
+
+            let mut v = vec![];
+
+            for s in members {
+                v.push(serde_json::from_str(&s)?);
+            }
+            Ok(v)
+        })
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn set_run_end_date(&mut self, run_id: usize, end_date: DateTime<Utc>) -> Result<bool> {
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            // SET NX so that only one worker claims the end of the run
+            Ok(cmd("SET")
+                .arg(format!("{RUN_END_DATE_KEY}:{run_id}"))
+                .arg(serde_json::to_string(&end_date)?)
+                .arg("NX")
+                .query_async::<_, Option<String>>(&mut conn)
+                .await?
+                .is_some())
+        })
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn create_new_run(
+        &mut self,
+        scheduled_date_for_run: DateTime<Utc>,
+        pipeline_hash: &str,
+    ) -> Result<Run> {
         block_on!({
             let mut conn = self.pool.get().await.expect("DB connection failed");
 
@@ -426,18 +1154,224 @@ impl Backend for RedisBackend {
                 run_id,
                 pipeline_name: pipeline_name.to_string(),
                 scheduled_date_for_run,
+                pipeline_hash: pipeline_hash.to_string(),
             };
 
-            cmd("RPUSH")
+            pipe()
+                .cmd("RPUSH")
                 .arg(format!("{RUNS_KEY}:{pipeline_name}"))
                 .arg(serde_json::to_string(&run)?)
+                .ignore()
+                .cmd("ZADD")
+                .arg(format!("{SLA_UNSETTLED_KEY}:{pipeline_name}"))
+                .arg(scheduled_date_for_run.timestamp_millis())
+                .arg(run_id)
+                .ignore()
                 .query_async::<_, ()>(&mut conn)
                 .await?;
 
+            self.append_run_event(
+                run_id,
+                &RunEvent::new(
+                    &self.get_actor(),
+                    None,
+                    None,
+                    "Created",
+                    Some(&format!("scheduled for {scheduled_date_for_run}")),
+                ),
+            )?;
+
             Ok(run)
         })
     }
 
+    #[timed(duration(printer = "debug!"))]
+    fn get_variables(&self, scope: VariableScope) -> Result<HashMap<String, Value>> {
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            let variables: HashMap<String, String> = cmd("HGETALL")
+                .arg(self.get_variables_key(scope)?)
+                .query_async(&mut conn)
+                .await?;
+
+            variables
+                .into_iter()
+                .map(|(key, value)| Ok((key, serde_json::from_str(&value)?)))
+                .collect()
+        })
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn get_variable(&self, scope: VariableScope, key: &str) -> Result<Option<Value>> {
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            let value: Option<String> = cmd("HGET")
+                .arg(self.get_variables_key(scope)?)
+                .arg(key)
+                .query_async(&mut conn)
+                .await?;
+
+            Ok(match value {
+                Some(value) => Some(serde_json::from_str(&value)?),
+                None => None,
+            })
+        })
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn set_variable(&mut self, scope: VariableScope, key: &str, value: &Value) -> Result<()> {
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            cmd("HSET")
+                .arg(self.get_variables_key(scope)?)
+                .arg(key)
+                .arg(serde_json::to_string(value)?)
+                .query_async::<_, ()>(&mut conn)
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn delete_variable(&mut self, scope: VariableScope, key: &str) -> Result<()> {
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            cmd("HDEL")
+                .arg(self.get_variables_key(scope)?)
+                .arg(key)
+                .query_async::<_, ()>(&mut conn)
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn get_secret_names(&self) -> Result<Vec<String>> {
+        get_secret_provider(self.pool.clone())?.get_secret_names()
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn get_secret(&self, name: &str) -> Result<Option<String>> {
+        get_secret_provider(self.pool.clone())?.get_secret(name)
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn set_secret(&mut self, name: &str, value: &str) -> Result<()> {
+        get_secret_provider(self.pool.clone())?.set_secret(name, value)
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn delete_secret(&mut self, name: &str) -> Result<()> {
+        get_secret_provider(self.pool.clone())?.delete_secret(name)
+    }
+
+    fn get_result_offload(&self) -> Result<Option<ResultOffload>> {
+        get_result_offload()
+    }
+
+    fn get_pipeline_options(&self) -> Result<PipelineOptions> {
+        block_on!({ self.get_options().await })
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn get_connection_ids(&self) -> Result<Vec<String>> {
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            Ok(cmd("HKEYS")
+                .arg(CONNECTIONS_KEY)
+                .query_async::<_, Vec<String>>(&mut conn)
+                .await?)
+        })
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn get_connection(&self, id: &str) -> Result<Option<Connection>> {
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            let connection: Option<String> = cmd("HGET")
+                .arg(CONNECTIONS_KEY)
+                .arg(id)
+                .query_async(&mut conn)
+                .await?;
+
+            connection
+                .map(|connection| {
+                    let mut connection: Connection = serde_json::from_str(&connection)?;
+                    if !connection.password.is_empty() {
+                        connection.password = decrypt_secret(&connection.password)?;
+                    }
+                    Ok(connection)
+                })
+                .transpose()
+        })
+    }
+
+    // passwords are stored encrypted like secrets
+    #[timed(duration(printer = "debug!"))]
+    fn set_connection(&mut self, id: &str, connection: &Connection) -> Result<()> {
+        let mut connection = connection.clone();
+        if !connection.password.is_empty() {
+            connection.password = encrypt_secret(&connection.password)?;
+        }
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            cmd("HSET")
+                .arg(CONNECTIONS_KEY)
+                .arg(id)
+                .arg(serde_json::to_string(&connection)?)
+                .query_async::<_, ()>(&mut conn)
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn delete_connection(&mut self, id: &str) -> Result<()> {
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            cmd("HDEL")
+                .arg(CONNECTIONS_KEY)
+                .arg(id)
+                .query_async::<_, ()>(&mut conn)
+                .await?;
+
+            Ok(())
+        })
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn get_trigger_params(&self, run_id: usize) -> Result<Value> {
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            let trigger_params: Option<String> = cmd("GET")
+                .arg(format!("{TRIGGER_PARAMS_KEY}:{run_id}"))
+                .query_async(&mut conn)
+                .await?;
+
+            Ok(match trigger_params {
+                Some(trigger_params) => serde_json::from_str(&trigger_params)?,
+                None => Value::Null,
+            })
+        })
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn set_trigger_params(&mut self, run_id: usize, trigger_params: &Value) -> Result<()> {
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            cmd("SET")
+                .arg(format!("{TRIGGER_PARAMS_KEY}:{run_id}"))
+                .arg(serde_json::to_string(trigger_params)?)
+                .query_async::<_, ()>(&mut conn)
+                .await?;
+
+            Ok(())
+        })
+    }
+
     #[timed(duration(printer = "debug!"))]
     fn insert_task_results(&mut self, run_id: usize, result: &TaskResult) -> Result<()> {
         block_on!({
@@ -456,6 +1390,17 @@ impl Backend for RedisBackend {
                 .query_async::<_, ()>(&mut conn)
                 .await?;
 
+            if let Some(pipeline_name) = &self.name {
+                let task: Task = serde_json::from_str(
+                    &cmd("GET")
+                        .arg(format!("{TASK_KEY}:{run_id}:{task_id}"))
+                        .query_async::<_, String>(&mut conn)
+                        .await?,
+                )?;
+                RedisBackend::record_task_metrics(pipeline_name, &task.key, result, &mut conn)
+                    .await?;
+            }
+
             Ok(())
         })
     }
@@ -637,6 +1582,53 @@ impl Backend for RedisBackend {
         })
     }
 
+    #[timed(duration(printer = "debug!"))]
+    fn get_current_pipeline_hash(&self) -> Result<String> {
+        block_on!({
+            let pipeline_name = self.get_pipeline_name()?;
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+
+            // pipelines uploaded before versioning have no hash
+            Ok(cmd("GET")
+                .arg(format!("{CURRENT_PIPELINE_HASH_KEY}:{pipeline_name}"))
+                .query_async::<_, Option<String>>(&mut conn)
+                .await?
+                .unwrap_or_default())
+        })
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn get_tasks_by_pipeline_hash(&self, pipeline_hash: &str) -> Result<Vec<Task>> {
+        if pipeline_hash.is_empty() {
+            return self.get_default_tasks();
+        }
+        block_on!({
+            Ok(Self::get_pipeline_version(
+                &self.get_pipeline_name()?,
+                pipeline_hash,
+                self.pool.clone(),
+            )
+            .await?
+            .tasks)
+        })
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn get_edges_by_pipeline_hash(&self, pipeline_hash: &str) -> Result<HashSet<(usize, usize)>> {
+        if pipeline_hash.is_empty() {
+            return self.get_default_edges();
+        }
+        block_on!({
+            Ok(Self::get_pipeline_version(
+                &self.get_pipeline_name()?,
+                pipeline_hash,
+                self.pool.clone(),
+            )
+            .await?
+            .edges)
+        })
+    }
+
     #[timed(duration(printer = "debug!"))]
     fn get_task_by_id(&self, run_id: usize, task_id: usize) -> Result<Task> {
         block_on!({
@@ -654,6 +1646,7 @@ impl Backend for RedisBackend {
     fn append_new_task_and_set_status_to_pending(
         &mut self,
         run_id: usize,
+        key: &str,
         name: &str,
         function_name: &str,
         template_args: &Value,
@@ -662,6 +1655,7 @@ impl Backend for RedisBackend {
         is_dynamic: bool,
         is_branch: bool,
         use_trigger_params: bool,
+        connections: &[String],
     ) -> Result<usize> {
         block_on!({
             let mut conn = self.pool.get().await.expect("DB connection failed");
@@ -674,6 +1668,7 @@ impl Backend for RedisBackend {
 
             let task = Task {
                 id: task_id,
+                key: key.to_owned(),
                 name: name.to_owned(),
                 function: function_name.to_owned(),
                 template_args: template_args.to_owned(),
@@ -682,6 +1677,7 @@ impl Backend for RedisBackend {
                 is_dynamic,
                 is_branch,
                 use_trigger_params,
+                connections: connections.to_vec(),
             };
             cmd("SADD")
                 .arg(format!("{TASKS_KEY}:{run_id}"))
@@ -698,7 +1694,7 @@ impl Backend for RedisBackend {
                 .arg(serde_json::to_string(&task.template_args)?)
                 .query_async::<_, ()>(&mut conn)
                 .await?;
-            self.set_task_status(run_id, task_id, TaskStatus::Pending)?;
+            self.set_task_status(run_id, task_id, TaskStatus::Pending, None)?;
             Ok(task_id)
         })
     }
@@ -731,6 +1727,86 @@ impl Backend for RedisBackend {
         })
     }
 
+    #[timed(duration(printer = "debug!"))]
+    fn enqueue_callbacks(&mut self, queued_callback: &QueuedCallback) -> Result<()> {
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            cmd("LPUSH")
+                .arg(CALLBACK_QUEUE_KEY)
+                .arg(serde_json::to_string(queued_callback)?)
+                .query_async::<_, ()>(&mut conn)
+                .await?;
+            Ok(())
+        })
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn enqueue_notification(&mut self, notification: &Notification) -> Result<()> {
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            cmd("LPUSH")
+                .arg(NOTIFICATION_QUEUE_KEY)
+                .arg(serde_json::to_string(notification)?)
+                .query_async::<_, ()>(&mut conn)
+                .await?;
+            Ok(())
+        })
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn pop_notification_queue(&mut self) -> Result<Option<Notification>> {
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            let popped = cmd("RPOP")
+                .arg(NOTIFICATION_QUEUE_KEY)
+                .query_async::<_, Option<String>>(&mut conn)
+                .await?;
+            Ok(match popped {
+                Some(popped) => Some(serde_json::from_str(&popped)?),
+                None => None,
+            })
+        })
+    }
+
+    // the oldest callback is moved to the temp queue in one step, so it is never lost
+    #[timed(duration(printer = "debug!"))]
+    fn pop_callback_queue(&mut self) -> Result<Option<QueuedCallback>> {
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            let popped = cmd("RPOPLPUSH")
+                .arg(CALLBACK_QUEUE_KEY)
+                .arg(CALLBACK_TEMP_QUEUE_KEY)
+                .query_async::<_, Option<String>>(&mut conn)
+                .await?;
+            Ok(match popped {
+                Some(popped) => Some(serde_json::from_str(&popped)?),
+                None => None,
+            })
+        })
+    }
+
+    #[timed(duration(printer = "debug!"))]
+    fn remove_from_callback_temp_queue(&self, queued_callback: &QueuedCallback) -> Result<()> {
+        block_on!({
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            let member = serde_json::to_string(queued_callback)?;
+            pipe()
+                .atomic()
+                .cmd("LREM")
+                .arg(CALLBACK_TEMP_QUEUE_KEY)
+                .arg(1)
+                .arg(&member)
+                .ignore()
+                .cmd("HDEL")
+                .arg(CALLBACK_SEEN_KEY)
+                .arg(&member)
+                .ignore()
+                .query_async::<_, ()>(&mut conn)
+                .await?;
+            Ok(())
+        })
+    }
+
     // #[timed(duration(printer = "debug!"))]
     fn pop_priority_queue(&mut self) -> Result<Option<TempQueuedTask>> {
         block_on!({
@@ -743,9 +1819,12 @@ impl Backend for RedisBackend {
 
             if let Ok(vec) = &res {
                 if !vec.is_empty() {
+                    let mut queued_task: QueuedTask = serde_json::from_str(&vec[0])?;
+                    queued_task.traceparent =
+                        take_queued_traceparent(&queued_task, &mut conn).await?;
                     let temp_queued_task = TempQueuedTask {
                         popped_date: Utc::now(),
-                        queued_task: serde_json::from_str(&vec[0])?,
+                        queued_task,
                     };
                     cmd("SADD")
                         .arg(&[
@@ -835,6 +1914,8 @@ impl Backend for RedisBackend {
                         .await?;
                 }
             }
+            // the member has no traceparent, so enqueueing the same attempt from two spans
+            // still adds it once
             cmd("ZADD")
                 .arg(&[
                     "queue".to_string(),
@@ -845,11 +1926,30 @@ impl Backend for RedisBackend {
                         pipeline_name,
                         scheduled_date_for_run,
                         attempt,
+                        traceparent: None,
                     })?,
                 ])
                 .query_async::<_, usize>(&mut conn)
                 .await?;
-            Ok(())
+            if let Some(traceparent) = get_traceparent(&Context::current()) {
+                cmd("HSET")
+                    .arg(format!("{QUEUED_TRACEPARENTS_KEY}:{run_id}"))
+                    .arg(format!("{task_id}:{attempt}"))
+                    .arg(traceparent)
+                    .query_async::<_, ()>(&mut conn)
+                    .await?;
+            }
+
+            self.append_run_event(
+                run_id,
+                &RunEvent::new(
+                    &self.get_actor(),
+                    Some(task_id),
+                    Some(format!("{:?}", self.get_task_status(run_id, task_id)?)),
+                    "Queued",
+                    Some(&format!("attempt {attempt}")),
+                ),
+            )
         })
     }
 
@@ -858,34 +1958,6 @@ impl Backend for RedisBackend {
         Ok(())
     }
 
-    // #[timed(duration(printer = "debug!"))]
-    fn take_last_stdout_line(
-        &mut self,
-        run_id: usize,
-        task_id: usize,
-        attempt: usize,
-    ) -> Result<Box<dyn Fn() -> Result<String> + Send>> {
-        let pool = self.pool.clone();
-        Ok(Box::new(move || {
-            tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(async {
-                    let mut conn = pool.get().await.expect("DB connection failed");
-                    let lines = cmd("RPOP")
-                        .arg(format!("{LOG_KEY}:{run_id}:{task_id}:{attempt}"))
-                        .arg(1)
-                        .query_async::<_, Vec<String>>(&mut conn)
-                        .await?;
-
-                    if lines.is_empty() {
-                        Ok("".to_string())
-                    } else {
-                        Ok(lines[0].to_string())
-                    }
-                })
-            })
-        }))
-    }
-
     fn get_pipeline_name(&self) -> Result<String> {
         if let Some(name) = &self.name {
             Ok(name.into())
@@ -0,0 +1,233 @@
+use std::{collections::HashMap, fs, io::Write, path::Path, time::Duration};
+
+use chrono::{DateTime, Utc};
+use deadpool_redis::Pool;
+use flate2::{write::GzEncoder, Compression};
+use serde::Serialize;
+use thepipelinetool_core::dev::*;
+use thepipelinetool_runner::{
+    backend::Backend,
+    blanket_backend::BlanketBackend,
+    pipeline_options::PipelineOptions,
+    run::{Run, RunEvent},
+};
+use tokio::time::sleep;
+
+use anyhow::Result;
+
+use crate::{
+    env::{get_archive_path, get_retention_loop_interval},
+    redis_backend::RedisBackend,
+};
+
+#[derive(Serialize)]
+struct RunArchive<'a> {
+    run: &'a Run,
+    tasks: Vec<Task>,
+    // task id -> all attempts
+    results: HashMap<usize, Vec<TaskResult>>,
+    // task id -> attempt -> lines
+    logs: HashMap<usize, HashMap<usize, Vec<LogLine>>>,
+    events: Vec<RunEvent>,
+}
+
+// `runs` are ordered oldest first, as stored by the backend
+pub fn get_expired_runs<'a>(
+    options: &PipelineOptions,
+    runs: &'a [Run],
+    now: DateTime<Utc>,
+) -> Vec<&'a Run> {
+    let keep_from = options.retention_runs.map_or(0, |retention_runs| {
+        runs.len().saturating_sub(retention_runs)
+    });
+
+    runs.iter()
+        .enumerate()
+        .filter(|(i, run)| {
+            *i < keep_from
+                || options.retention_days.is_some_and(|retention_days| {
+                    now - run.scheduled_date_for_run > chrono::Duration::days(retention_days as i64)
+                })
+        })
+        .map(|(_, run)| run)
+        .collect()
+}
+
+pub fn is_run_done(backend: &RedisBackend, run_id: usize) -> Result<bool> {
+    for task in backend.get_all_tasks(run_id)? {
+        if matches!(
+            backend.get_task_status(run_id, task.id)?,
+            TaskStatus::Pending | TaskStatus::Running | TaskStatus::RetryPending
+        ) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+// writes the tasks, results, logs and events of the run to `{path}/{pipeline}/{run_id}.json.gz`
+async fn archive_run(run: &Run, path: &Path, pool: Pool) -> Result<()> {
+    let mut backend = RedisBackend::from(&run.pipeline_name, pool.clone());
+    let tasks = backend.get_all_tasks(run.run_id)?;
+    let mut results = HashMap::new();
+    let mut logs = HashMap::new();
+
+    for task in &tasks {
+        let mut task_results =
+            RedisBackend::get_all_results(run.run_id, task.id, pool.clone()).await?;
+        // offloaded results are deleted with the run, so the archive keeps them inline
+        for task_result in &mut task_results {
+            backend.load_result(task_result)?;
+            task_result.result_ref = None;
+        }
+        let mut task_logs = HashMap::new();
+        for task_result in &task_results {
+            task_logs.insert(
+                task_result.attempt,
+                backend.get_log_lines(run.run_id, task.id, task_result.attempt, 0)?,
+            );
+        }
+        results.insert(task.id, task_results);
+        logs.insert(task.id, task_logs);
+    }
+
+    let archive = RunArchive {
+        run,
+        tasks,
+        results,
+        logs,
+        events: backend.get_run_events(run.run_id)?,
+    };
+    let path = path.join(&run.pipeline_name);
+    fs::create_dir_all(&path)?;
+
+    let mut encoder = GzEncoder::new(
+        fs::File::create(path.join(format!("{}.json.gz", run.run_id)))?,
+        Compression::default(),
+    );
+    encoder.write_all(&serde_json::to_vec(&archive)?)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+// removes the expired runs of one pipeline, a run that can't be removed is retried next loop
+async fn cleanup_pipeline(
+    pipeline_name: &str,
+    archive_path: Option<&Path>,
+    pool: Pool,
+) -> Result<()> {
+    let backend = RedisBackend::from(pipeline_name, pool.clone());
+    let options = backend.get_options().await?;
+    if options.retention_runs.is_none() && options.retention_days.is_none() {
+        // keep all runs of this pipeline
+        return Ok(());
+    }
+
+    let runs = RedisBackend::get_runs(pipeline_name, pool.clone()).await?;
+    for run in get_expired_runs(&options, &runs, Utc::now()) {
+        match cleanup_run(&backend, run, archive_path, pool.clone()).await {
+            Ok(true) => println!("removed run {} of {pipeline_name}", run.run_id),
+            Ok(false) => {}
+            Err(e) => eprintln!(
+                "could not remove run {} of {pipeline_name}: {e:?}",
+                run.run_id
+            ),
+        }
+    }
+    Ok(())
+}
+
+// returns false if the run is not done yet
+async fn cleanup_run(
+    backend: &RedisBackend,
+    run: &Run,
+    archive_path: Option<&Path>,
+    pool: Pool,
+) -> Result<bool> {
+    if !is_run_done(backend, run.run_id)? {
+        return Ok(false);
+    }
+    if let Some(archive_path) = archive_path {
+        archive_run(run, archive_path, pool.clone()).await?;
+    }
+    RedisBackend::delete_run(run, pool).await?;
+    Ok(true)
+}
+
+pub async fn cleanup(pool: Pool) -> Result<()> {
+    let loop_interval = Duration::new(get_retention_loop_interval()?, 0);
+    let archive_path = get_archive_path();
+
+    loop {
+        match RedisBackend::get_pipelines(pool.clone()).await {
+            Ok(pipeline_names) => {
+                for pipeline_name in pipeline_names {
+                    if let Err(e) =
+                        cleanup_pipeline(&pipeline_name, archive_path.as_deref(), pool.clone())
+                            .await
+                    {
+                        eprintln!("could not clean up the runs of {pipeline_name}: {e:?}");
+                    }
+                }
+            }
+            Err(e) => eprintln!("could not read the pipelines: {e:?}"),
+        }
+
+        sleep(loop_interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(run_id: usize, days_ago: i64, now: DateTime<Utc>) -> Run {
+        Run {
+            run_id,
+            pipeline_name: "pipeline".into(),
+            scheduled_date_for_run: now - chrono::Duration::days(days_ago),
+            pipeline_hash: "".into(),
+        }
+    }
+
+    #[test]
+    fn test_get_expired_runs() {
+        let now = Utc::now();
+        let runs = vec![
+            run(0, 10, now),
+            run(1, 5, now),
+            run(2, 1, now),
+            run(3, 0, now),
+        ];
+        let expired_ids = |options: &PipelineOptions| {
+            get_expired_runs(options, &runs, now)
+                .iter()
+                .map(|run| run.run_id)
+                .collect::<Vec<usize>>()
+        };
+
+        assert!(expired_ids(&PipelineOptions::default()).is_empty());
+        assert_eq!(
+            expired_ids(&PipelineOptions {
+                retention_runs: Some(3),
+                ..Default::default()
+            }),
+            vec![0]
+        );
+        assert_eq!(
+            expired_ids(&PipelineOptions {
+                retention_days: Some(3),
+                ..Default::default()
+            }),
+            vec![0, 1]
+        );
+        assert_eq!(
+            expired_ids(&PipelineOptions {
+                retention_runs: Some(1),
+                retention_days: Some(7),
+                ..Default::default()
+            }),
+            vec![0, 1, 2]
+        );
+    }
+}
@@ -1,16 +1,43 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
 
 use axum::{
-    extract::{self, Path, State},
-    http::StatusCode,
+    extract::{self, ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     Json,
 };
+use futures::{stream, Stream, StreamExt};
+use opentelemetry::{
+    trace::{TraceContextExt, Tracer},
+    Context, KeyValue,
+};
+use serde::Deserialize;
 
 use chrono::Utc;
 use thepipelinetool_core::dev::*;
-use thepipelinetool_runner::pipeline::Pipeline;
+use thepipelinetool_runner::{
+    backend::VariableScope,
+    pipeline::{Pipeline, PipelineVersion},
+    pipeline_diff::{diff_pipelines, PipelineDiff},
+    run::{render_run_events_jsonl, RunEvent},
+    telemetry::{get_tracer, record_error},
+    template_variables::REDACTED,
+};
 
-use crate::*;
+use crate::{
+    env::{get_log_stream_idle_timeout, DEFAULT_LOG_STREAM_IDLE_TIMEOUT},
+    metrics::collect_metrics,
+    sla::SlaMiss,
+    *,
+};
 
 type ServerResult<E> = Result<E, (StatusCode, String)>;
 
@@ -22,6 +49,20 @@ pub async fn ping() -> &'static str {
     "pong"
 }
 
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+pub async fn get_metrics(State(pool): State<Pool>) -> ServerResult<Response> {
+    let metrics = collect_metrics(pool)
+        .await
+        .map_err(|e| service_err(format!("could not collect metrics\n{:?}", e)))?;
+
+    Ok((
+        [(header::CONTENT_TYPE, METRICS_CONTENT_TYPE)],
+        metrics.render(),
+    )
+        .into_response())
+}
+
 // TODO paginate
 
 pub async fn get_runs(
@@ -115,7 +156,13 @@ pub async fn get_runs_with_tasks(
                 pipeline_name, e
             ))
         })? {
-            tasks[format!("{}_{}", task.name, task.id)] = json!(task);
+            // keys identify the same task across runs, older runs fall back to name and id
+            let task_key = if task.key.is_empty() {
+                format!("{}_{}", task.name, task.id)
+            } else {
+                task.key.clone()
+            };
+            tasks[task_key] = json!(task);
         }
         res[run.run_id.to_string()] = json!({
             "date": run.scheduled_date_for_run,
@@ -234,6 +281,49 @@ pub async fn get_run_status(
     })?))
 }
 
+// '?format=jsonl' exports the events as JSON Lines
+pub async fn get_run_events(
+    Path(run_id): Path<usize>,
+    Query(query): Query<FormatQuery>,
+    State(pool): State<Pool>,
+) -> ServerResult<Response> {
+    let events = RedisBackend::dummy(pool)
+        .get_run_events(run_id)
+        .map_err(|e| {
+            service_err(format!(
+                "could not get events for run_id '{}'\n{:?}",
+                run_id, e
+            ))
+        })?;
+
+    Ok(match query.format.as_deref() {
+        Some("jsonl") => (
+            [(header::CONTENT_TYPE, "application/x-ndjson")],
+            render_run_events_jsonl(&events),
+        )
+            .into_response(),
+        _ => Json(events).into_response(),
+    })
+}
+
+pub async fn get_sla_misses(
+    Path(pipeline_name): Path<String>,
+    State(pool): State<Pool>,
+) -> ServerResult<Json<Vec<SlaMiss>>> {
+    assert_pipeline_exists(&pipeline_name, pool.clone()).await?;
+
+    Ok(Json(
+        RedisBackend::get_sla_misses(&pipeline_name, pool)
+            .await
+            .map_err(|e| {
+                service_err(format!(
+                    "could not get SLA misses for pipeline '{}'\n{:?}",
+                    pipeline_name, e
+                ))
+            })?,
+    ))
+}
+
 pub async fn get_task_result(
     Path((run_id, task_id)): Path<(usize, usize)>,
     State(pool): State<Pool>,
@@ -248,18 +338,191 @@ pub async fn get_task_result(
     )?))
 }
 
+#[derive(Deserialize)]
+pub struct FormatQuery {
+    format: Option<String>,
+}
+
+// lines can be filtered with e.g. '?stream=stderr&level=error&since=2024-01-01T00:00:00Z',
+// '?format=json' returns the structured lines instead of text
 pub async fn get_task_log(
     Path((run_id, task_id, attempt)): Path<(usize, usize, usize)>,
+    Query(filter): Query<LogFilter>,
+    Query(query): Query<FormatQuery>,
     State(pool): State<Pool>,
-) -> ServerResult<String> {
-    RedisBackend::dummy(pool)
-        .get_log(run_id, task_id, attempt)
+) -> ServerResult<Response> {
+    let lines: Vec<LogLine> = RedisBackend::dummy(pool)
+        .get_log_lines(run_id, task_id, attempt, 0)
         .map_err(|e| {
             service_err(format!(
                 "could not get task log for run_id '{}', task_id '{}', and attempt '{}'\n{:?}",
                 run_id, task_id, attempt, e
             ))
-        })
+        })?
+        .into_iter()
+        .filter(|line| filter.matches(line))
+        .collect();
+
+    Ok(match query.format.as_deref() {
+        Some("json") => Json(lines).into_response(),
+        _ => render_log_lines(&lines).into_response(),
+    })
+}
+
+pub async fn get_callback_log(
+    Path((run_id, task_id, attempt, event)): Path<(usize, usize, usize, CallbackEvent)>,
+    Query(query): Query<FormatQuery>,
+    State(pool): State<Pool>,
+) -> ServerResult<Response> {
+    let lines = RedisBackend::dummy(pool)
+        .get_callback_log_lines(run_id, task_id, attempt, event)
+        .map_err(|e| {
+            service_err(format!(
+                "could not get {} callback log for run_id '{}', task_id '{}', and attempt '{}'\n{:?}",
+                event, run_id, task_id, attempt, e
+            ))
+        })?;
+
+    Ok(match query.format.as_deref() {
+        Some("json") => Json(lines).into_response(),
+        _ => render_log_lines(&lines).into_response(),
+    })
+}
+
+const LOG_STREAM_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Deserialize)]
+pub struct LogStreamQuery {
+    offset: Option<usize>,
+}
+
+// the attempt is over once its result is stored, or never comes once the task is done, the
+// attempt is past its last one or the run was removed, results are only read once the status of
+// the task shows the attempt is no longer running
+async fn get_attempt_end_status(
+    run_id: usize,
+    task_id: usize,
+    attempt: usize,
+    pool: Pool,
+) -> anyhow::Result<Option<String>> {
+    let backend = RedisBackend::dummy(pool.clone());
+    let Ok(status) = backend.get_task_status(run_id, task_id) else {
+        return Ok(Some("not_found".to_string()));
+    };
+    let current_attempt = RedisBackend::get_current_attempt(run_id, task_id, pool.clone()).await?;
+    if attempt == current_attempt && matches!(status, TaskStatus::Pending | TaskStatus::Running) {
+        return Ok(None);
+    }
+
+    if attempt <= current_attempt {
+        if let Some(result) = RedisBackend::get_all_results(run_id, task_id, pool)
+            .await?
+            .iter()
+            .find(|r| r.attempt == attempt)
+        {
+            return Ok(Some(
+                if result.success { "success" } else { "failure" }.to_string(),
+            ));
+        }
+    }
+
+    let Ok(task) = backend.get_task_by_id(run_id, task_id) else {
+        return Ok(Some("not_found".to_string()));
+    };
+    if !task.options.is_sensor && attempt > task.options.max_attempts.max(1) {
+        return Ok(Some("not_found".to_string()));
+    }
+    Ok(match status {
+        TaskStatus::Skipped => Some("skipped".to_string()),
+        TaskStatus::Success | TaskStatus::Failure => Some("not_found".to_string()),
+        _ => None,
+    })
+}
+
+async fn poll_task_log(
+    run_id: usize,
+    task_id: usize,
+    attempt: usize,
+    offset: usize,
+    pool: Pool,
+) -> anyhow::Result<(Option<String>, Vec<LogLine>)> {
+    // checked before reading so lines written before the end are not missed
+    let end_status = get_attempt_end_status(run_id, task_id, attempt, pool.clone()).await?;
+    let lines = RedisBackend::dummy(pool).get_log_lines(run_id, task_id, attempt, offset)?;
+    Ok((end_status, lines))
+}
+
+// sends each log line as a JSON event with its index as id and a final 'end' event with the
+// status of the attempt, or 'timeout' after LOG_STREAM_IDLE_TIMEOUT seconds without lines,
+// clients resume with '?offset=' or the Last-Event-ID header and may filter lines like
+// `get_task_log`
+pub async fn stream_task_log(
+    Path((run_id, task_id, attempt)): Path<(usize, usize, usize)>,
+    Query(query): Query<LogStreamQuery>,
+    Query(filter): Query<LogFilter>,
+    headers: HeaderMap,
+    State(pool): State<Pool>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let offset = query.offset.unwrap_or_else(|| {
+        headers
+            .get("last-event-id")
+            .and_then(|id| id.to_str().ok()?.parse::<usize>().ok())
+            .map_or(0, |id| id + 1)
+    });
+    let idle_timeout = Duration::from_secs(
+        get_log_stream_idle_timeout().unwrap_or(DEFAULT_LOG_STREAM_IDLE_TIMEOUT),
+    );
+
+    let events = stream::unfold(Some(offset), move |offset| {
+        let pool = pool.clone();
+        let filter = filter.clone();
+        async move {
+            let mut offset = offset?;
+            let idle_since = Instant::now();
+            loop {
+                let (end_status, lines) =
+                    match poll_task_log(run_id, task_id, attempt, offset, pool.clone()).await {
+                        Ok(polled) => polled,
+                        Err(e) => {
+                            let event = Event::default().event("error").data(e.to_string());
+                            return Some((vec![event], None));
+                        }
+                    };
+                if lines.is_empty() && end_status.is_none() {
+                    // e.g. a task that never runs because its trigger rule is not met
+                    if idle_since.elapsed() > idle_timeout {
+                        let event = Event::default().event("end").data("timeout");
+                        return Some((vec![event], None));
+                    }
+                    tokio::time::sleep(LOG_STREAM_POLL_INTERVAL).await;
+                    continue;
+                }
+
+                let mut events: Vec<Event> = lines
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, line)| filter.matches(line))
+                    .map(|(i, line)| {
+                        Event::default()
+                            .id((offset + i).to_string())
+                            .data(serde_json::to_string(line).unwrap())
+                    })
+                    .collect();
+                offset += lines.len();
+
+                return match end_status {
+                    Some(end_status) => {
+                        events.push(Event::default().event("end").data(end_status));
+                        Some((events, None))
+                    }
+                    None => Some((events, Some(offset))),
+                };
+            }
+        }
+    })
+    .flat_map(|events| stream::iter(events.into_iter().map(Ok)));
+
+    Sse::new(events).keep_alive(KeepAlive::default())
 }
 
 pub async fn get_pipelines(State(pool): State<Pool>) -> ServerResult<Json<Value>> {
@@ -352,13 +615,128 @@ pub async fn assert_pipeline_exists(pipeline_name: &str, pool: Pool) -> ServerRe
 
 pub async fn trigger(
     Path(pipeline_name): Path<String>,
+    ConnectInfo(source): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     State(pool): State<Pool>,
 ) -> ServerResult<Json<usize>> {
-    assert_pipeline_exists(&pipeline_name, pool.clone()).await?;
+    _trigger(
+        &pipeline_name,
+        None,
+        None,
+        get_actor(source, &headers),
+        pool,
+    )
+    .await
+}
+
+pub async fn trigger_params(
+    Path(pipeline_name): Path<String>,
+    ConnectInfo(source): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    State(pool): State<Pool>,
+    extract::Json(params): extract::Json<Value>,
+) -> ServerResult<Json<usize>> {
+    _trigger(
+        &pipeline_name,
+        None,
+        Some(params),
+        get_actor(source, &headers),
+        pool,
+    )
+    .await
+}
+
+pub async fn trigger_version(
+    Path((pipeline_name, pipeline_hash)): Path<(String, String)>,
+    ConnectInfo(source): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    State(pool): State<Pool>,
+) -> ServerResult<Json<usize>> {
+    _trigger(
+        &pipeline_name,
+        Some(pipeline_hash),
+        None,
+        get_actor(source, &headers),
+        pool,
+    )
+    .await
+}
+
+pub async fn trigger_version_params(
+    Path((pipeline_name, pipeline_hash)): Path<(String, String)>,
+    ConnectInfo(source): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    State(pool): State<Pool>,
+    extract::Json(params): extract::Json<Value>,
+) -> ServerResult<Json<usize>> {
+    _trigger(
+        &pipeline_name,
+        Some(pipeline_hash),
+        Some(params),
+        get_actor(source, &headers),
+        pool,
+    )
+    .await
+}
+
+// events record where the request came from, the X-Actor header only as the claimed actor
+// since the caller can set it to anything
+fn get_actor(source: SocketAddr, headers: &HeaderMap) -> Actor {
+    Actor {
+        actor: format!("api@{}", source.ip()),
+        claimed_actor: headers
+            .get("x-actor")
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string()),
+    }
+}
+
+struct Actor {
+    actor: String,
+    claimed_actor: Option<String>,
+}
+
+async fn _trigger(
+    pipeline_name: &str,
+    pipeline_hash: Option<String>,
+    params: Option<Value>,
+    Actor {
+        actor,
+        claimed_actor,
+    }: Actor,
+    pool: Pool,
+) -> ServerResult<Json<usize>> {
+    assert_pipeline_exists(pipeline_name, pool.clone()).await?;
 
     let scheduled_date = Utc::now();
-    let mut backend = RedisBackend::from(&pipeline_name, pool.clone());
-    let run = backend.create_new_run(scheduled_date).map_err(|e| {
+    let mut backend = RedisBackend::from(pipeline_name, pool.clone())
+        .with_actor(&actor)
+        .with_claimed_actor(claimed_actor.as_deref());
+    let pipeline_hash = match pipeline_hash {
+        Some(pipeline_hash) => {
+            RedisBackend::get_pipeline_version(pipeline_name, &pipeline_hash, pool.clone())
+                .await
+                .map_err(|e| service_err(format!("{:?}", e)))?;
+            pipeline_hash
+        }
+        None => backend.get_current_pipeline_hash().map_err(|e| {
+            service_err(format!(
+                "could not get current version for pipeline '{}'\n{:?}",
+                pipeline_name, e
+            ))
+        })?,
+    };
+    // the run's trace lasts until all of its initial tasks are enqueued
+    let cx = Context::current_with_span(get_tracer().start("create_run"));
+    cx.span().set_attributes([
+        KeyValue::new("pipeline", pipeline_name.to_string()),
+        KeyValue::new("actor", actor.clone()),
+    ]);
+    let run = {
+        let _guard = cx.clone().attach();
+        record_error(&cx, backend.create_new_run(scheduled_date, &pipeline_hash))
+    }
+    .map_err(|e| {
         service_err(format!(
             "could not create new run for pipeline '{}'\n{:?}",
             pipeline_name, e
@@ -366,31 +744,87 @@ pub async fn trigger(
     })?;
     let run_id = run.run_id;
 
-    tokio::spawn(async move { backend.enqueue_run(&run, None) });
+    let reason = format!(
+        "triggered version '{pipeline_hash}'{}",
+        if params.is_some() { " with params" } else { "" }
+    );
+    backend
+        .append_run_event(
+            run_id,
+            &RunEvent::new(
+                &actor,
+                None,
+                Some("Created".into()),
+                "Triggered",
+                Some(&reason),
+            ),
+        )
+        .map_err(|e| {
+            service_err(format!(
+                "could not record trigger of run '{}'\n{:?}",
+                run_id, e
+            ))
+        })?;
+
+    cx.span()
+        .set_attribute(KeyValue::new("run_id", run_id as i64));
+    tokio::spawn(async move {
+        let _guard = cx.clone().attach();
+        let enqueued = record_error(&cx, backend.enqueue_run(&run, params));
+        cx.span().end();
+        enqueued
+    });
 
     Ok(run_id.into())
 }
 
-pub async fn trigger_params(
+pub async fn get_pipeline_versions(
     Path(pipeline_name): Path<String>,
     State(pool): State<Pool>,
-    extract::Json(params): extract::Json<Value>,
-) -> ServerResult<Json<usize>> {
+) -> ServerResult<Json<Vec<PipelineVersion>>> {
     assert_pipeline_exists(&pipeline_name, pool.clone()).await?;
 
-    let scheduled_date = Utc::now();
-    let mut backend = RedisBackend::from(&pipeline_name, pool.clone());
-    let run = backend.create_new_run(scheduled_date).map_err(|e| {
-        service_err(format!(
-            "could not create new run for pipeline '{}'\n{:?}",
-            pipeline_name, e
-        ))
-    })?;
-    let run_id = run.run_id;
+    Ok(Json(
+        RedisBackend::get_pipeline_versions(&pipeline_name, pool)
+            .await
+            .map_err(|e| {
+                service_err(format!(
+                    "could not get versions for pipeline '{}'\n{:?}",
+                    pipeline_name, e
+                ))
+            })?,
+    ))
+}
 
-    tokio::spawn(async move { backend.enqueue_run(&run, Some(params)) });
+pub async fn get_pipeline_version(
+    Path((pipeline_name, pipeline_hash)): Path<(String, String)>,
+    State(pool): State<Pool>,
+) -> ServerResult<Json<Pipeline>> {
+    assert_pipeline_exists(&pipeline_name, pool.clone()).await?;
 
-    Ok(run_id.into())
+    Ok(Json(
+        RedisBackend::get_pipeline_version(&pipeline_name, &pipeline_hash, pool)
+            .await
+            .map_err(|e| service_err(format!("{:?}", e)))?,
+    ))
+}
+
+pub async fn get_pipeline_versions_diff(
+    Path((pipeline_name, old_hash, new_hash)): Path<(String, String, String)>,
+    State(pool): State<Pool>,
+) -> ServerResult<Json<PipelineDiff>> {
+    assert_pipeline_exists(&pipeline_name, pool.clone()).await?;
+
+    let old = RedisBackend::get_pipeline_version(&pipeline_name, &old_hash, pool.clone())
+        .await
+        .map_err(|e| service_err(format!("{:?}", e)))?;
+    let new = RedisBackend::get_pipeline_version(&pipeline_name, &new_hash, pool)
+        .await
+        .map_err(|e| service_err(format!("{:?}", e)))?;
+
+    Ok(Json(
+        diff_pipelines(&old, &new).map_err(|e| service_err(format!("{:?}", e)))?,
+    ))
 }
 
 pub async fn upload_pipeline(
@@ -412,3 +846,243 @@ pub async fn upload_pipeline(
         })?;
     Ok("ok".to_string())
 }
+
+fn get_variables_backend(scope: VariableScope, pipeline_name: &str, pool: Pool) -> RedisBackend {
+    match scope {
+        VariableScope::Pipeline => RedisBackend::from(pipeline_name, pool),
+        VariableScope::Global | VariableScope::Run(_) => RedisBackend::dummy(pool),
+    }
+}
+
+fn _get_variables(
+    scope: VariableScope,
+    pipeline_name: &str,
+    pool: Pool,
+) -> ServerResult<Json<HashMap<String, Value>>> {
+    Ok(Json(
+        get_variables_backend(scope, pipeline_name, pool)
+            .get_variables(scope)
+            .map_err(|e| service_err(format!("could not get variables\n{:?}", e)))?,
+    ))
+}
+
+fn _get_variable(
+    scope: VariableScope,
+    pipeline_name: &str,
+    key: &str,
+    pool: Pool,
+) -> ServerResult<Json<Value>> {
+    match get_variables_backend(scope, pipeline_name, pool)
+        .get_variable(scope, key)
+        .map_err(|e| service_err(format!("could not get variable '{}'\n{:?}", key, e)))?
+    {
+        Some(value) => Ok(Json(value)),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            format!("could not find variable '{}'", key),
+        )),
+    }
+}
+
+fn _set_variable(
+    scope: VariableScope,
+    pipeline_name: &str,
+    key: &str,
+    value: &Value,
+    pool: Pool,
+) -> ServerResult<String> {
+    get_variables_backend(scope, pipeline_name, pool)
+        .set_variable(scope, key, value)
+        .map_err(|e| service_err(format!("could not set variable '{}'\n{:?}", key, e)))?;
+    Ok("ok".to_string())
+}
+
+fn _delete_variable(
+    scope: VariableScope,
+    pipeline_name: &str,
+    key: &str,
+    pool: Pool,
+) -> ServerResult<String> {
+    get_variables_backend(scope, pipeline_name, pool)
+        .delete_variable(scope, key)
+        .map_err(|e| service_err(format!("could not delete variable '{}'\n{:?}", key, e)))?;
+    Ok("ok".to_string())
+}
+
+pub async fn get_variables(
+    Path(pipeline_name): Path<String>,
+    State(pool): State<Pool>,
+) -> ServerResult<Json<HashMap<String, Value>>> {
+    assert_pipeline_exists(&pipeline_name, pool.clone()).await?;
+    _get_variables(VariableScope::Pipeline, &pipeline_name, pool)
+}
+
+pub async fn get_variable(
+    Path((pipeline_name, key)): Path<(String, String)>,
+    State(pool): State<Pool>,
+) -> ServerResult<Json<Value>> {
+    assert_pipeline_exists(&pipeline_name, pool.clone()).await?;
+    _get_variable(VariableScope::Pipeline, &pipeline_name, &key, pool)
+}
+
+pub async fn set_variable(
+    Path((pipeline_name, key)): Path<(String, String)>,
+    State(pool): State<Pool>,
+    extract::Json(value): extract::Json<Value>,
+) -> ServerResult<String> {
+    assert_pipeline_exists(&pipeline_name, pool.clone()).await?;
+    _set_variable(VariableScope::Pipeline, &pipeline_name, &key, &value, pool)
+}
+
+pub async fn delete_variable(
+    Path((pipeline_name, key)): Path<(String, String)>,
+    State(pool): State<Pool>,
+) -> ServerResult<String> {
+    assert_pipeline_exists(&pipeline_name, pool.clone()).await?;
+    _delete_variable(VariableScope::Pipeline, &pipeline_name, &key, pool)
+}
+
+pub async fn get_global_variables(
+    State(pool): State<Pool>,
+) -> ServerResult<Json<HashMap<String, Value>>> {
+    _get_variables(VariableScope::Global, "", pool)
+}
+
+pub async fn get_global_variable(
+    Path(key): Path<String>,
+    State(pool): State<Pool>,
+) -> ServerResult<Json<Value>> {
+    _get_variable(VariableScope::Global, "", &key, pool)
+}
+
+pub async fn set_global_variable(
+    Path(key): Path<String>,
+    State(pool): State<Pool>,
+    extract::Json(value): extract::Json<Value>,
+) -> ServerResult<String> {
+    _set_variable(VariableScope::Global, "", &key, &value, pool)
+}
+
+pub async fn delete_global_variable(
+    Path(key): Path<String>,
+    State(pool): State<Pool>,
+) -> ServerResult<String> {
+    _delete_variable(VariableScope::Global, "", &key, pool)
+}
+
+pub async fn get_run_variables(
+    Path(run_id): Path<usize>,
+    State(pool): State<Pool>,
+) -> ServerResult<Json<HashMap<String, Value>>> {
+    _get_variables(VariableScope::Run(run_id), "", pool)
+}
+
+pub async fn get_run_variable(
+    Path((run_id, key)): Path<(usize, String)>,
+    State(pool): State<Pool>,
+) -> ServerResult<Json<Value>> {
+    _get_variable(VariableScope::Run(run_id), "", &key, pool)
+}
+
+pub async fn set_run_variable(
+    Path((run_id, key)): Path<(usize, String)>,
+    State(pool): State<Pool>,
+    extract::Json(value): extract::Json<Value>,
+) -> ServerResult<String> {
+    _set_variable(VariableScope::Run(run_id), "", &key, &value, pool)
+}
+
+pub async fn delete_run_variable(
+    Path((run_id, key)): Path<(usize, String)>,
+    State(pool): State<Pool>,
+) -> ServerResult<String> {
+    _delete_variable(VariableScope::Run(run_id), "", &key, pool)
+}
+
+// secret values are write-only, only their names can be read back
+pub async fn get_secret_names(State(pool): State<Pool>) -> ServerResult<Json<Vec<String>>> {
+    Ok(Json(RedisBackend::dummy(pool).get_secret_names().map_err(
+        |e| service_err(format!("could not get secrets\n{:?}", e)),
+    )?))
+}
+
+pub async fn set_secret(
+    Path(name): Path<String>,
+    State(pool): State<Pool>,
+    extract::Json(value): extract::Json<String>,
+) -> ServerResult<String> {
+    RedisBackend::dummy(pool)
+        .set_secret(&name, &value)
+        .map_err(|e| service_err(format!("could not set secret '{}'\n{:?}", name, e)))?;
+    Ok("ok".to_string())
+}
+
+pub async fn delete_secret(
+    Path(name): Path<String>,
+    State(pool): State<Pool>,
+) -> ServerResult<String> {
+    RedisBackend::dummy(pool)
+        .delete_secret(&name)
+        .map_err(|e| service_err(format!("could not delete secret '{}'\n{:?}", name, e)))?;
+    Ok("ok".to_string())
+}
+
+pub async fn get_connection_ids(State(pool): State<Pool>) -> ServerResult<Json<Vec<String>>> {
+    Ok(Json(
+        RedisBackend::dummy(pool)
+            .get_connection_ids()
+            .map_err(|e| service_err(format!("could not get connections\n{:?}", e)))?,
+    ))
+}
+
+// like secrets, connection passwords are never sent back
+pub async fn get_connection(
+    Path(id): Path<String>,
+    State(pool): State<Pool>,
+) -> ServerResult<Json<Connection>> {
+    match RedisBackend::dummy(pool)
+        .get_connection(&id)
+        .map_err(|e| service_err(format!("could not get connection '{}'\n{:?}", id, e)))?
+    {
+        Some(mut connection) => {
+            if !connection.password.is_empty() {
+                connection.password = REDACTED.to_string();
+            }
+            Ok(Json(connection))
+        }
+        None => Err((
+            StatusCode::NOT_FOUND,
+            format!("could not find connection '{}'", id),
+        )),
+    }
+}
+
+pub async fn set_connection(
+    Path(id): Path<String>,
+    State(pool): State<Pool>,
+    extract::Json(mut connection): extract::Json<Connection>,
+) -> ServerResult<String> {
+    let mut backend = RedisBackend::dummy(pool);
+    // a connection read back from `get_connection` keeps its stored password
+    if connection.password == REDACTED {
+        connection.password = backend
+            .get_connection(&id)
+            .map_err(|e| service_err(format!("could not get connection '{}'\n{:?}", id, e)))?
+            .map(|stored| stored.password)
+            .unwrap_or_default();
+    }
+    backend
+        .set_connection(&id, &connection)
+        .map_err(|e| service_err(format!("could not set connection '{}'\n{:?}", id, e)))?;
+    Ok("ok".to_string())
+}
+
+pub async fn delete_connection(
+    Path(id): Path<String>,
+    State(pool): State<Pool>,
+) -> ServerResult<String> {
+    RedisBackend::dummy(pool)
+        .delete_connection(&id)
+        .map_err(|e| service_err(format!("could not delete connection '{}'\n{:?}", id, e)))?;
+    Ok("ok".to_string())
+}
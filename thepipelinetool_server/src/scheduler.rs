@@ -3,8 +3,16 @@ use std::{collections::HashSet, sync::Arc, time::Duration};
 use chrono::{DateTime, Utc};
 
 use deadpool_redis::Pool;
+use opentelemetry::{
+    trace::{TraceContextExt, Tracer},
+    KeyValue,
+};
 use saffron::{Cron, CronTimesIter};
-use thepipelinetool_runner::{backend::Backend, blanket_backend::BlanketBackend};
+use thepipelinetool_runner::{
+    backend::Backend,
+    blanket_backend::BlanketBackend,
+    telemetry::{get_tracer, record_error},
+};
 use tokio::{sync::Mutex, time::sleep};
 
 use anyhow::Result;
@@ -103,9 +111,21 @@ pub async fn _scheduler(
             continue;
         }
 
-        let mut backend = RedisBackend::from(pipeline_name, pool.clone());
-        let run = backend.create_new_run(scheduled_date)?;
-        backend.enqueue_run(&run, None)?;
+        let mut backend = RedisBackend::from(pipeline_name, pool.clone()).with_actor("scheduler");
+        let pipeline_hash = backend.get_current_pipeline_hash()?;
+        let lag = (Utc::now() - scheduled_date).num_milliseconds() as f64 / 1000.0;
+        get_tracer().in_span("create_run", |cx| {
+            cx.span().set_attributes([
+                KeyValue::new("pipeline", pipeline_name.to_string()),
+                KeyValue::new("actor", backend.get_actor()),
+                KeyValue::new("scheduled_date", scheduled_date.to_rfc3339()),
+            ]);
+            let run = record_error(&cx, backend.create_new_run(scheduled_date, &pipeline_hash))?;
+            cx.span()
+                .set_attribute(KeyValue::new("run_id", run.run_id as i64));
+            backend.enqueue_run(&run, None)
+        })?;
+        RedisBackend::set_scheduler_lag(pipeline_name, lag, pool.clone()).await?;
         println!(
             "scheduling catchup {pipeline_name} {}",
             scheduled_date.format("%F %R")
@@ -0,0 +1,478 @@
+use std::{collections::HashMap, fs, io::Read, path::PathBuf};
+
+use aes_gcm::{
+    aead::{consts::U32, Aead, KeyInit, Payload},
+    aes::Aes256,
+    AesGcm, Nonce,
+};
+use age::armor::ArmoredReader;
+use anyhow::{anyhow, Result};
+use base64::prelude::*;
+use deadpool_redis::{redis::cmd, Pool};
+use serde_json::Value;
+
+use crate::secrets::{decrypt_secret, decrypt_secret_with_key, encrypt_secret};
+
+const SECRETS_KEY: &str = "s";
+
+/// Source of the secrets referenced as `{{ secret.name }}`, see `get_secret_provider`.
+///
+/// Only secrets stored in redis can be changed through the server routes, secrets of other
+/// providers are managed outside of tpt.
+pub trait SecretProvider: Send + Sync {
+    fn get_secret_names(&self) -> Result<Vec<String>>;
+    fn get_secret(&self, name: &str) -> Result<Option<String>>;
+
+    fn set_secret(&self, _name: &str, _value: &str) -> Result<()> {
+        Err(anyhow!(
+            "secrets are read-only when SECRET_PROVIDER is set to another provider than redis"
+        ))
+    }
+
+    fn delete_secret(&self, _name: &str) -> Result<()> {
+        Err(anyhow!(
+            "secrets are read-only when SECRET_PROVIDER is set to another provider than redis"
+        ))
+    }
+}
+
+/// Keeps secrets in a redis hash, encrypted with `SECRET_KEY`.
+pub struct RedisSecretProvider {
+    pub pool: Pool,
+}
+
+impl RedisSecretProvider {
+    // backends are called from blocking code running on the tokio runtime
+    fn block_on<T>(&self, f: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+        tokio::task::block_in_place(|| tokio::runtime::Handle::current().block_on(f))
+    }
+}
+
+impl SecretProvider for RedisSecretProvider {
+    fn get_secret_names(&self) -> Result<Vec<String>> {
+        self.block_on(async {
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            Ok(cmd("HKEYS")
+                .arg(SECRETS_KEY)
+                .query_async::<_, Vec<String>>(&mut conn)
+                .await?)
+        })
+    }
+
+    fn get_secret(&self, name: &str) -> Result<Option<String>> {
+        self.block_on(async {
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            let encrypted: Option<String> = cmd("HGET")
+                .arg(SECRETS_KEY)
+                .arg(name)
+                .query_async(&mut conn)
+                .await?;
+
+            encrypted
+                .map(|encrypted| decrypt_secret(&encrypted))
+                .transpose()
+        })
+    }
+
+    fn set_secret(&self, name: &str, value: &str) -> Result<()> {
+        self.block_on(async {
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            cmd("HSET")
+                .arg(SECRETS_KEY)
+                .arg(name)
+                .arg(encrypt_secret(value)?)
+                .query_async::<_, ()>(&mut conn)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn delete_secret(&self, name: &str) -> Result<()> {
+        self.block_on(async {
+            let mut conn = self.pool.get().await.expect("DB connection failed");
+            cmd("HDEL")
+                .arg(SECRETS_KEY)
+                .arg(name)
+                .query_async::<_, ()>(&mut conn)
+                .await?;
+            Ok(())
+        })
+    }
+}
+
+fn parse_dotenv(contents: &str) -> HashMap<String, String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (key, value) = line.trim_start_matches("export ").split_once('=')?;
+            let value = value.trim();
+            let value = match (value.chars().next(), value.chars().last()) {
+                (Some('"'), Some('"')) | (Some('\''), Some('\'')) if value.len() > 1 => {
+                    &value[1..value.len() - 1]
+                }
+                _ => value,
+            };
+            Some((key.trim().to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Reads `KEY=value` lines from a dotenv file, the file is re-read on every lookup.
+pub struct DotenvSecretProvider {
+    pub path: PathBuf,
+}
+
+impl DotenvSecretProvider {
+    fn read(&self) -> Result<HashMap<String, String>> {
+        Ok(parse_dotenv(&fs::read_to_string(&self.path)?))
+    }
+}
+
+impl SecretProvider for DotenvSecretProvider {
+    fn get_secret_names(&self) -> Result<Vec<String>> {
+        Ok(self.read()?.into_keys().collect())
+    }
+
+    fn get_secret(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.read()?.remove(name))
+    }
+}
+
+/// Reads a JSON or YAML file mapping secret names to `ENC[<base64>]` values, as printed by the
+/// `encrypt_secret` binary. The base64 holds a random 12 byte nonce followed by the AES-256-GCM
+/// ciphertext under `key`, the same scheme secrets stored in redis use.
+///
+/// This is tpt's own format, see `SopsSecretProvider` for files encrypted by sops.
+pub struct EncryptedFileSecretProvider {
+    pub path: PathBuf,
+    pub key: Vec<u8>,
+}
+
+impl EncryptedFileSecretProvider {
+    fn read(&self) -> Result<HashMap<String, String>> {
+        Ok(serde_yaml::from_str(&fs::read_to_string(&self.path)?)?)
+    }
+}
+
+impl SecretProvider for EncryptedFileSecretProvider {
+    fn get_secret_names(&self) -> Result<Vec<String>> {
+        Ok(self.read()?.into_keys().collect())
+    }
+
+    fn get_secret(&self, name: &str) -> Result<Option<String>> {
+        self.read()?
+            .get(name)
+            .map(
+                |value| match value.strip_prefix("ENC[").and_then(|v| v.strip_suffix(']')) {
+                    Some(encrypted) => decrypt_secret_with_key(&self.key, encrypted),
+                    None => Err(anyhow!("secret '{name}' is not encrypted")),
+                },
+            )
+            .transpose()
+    }
+}
+
+// sops encrypts every value as `ENC[AES256_GCM,data:<base64>,iv:<base64>,tag:<base64>,type:str]`
+// with the path of its key as additional data, i.e. `name:` for top-level keys
+fn decrypt_sops_value(data_key: &[u8], name: &str, value: &str) -> Result<String> {
+    let Some(fields) = value
+        .strip_prefix("ENC[AES256_GCM,")
+        .and_then(|v| v.strip_suffix(']'))
+    else {
+        // values of keys matching sops' unencrypted_suffix are stored as is
+        return Ok(value.to_string());
+    };
+    let fields: HashMap<&str, &str> = fields
+        .split(',')
+        .filter_map(|f| f.split_once(':'))
+        .collect();
+    let field = |field: &str| -> Result<Vec<u8>> {
+        let value = fields
+            .get(field)
+            .ok_or_else(|| anyhow!("sops value of secret '{name}' has no {field}"))?;
+        Ok(BASE64_STANDARD.decode(value)?)
+    };
+    if fields.get("type") != Some(&"str") {
+        return Err(anyhow!("secret '{name}' is not a string"));
+    }
+    let iv = field("iv")?;
+    if iv.len() != 32 {
+        return Err(anyhow!("sops value of secret '{name}' has an invalid iv"));
+    }
+
+    let value = AesGcm::<Aes256, U32>::new_from_slice(data_key)
+        .map_err(|_| anyhow!("sops data key must be 32 bytes"))?
+        .decrypt(
+            Nonce::from_slice(&iv),
+            Payload {
+                msg: &[field("data")?, field("tag")?].concat(),
+                aad: format!("{name}:").as_bytes(),
+            },
+        )
+        .map_err(|_| anyhow!("could not decrypt secret '{name}'"))?;
+
+    Ok(String::from_utf8(value)?)
+}
+
+/// Reads a JSON or YAML file encrypted by sops for an age recipient, e.g.
+/// `sops -e --age <recipient> secrets.yaml`. The data key is decrypted with the identities in
+/// `identity_file` and only top-level values are read, the sops MAC is not verified.
+pub struct SopsSecretProvider {
+    pub path: PathBuf,
+    pub identity_file: PathBuf,
+}
+
+impl SopsSecretProvider {
+    fn decrypt_data_key(&self, metadata: &serde_yaml::Value) -> Result<Vec<u8>> {
+        let identities =
+            age::IdentityFile::from_file(self.identity_file.to_string_lossy().into_owned())?
+                .into_identities()?;
+
+        for recipient in metadata["age"].as_sequence().into_iter().flatten() {
+            let Some(enc) = recipient["enc"].as_str() else {
+                continue;
+            };
+            let age::Decryptor::Recipients(decryptor) =
+                age::Decryptor::new(ArmoredReader::new(enc.as_bytes()))?
+            else {
+                continue;
+            };
+            if let Ok(mut reader) =
+                decryptor.decrypt(identities.iter().map(|i| i.as_ref() as &dyn age::Identity))
+            {
+                let mut data_key = vec![];
+                reader.read_to_end(&mut data_key)?;
+                return Ok(data_key);
+            }
+        }
+
+        Err(anyhow!(
+            "none of the identities in '{}' can decrypt '{}'",
+            self.identity_file.display(),
+            self.path.display()
+        ))
+    }
+
+    fn read(&self) -> Result<HashMap<String, String>> {
+        let mut file: HashMap<String, serde_yaml::Value> =
+            serde_yaml::from_str(&fs::read_to_string(&self.path)?)?;
+        let metadata = file
+            .remove("sops")
+            .ok_or_else(|| anyhow!("'{}' is not encrypted by sops", self.path.display()))?;
+        let data_key = self.decrypt_data_key(&metadata)?;
+
+        file.into_iter()
+            .map(|(name, value)| {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| anyhow!("secret '{name}' is not a string"))?;
+                Ok((name.clone(), decrypt_sops_value(&data_key, &name, value)?))
+            })
+            .collect()
+    }
+}
+
+impl SecretProvider for SopsSecretProvider {
+    fn get_secret_names(&self) -> Result<Vec<String>> {
+        Ok(self.read()?.into_keys().collect())
+    }
+
+    fn get_secret(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.read()?.remove(name))
+    }
+}
+
+/// Reads the keys of a single secret from a Vault KV v2 engine,
+/// i.e. `GET {address}/v1/{mount}/data/{path}`.
+pub struct VaultSecretProvider {
+    pub address: String,
+    pub token: String,
+    pub mount: String,
+    pub path: String,
+}
+
+impl VaultSecretProvider {
+    fn read(&self) -> Result<HashMap<String, String>> {
+        let url = format!(
+            "{}/v1/{}/data/{}",
+            self.address.trim_end_matches('/'),
+            self.mount,
+            self.path
+        );
+
+        // backends are called from blocking code running on the tokio runtime
+        let res = tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(async {
+                let res = reqwest::Client::new()
+                    .get(&url)
+                    .header("X-Vault-Token", &self.token)
+                    .send()
+                    .await?;
+                if res.status() == reqwest::StatusCode::NOT_FOUND {
+                    return Ok(None);
+                }
+                if !res.status().is_success() {
+                    return Err(anyhow!(
+                        "could not read vault secret '{url}': {}",
+                        res.status()
+                    ));
+                }
+                Ok(Some(res.json::<Value>().await?))
+            })
+        })?;
+
+        Ok(match res {
+            Some(res) => res["data"]["data"]
+                .as_object()
+                .ok_or_else(|| anyhow!("unexpected vault response from '{url}'"))?
+                .iter()
+                .map(|(k, v)| match v {
+                    Value::String(v) => (k.clone(), v.clone()),
+                    v => (k.clone(), v.to_string()),
+                })
+                .collect(),
+            None => HashMap::new(),
+        })
+    }
+}
+
+impl SecretProvider for VaultSecretProvider {
+    fn get_secret_names(&self) -> Result<Vec<String>> {
+        Ok(self.read()?.into_keys().collect())
+    }
+
+    fn get_secret(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.read()?.remove(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use aes_gcm::aead::AeadCore;
+
+    use super::*;
+    use crate::secrets::encrypt_secret_with_key;
+
+    #[test]
+    fn test_parse_dotenv() {
+        let secrets = parse_dotenv("# comment\nA=1\nexport B = \"two\"\n\nC='3'\nD=");
+
+        assert_eq!(secrets["A"], "1");
+        assert_eq!(secrets["B"], "two");
+        assert_eq!(secrets["C"], "3");
+        assert_eq!(secrets["D"], "");
+    }
+
+    // answers a single request like a Vault KV v2 engine would
+    fn mock_vault(body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let len = stream.read(&mut request).unwrap();
+            let request = String::from_utf8_lossy(&request[..len]).to_lowercase();
+
+            let response = if request.starts_with("get /v1/secret/data/tpt ")
+                && request.contains("x-vault-token: token")
+            {
+                format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{body}",
+                    body.len()
+                )
+            } else {
+                "HTTP/1.1 403 Forbidden\r\ncontent-length: 0\r\n\r\n".to_string()
+            };
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+
+        address
+    }
+
+    #[test]
+    fn test_encrypted_file_secret_provider() {
+        let key = vec![7; 32];
+        let path = std::env::temp_dir().join(format!("tpt_secrets_{}.yaml", std::process::id()));
+        fs::write(
+            &path,
+            format!(
+                "db_password: ENC[{}]\nplain: hunter2\n",
+                encrypt_secret_with_key(&key, "hunter2").unwrap()
+            ),
+        )
+        .unwrap();
+        let provider = EncryptedFileSecretProvider {
+            path: path.clone(),
+            key,
+        };
+
+        assert_eq!(
+            provider.get_secret("db_password").unwrap(),
+            Some("hunter2".to_string())
+        );
+        assert!(provider.get_secret("plain").is_err());
+        assert_eq!(provider.get_secret("missing").unwrap(), None);
+        assert!(provider.set_secret("db_password", "changed").is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_decrypt_sops_value() {
+        let data_key = [3; 32];
+        let cipher = AesGcm::<Aes256, U32>::new_from_slice(&data_key).unwrap();
+        let iv = AesGcm::<Aes256, U32>::generate_nonce(&mut aes_gcm::aead::OsRng);
+        let encrypted = cipher
+            .encrypt(
+                &iv,
+                Payload {
+                    msg: b"hunter2",
+                    aad: b"db_password:",
+                },
+            )
+            .unwrap();
+        let (data, tag) = encrypted.split_at(encrypted.len() - 16);
+        let value = format!(
+            "ENC[AES256_GCM,data:{},iv:{},tag:{},type:str]",
+            BASE64_STANDARD.encode(data),
+            BASE64_STANDARD.encode(iv),
+            BASE64_STANDARD.encode(tag)
+        );
+
+        assert_eq!(
+            decrypt_sops_value(&data_key, "db_password", &value).unwrap(),
+            "hunter2"
+        );
+        assert!(decrypt_sops_value(&data_key, "other", &value).is_err());
+        assert!(decrypt_sops_value(&[4; 32], "db_password", &value).is_err());
+        assert_eq!(
+            decrypt_sops_value(&data_key, "plain_unencrypted", "hunter2").unwrap(),
+            "hunter2"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_vault_secret_provider() {
+        let provider = VaultSecretProvider {
+            address: mock_vault(
+                r#"{"data": {"data": {"db_password": "hunter2"}, "metadata": {}}}"#,
+            ),
+            token: "token".into(),
+            mount: "secret".into(),
+            path: "tpt".into(),
+        };
+
+        assert_eq!(
+            provider.get_secret("db_password").unwrap(),
+            Some("hunter2".to_string())
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use anyhow::{anyhow, Result};
+use base64::prelude::*;
+
+use crate::env::get_secret_key;
+
+const NONCE_LENGTH: usize = 12;
+
+fn get_cipher(key: &[u8]) -> Result<Aes256Gcm> {
+    Aes256Gcm::new_from_slice(key).map_err(|_| anyhow!("secret key must be 32 bytes"))
+}
+
+// stored as base64 of the random nonce followed by the ciphertext
+pub fn encrypt_secret_with_key(key: &[u8], value: &str) -> Result<String> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = get_cipher(key)?
+        .encrypt(&nonce, value.as_bytes())
+        .map_err(|_| anyhow!("could not encrypt secret"))?;
+
+    Ok(BASE64_STANDARD.encode([nonce.as_slice(), &ciphertext].concat()))
+}
+
+pub fn decrypt_secret_with_key(key: &[u8], encrypted: &str) -> Result<String> {
+    let bytes = BASE64_STANDARD.decode(encrypted)?;
+    if bytes.len() < NONCE_LENGTH {
+        return Err(anyhow!("invalid encrypted secret"));
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LENGTH);
+    let value = get_cipher(key)?
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("could not decrypt secret, was SECRET_KEY changed?"))?;
+
+    Ok(String::from_utf8(value)?)
+}
+
+pub fn encrypt_secret(value: &str) -> Result<String> {
+    encrypt_secret_with_key(&get_secret_key()?, value)
+}
+
+pub fn decrypt_secret(encrypted: &str) -> Result<String> {
+    decrypt_secret_with_key(&get_secret_key()?, encrypted)
+}
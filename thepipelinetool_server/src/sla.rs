@@ -0,0 +1,283 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use deadpool_redis::Pool;
+use serde::{Deserialize, Serialize};
+use thepipelinetool_core::dev::TempQueuedTask;
+use thepipelinetool_runner::{
+    backend::Backend,
+    blanket_backend::BlanketBackend,
+    notifications::{Notification, NotificationEvent},
+    run::RunEvent,
+};
+use tokio::time::sleep;
+
+use anyhow::Result;
+
+use crate::{
+    env::get_check_sla_loop_interval, redis_backend::RedisBackend, retention::is_run_done,
+};
+
+const SLA_MISS_STATE: &str = "SlaMiss";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SlaMiss {
+    pub pipeline_name: String,
+    pub run_id: usize,
+    pub scheduled_date_for_run: DateTime<Utc>,
+    // unset when the run itself missed its SLA
+    pub task_id: Option<usize>,
+    pub task_name: Option<String>,
+    pub attempt: Option<usize>,
+    pub deadline: DateTime<Utc>,
+    pub detected: DateTime<Utc>,
+}
+
+impl SlaMiss {
+    fn reason(&self) -> String {
+        match &self.task_name {
+            Some(task_name) => format!(
+                "task {task_name} still running after its expected duration, due at {}",
+                self.deadline
+            ),
+            None => format!("run not done by {}", self.deadline),
+        }
+    }
+}
+
+// `finished` is unset while the run is not done
+pub fn is_run_sla_missed(
+    deadline: DateTime<Utc>,
+    finished: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> bool {
+    now > deadline && finished.is_none_or(|finished| finished > deadline)
+}
+
+// whether a run past its deadline missed its SLA, unset when it is not reported: runs due
+// before the SLA was configured and runs that ended without recording their end
+pub fn judge_run_sla(
+    deadline: DateTime<Utc>,
+    sla_since: DateTime<Utc>,
+    finished: Option<DateTime<Utc>>,
+    is_done: bool,
+    now: DateTime<Utc>,
+) -> Option<bool> {
+    if deadline < sla_since || (finished.is_none() && is_done) {
+        return None;
+    }
+    Some(is_run_sla_missed(deadline, finished, now))
+}
+
+// records the miss as a run event and notifies, unless it was already recorded
+async fn record_sla_miss(sla_miss: SlaMiss, pool: Pool) -> Result<()> {
+    if !RedisBackend::record_sla_miss(&sla_miss, pool.clone()).await? {
+        return Ok(());
+    }
+    println!(
+        "SLA miss in run {} of {}: {}",
+        sla_miss.run_id,
+        sla_miss.pipeline_name,
+        sla_miss.reason()
+    );
+
+    let mut backend = RedisBackend::from(&sla_miss.pipeline_name, pool).with_actor("check_sla");
+    backend.append_run_event(
+        sla_miss.run_id,
+        &RunEvent::new(
+            &backend.get_actor(),
+            sla_miss.task_id,
+            None,
+            SLA_MISS_STATE,
+            Some(&sla_miss.reason()),
+        ),
+    )?;
+
+    let mut notification = Notification::new(
+        NotificationEvent::SlaMiss,
+        &sla_miss.pipeline_name,
+        sla_miss.run_id,
+        sla_miss.scheduled_date_for_run,
+    );
+    notification.task_id = sla_miss.task_id;
+    notification.task_name = sla_miss.task_name.clone();
+    notification.attempt = sla_miss.attempt;
+    notification.error = Some(sla_miss.reason());
+    let notifications = backend.get_options().await?.notifications;
+    backend.notify(&notifications, notification)
+}
+
+// attempts running longer than the expected duration of their task
+async fn check_task_sla(
+    temp_queued_task: &TempQueuedTask,
+    dummy: &RedisBackend,
+    now: DateTime<Utc>,
+    pool: Pool,
+) -> Result<()> {
+    let queued_task = &temp_queued_task.queued_task;
+    let task = dummy.get_task_by_id(queued_task.run_id, queued_task.task_id)?;
+    let Some(expected_duration) = task.options.expected_duration else {
+        return Ok(());
+    };
+    let deadline = temp_queued_task.popped_date + chrono::Duration::from_std(expected_duration)?;
+    if now > deadline {
+        record_sla_miss(
+            SlaMiss {
+                pipeline_name: queued_task.pipeline_name.clone(),
+                run_id: queued_task.run_id,
+                scheduled_date_for_run: queued_task.scheduled_date_for_run,
+                task_id: Some(task.id),
+                task_name: Some(task.name.clone()),
+                attempt: Some(queued_task.attempt),
+                deadline,
+                detected: now,
+            },
+            pool,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+// runs not done by the pipeline SLA, each run is settled once its deadline has passed
+async fn check_run_sla(
+    backend: &RedisBackend,
+    run_id: usize,
+    scheduled_date_for_run: DateTime<Utc>,
+    deadline: DateTime<Utc>,
+    sla_since: DateTime<Utc>,
+    now: DateTime<Utc>,
+    pool: Pool,
+) -> Result<()> {
+    let pipeline_name = backend.get_pipeline_name()?;
+    let finished = RedisBackend::get_run_end_date(run_id, pool.clone()).await?;
+    let is_done = finished.is_none() && is_run_done(backend, run_id)?;
+
+    if judge_run_sla(deadline, sla_since, finished, is_done, now) == Some(true) {
+        record_sla_miss(
+            SlaMiss {
+                pipeline_name: pipeline_name.clone(),
+                run_id,
+                scheduled_date_for_run,
+                task_id: None,
+                task_name: None,
+                attempt: None,
+                deadline,
+                detected: now,
+            },
+            pool.clone(),
+        )
+        .await?;
+    }
+    RedisBackend::settle_sla(&pipeline_name, run_id, pool).await
+}
+
+async fn check_pipeline_sla(pipeline_name: &str, now: DateTime<Utc>, pool: Pool) -> Result<()> {
+    let backend = RedisBackend::from(pipeline_name, pool.clone());
+    let Some(sla) = backend.get_options().await?.sla else {
+        return RedisBackend::delete_sla_since(pipeline_name, pool).await;
+    };
+    // runs due before the SLA was configured are not reported
+    let sla_since = RedisBackend::get_sla_since(pipeline_name, now, pool.clone()).await?;
+
+    // only runs whose deadline has passed
+    let sla = chrono::Duration::from_std(sla)?;
+    for (run_id, scheduled_date_for_run) in
+        RedisBackend::get_unsettled_sla_runs(pipeline_name, now - sla, pool.clone()).await?
+    {
+        let deadline = scheduled_date_for_run + sla;
+        if let Err(e) = check_run_sla(
+            &backend,
+            run_id,
+            scheduled_date_for_run,
+            deadline,
+            sla_since,
+            now,
+            pool.clone(),
+        )
+        .await
+        {
+            eprintln!("could not check the SLA of run {run_id} of {pipeline_name}: {e:?}");
+        }
+    }
+    Ok(())
+}
+
+// errors are logged per attempt, run and pipeline, so one bad entry doesn't stop the checks
+pub async fn check_sla(pool: Pool) -> Result<()> {
+    let dummy = RedisBackend::dummy(pool.clone());
+    let loop_interval = Duration::new(get_check_sla_loop_interval()?, 0);
+
+    loop {
+        let now = Utc::now();
+
+        match dummy.get_temp_queue().await {
+            Ok(temp_queue) => {
+                for temp_queued_task in &temp_queue {
+                    if let Err(e) =
+                        check_task_sla(temp_queued_task, &dummy, now, pool.clone()).await
+                    {
+                        eprintln!(
+                            "could not check the SLA of task {} of run {}: {e:?}",
+                            temp_queued_task.queued_task.task_id,
+                            temp_queued_task.queued_task.run_id
+                        );
+                    }
+                }
+            }
+            Err(e) => eprintln!("could not read the temp queue: {e:?}"),
+        }
+
+        match RedisBackend::get_pipelines(pool.clone()).await {
+            Ok(pipeline_names) => {
+                for pipeline_name in pipeline_names {
+                    if let Err(e) = check_pipeline_sla(&pipeline_name, now, pool.clone()).await {
+                        eprintln!("could not check the SLA of {pipeline_name}: {e:?}");
+                    }
+                }
+            }
+            Err(e) => eprintln!("could not read the pipelines: {e:?}"),
+        }
+
+        sleep(loop_interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_is_run_sla_missed() {
+        let deadline = Utc.with_ymd_and_hms(2024, 1, 1, 7, 0, 0).unwrap();
+        let before = Utc.with_ymd_and_hms(2024, 1, 1, 6, 0, 0).unwrap();
+        let after = Utc.with_ymd_and_hms(2024, 1, 1, 8, 0, 0).unwrap();
+
+        assert!(!is_run_sla_missed(deadline, None, before));
+        assert!(is_run_sla_missed(deadline, None, after));
+        assert!(!is_run_sla_missed(deadline, Some(before), after));
+        assert!(is_run_sla_missed(deadline, Some(after), after));
+    }
+
+    #[test]
+    fn test_judge_run_sla() {
+        let deadline = Utc.with_ymd_and_hms(2024, 1, 1, 7, 0, 0).unwrap();
+        let before = Utc.with_ymd_and_hms(2024, 1, 1, 6, 0, 0).unwrap();
+        let after = Utc.with_ymd_and_hms(2024, 1, 1, 8, 0, 0).unwrap();
+
+        assert_eq!(
+            judge_run_sla(deadline, before, None, false, after),
+            Some(true)
+        );
+        assert_eq!(
+            judge_run_sla(deadline, before, Some(before), true, after),
+            Some(false)
+        );
+        // the SLA was configured after the deadline
+        assert_eq!(judge_run_sla(deadline, after, None, false, after), None);
+        // done without an end date
+        assert_eq!(judge_run_sla(deadline, before, None, true, after), None);
+    }
+}
@@ -0,0 +1,107 @@
+use anyhow::Result;
+use opentelemetry::{global, KeyValue};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+
+use crate::env::get_otlp_endpoint;
+
+// exports spans as OTLP/HTTP protobuf to `{endpoint}/v1/traces`
+pub fn build_tracer_provider(service_name: &'static str, endpoint: &str) -> Result<TracerProvider> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()?;
+
+    Ok(TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new("service.name", service_name)]))
+        .build())
+}
+
+// installs the global tracer provider when OTEL_EXPORTER_OTLP_ENDPOINT is set, short-lived
+// processes need to shut the returned provider down to flush their spans
+pub fn init_telemetry(service_name: &'static str) -> Result<Option<TracerProvider>> {
+    let Some(endpoint) = get_otlp_endpoint() else {
+        return Ok(None);
+    };
+    let provider = build_tracer_provider(service_name, &endpoint)?;
+    global::set_tracer_provider(provider.clone());
+    Ok(Some(provider))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+        thread,
+    };
+
+    use opentelemetry::trace::{TraceContextExt, Tracer, TracerProvider as _};
+    use thepipelinetool_runner::telemetry::{context_from_traceparent, get_traceparent};
+
+    use super::*;
+
+    // stands in for an OTLP collector, sends back the request line and body of each export
+    fn mock_collector() -> (String, mpsc::Receiver<(String, Vec<u8>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut reader = BufReader::new(stream.unwrap());
+                let mut head = vec![];
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    head.push(line.trim().to_lowercase());
+                }
+                let content_length = head
+                    .iter()
+                    .find_map(|h| h.strip_prefix("content-length: "))
+                    .map_or(0, |l| l.parse().unwrap());
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                reader
+                    .into_inner()
+                    .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                    .unwrap();
+                let _ = tx.send((head[0].clone(), body));
+            }
+        });
+
+        (endpoint, rx)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_export_to_collector() {
+        let (endpoint, rx) = mock_collector();
+        let provider = build_tracer_provider("tpt_test", &endpoint).unwrap();
+        let tracer = provider.tracer("test");
+
+        // continues the trace of the process that enqueued the task
+        let parent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let parent_cx = context_from_traceparent(parent);
+        let cx = parent_cx.with_span(tracer.start_with_context("execute_task", &parent_cx));
+        let traceparent = get_traceparent(&cx).unwrap();
+        cx.span().end();
+        assert!(traceparent.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+        assert_ne!(traceparent, parent);
+
+        tokio::task::spawn_blocking(move || provider.shutdown())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let (request_line, body) = rx.try_recv().unwrap();
+        assert!(request_line.starts_with("post /v1/traces "));
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.contains("execute_task"));
+        assert!(body.contains("tpt_test"));
+    }
+}
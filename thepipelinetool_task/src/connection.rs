@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Named connection details, e.g. for a database, shared by all tasks that reference its id.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Connection {
+    #[serde(rename = "type")]
+    pub conn_type: String,
+    #[serde(default)]
+    pub host: String,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub login: String,
+    #[serde(default)]
+    pub password: String,
+    #[serde(default)]
+    pub extras: Value,
+}
@@ -4,13 +4,23 @@ use anyhow::Result;
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use task_context::TaskContext;
+use task_error::TaskError;
 use task_options::TaskOptions;
 use task_result::TaskResult;
-use thepipelinetool_utils::{spawn, value_from_file, value_to_file};
+use thepipelinetool_utils::{
+    spawn, value_from_file, value_to_file, RESULT_PATH_ENV, TASK_ERROR_KEY,
+};
 
 pub mod branch;
+pub mod connection;
+pub mod log_line;
 pub mod ordered_queued_task;
 pub mod queued_task;
+pub mod task_callback;
+pub mod task_context;
+pub mod task_error;
+pub mod task_kind;
 pub mod task_options;
 pub mod task_ref_inner;
 pub mod task_result;
@@ -18,23 +28,17 @@ pub mod task_status;
 pub mod temp_queued_task;
 pub mod trigger_rule;
 
-fn get_json_dir() -> String {
-    env::var("JSON_DIR")
-        .unwrap_or("./json/".to_string())
-        .to_string()
-}
-
-fn get_save_to_file() -> bool {
-    env::var("SAVE_TO_FILE")
-        .unwrap_or("false".to_string())
-        .to_string()
-        .parse::<bool>()
-        .unwrap()
+// args and results are exchanged through files in a directory of their own, created in JSON_DIR
+// to keep the results for inspection, the args are always removed since they may hold secrets
+fn get_json_dir() -> Option<PathBuf> {
+    env::var("JSON_DIR").ok().map(PathBuf::from)
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Task {
     pub id: usize,
+    #[serde(default)]
+    pub key: String,
     pub name: String,
     pub function: String,
     pub template_args: Value,
@@ -43,19 +47,37 @@ pub struct Task {
     pub is_dynamic: bool,
     pub is_branch: bool,
     pub use_trigger_params: bool,
+    #[serde(default)]
+    pub connections: Vec<String>,
 }
 
 impl Task {
+    // a task keyed by its name, calling a function of the same name with default options
+    pub fn dummy(id: usize, name: &str) -> Self {
+        Self {
+            id,
+            key: name.to_string(),
+            name: name.to_string(),
+            function: name.to_string(),
+            template_args: Value::Null,
+            options: TaskOptions::default(),
+            lazy_expand: false,
+            is_dynamic: false,
+            is_branch: false,
+            use_trigger_params: false,
+            connections: vec![],
+        }
+    }
+
     pub fn execute<P, D>(
         &self,
         resolved_args: &Value,
         attempt: usize,
         handle_stdout_log: Box<dyn Fn(String) -> Result<()> + Send>,
         handle_stderr_log: Box<dyn Fn(String) -> Result<()> + Send>,
-        take_last_stdout_line: Box<dyn Fn() -> Result<String> + Send>,
         pipeline_path: P,
         tpt_path: D,
-        run_id: usize,
+        context: &TaskContext,
     ) -> Result<TaskResult>
     where
         P: AsRef<OsStr>,
@@ -67,24 +89,24 @@ impl Task {
         let mut cmd = Command::new(tpt_path);
         cmd.arg(pipeline_path);
         cmd.args(["run", "function", &self.function]);
-        cmd.env("run_id", run_id.to_string());
+        context.set_env(&mut cmd);
 
-        let out_path: Option<PathBuf> = if get_save_to_file() {
-            let json_dir = get_json_dir();
-            let out_path: PathBuf = [&json_dir, &format!("{function_name}_{task_id}_out.json")]
-                .iter()
-                .collect();
-            let in_path: PathBuf = [&json_dir, &format!("{function_name}_{task_id}_in.json")]
-                .iter()
-                .collect();
-            fs::create_dir_all(&json_dir).unwrap();
-            value_to_file(resolved_args, &in_path);
-            cmd.args([&in_path, &out_path]);
-            Some(out_path)
-        } else {
-            cmd.arg(&resolved_args_str);
-            None
-        };
+        // the result is read from its own file, so the child's stdout only holds logs
+        let json_dir = get_json_dir();
+        if let Some(json_dir) = &json_dir {
+            fs::create_dir_all(json_dir)?;
+        }
+        let exchange_dir = tempfile::Builder::new()
+            .prefix(&format!(
+                "tpt_{}_{}_{task_id}_{}_{attempt}_",
+                context.pipeline_name, context.run_id, self.name
+            ))
+            .tempdir_in(json_dir.clone().unwrap_or_else(env::temp_dir))?;
+        let in_path = exchange_dir.path().join("in.json");
+        let out_path = exchange_dir.path().join("out.json");
+        value_to_file(resolved_args, &in_path);
+        cmd.args([&in_path, &out_path]);
+        cmd.env(RESULT_PATH_ENV, &out_path);
 
         if attempt > 1 {
             thread::sleep(self.options.retry_delay);
@@ -105,10 +127,31 @@ impl Task {
         let timed_out = code == Some(124);
         let end = Utc::now();
 
-        let result = match (success, get_save_to_file()) {
-            (true, true) => value_from_file(&out_path.unwrap()).unwrap(),
-            (true, false) => serde_json::from_str(&take_last_stdout_line().unwrap()).unwrap(),
-            (false, _) => Value::Null,
+        let result = value_from_file::<Value>(&out_path);
+        let _ = fs::remove_file(&in_path);
+        if json_dir.is_some() {
+            let _ = exchange_dir.keep();
+        }
+
+        let (success, result, error) = match (success, result) {
+            (true, Ok(result)) => (true, result, None),
+            // task functions returning Err report it in place of their result
+            (false, Ok(result)) => (
+                false,
+                Value::Null,
+                serde_json::from_value::<TaskError>(result[TASK_ERROR_KEY].clone()).ok(),
+            ),
+            // exiting successfully without writing a result fails the task
+            (true, Err(e)) => (
+                false,
+                Value::Null,
+                Some(TaskError {
+                    message: format!("could not read result from '{}': {e}", out_path.display()),
+                    kind: "missing_result".into(),
+                    data: None,
+                }),
+            ),
+            (false, Err(_)) => (false, Value::Null, None),
         };
 
         Ok(TaskResult {
@@ -128,6 +171,8 @@ impl Task {
             is_branch: self.is_branch,
             is_sensor: self.options.is_sensor,
             exit_code: code,
+            error,
+            result_ref: None,
         })
     }
 }
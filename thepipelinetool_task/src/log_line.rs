@@ -0,0 +1,182 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+impl Display for LogStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LogStream::Stdout => "stdout",
+            LogStream::Stderr => "stderr",
+        })
+    }
+}
+
+/// A single line written by a task, as stored by the backends.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LogLine {
+    pub timestamp: DateTime<Utc>,
+    pub stream: LogStream,
+    pub attempt: usize,
+    #[serde(default)]
+    pub level: Option<String>,
+    pub message: String,
+}
+
+impl LogLine {
+    // JSON lines such as those written by `TaskContext::log` carry their own level
+    pub fn new(stream: LogStream, attempt: usize, message: &str) -> Self {
+        let message = message.trim_end_matches('\n');
+        let level = serde_json::from_str::<Value>(message)
+            .ok()
+            .and_then(|v| Some(v.get("level")?.as_str()?.to_lowercase()));
+
+        Self {
+            timestamp: Utc::now(),
+            stream,
+            attempt,
+            level,
+            message: message.to_string(),
+        }
+    }
+
+    // logs stored before lines were structured only hold the text
+    pub fn parse(attempt: usize, stored: &str) -> Self {
+        serde_json::from_str(stored)
+            .unwrap_or_else(|_| Self::new(LogStream::Stdout, attempt, stored))
+    }
+}
+
+// plain-text rendering, e.g. `2024-01-01T00:00:00.000Z stderr info {"type":"log",...}`
+impl Display for LogLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}",
+            self.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+            self.stream
+        )?;
+        if let Some(level) = &self.level {
+            write!(f, " {level}")?;
+        }
+        write!(f, " {}", self.message)
+    }
+}
+
+/// Selects log lines by stream, level and time range, all optional.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LogFilter {
+    pub stream: Option<LogStream>,
+    pub level: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl LogFilter {
+    pub fn matches(&self, line: &LogLine) -> bool {
+        self.stream.is_none_or(|stream| stream == line.stream)
+            && self
+                .level
+                .as_ref()
+                .is_none_or(|level| line.level.as_ref() == Some(&level.to_lowercase()))
+            && self.since.is_none_or(|since| line.timestamp >= since)
+            && self.until.is_none_or(|until| line.timestamp <= until)
+    }
+}
+
+pub fn render_log_lines(lines: &[LogLine]) -> String {
+    lines
+        .iter()
+        .map(|line| line.to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn line(stream: LogStream, message: &str) -> LogLine {
+        LogLine::new(stream, 1, message)
+    }
+
+    #[test]
+    fn test_level_detection() {
+        assert_eq!(
+            line(LogStream::Stdout, r#"{"type":"log","level":"WARN"}"#).level,
+            Some("warn".into())
+        );
+        assert_eq!(line(LogStream::Stdout, r#"{"level":1}"#).level, None);
+        assert_eq!(line(LogStream::Stdout, "level: info").level, None);
+        assert_eq!(line(LogStream::Stdout, "hello\n").message, "hello");
+    }
+
+    #[test]
+    fn test_filter_stream() {
+        let filter = LogFilter {
+            stream: Some(LogStream::Stderr),
+            ..Default::default()
+        };
+        assert!(filter.matches(&line(LogStream::Stderr, "a")));
+        assert!(!filter.matches(&line(LogStream::Stdout, "a")));
+    }
+
+    #[test]
+    fn test_filter_level() {
+        let filter = LogFilter {
+            level: Some("ERROR".into()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&line(LogStream::Stdout, r#"{"level":"error"}"#)));
+        assert!(!filter.matches(&line(LogStream::Stdout, r#"{"level":"info"}"#)));
+        assert!(!filter.matches(&line(LogStream::Stdout, "error")));
+    }
+
+    #[test]
+    fn test_filter_time_range() {
+        let log_line = line(LogStream::Stdout, "a");
+        let at = log_line.timestamp;
+        let second = Duration::seconds(1);
+
+        let since = |since| LogFilter {
+            since: Some(since),
+            ..Default::default()
+        };
+        assert!(since(at).matches(&log_line));
+        assert!(!since(at + second).matches(&log_line));
+
+        let until = |until| LogFilter {
+            until: Some(until),
+            ..Default::default()
+        };
+        assert!(until(at).matches(&log_line));
+        assert!(!until(at - second).matches(&log_line));
+
+        assert!(LogFilter::default().matches(&log_line));
+    }
+
+    #[test]
+    fn test_parse_legacy_line() {
+        let stored = line(LogStream::Stderr, r#"{"level":"info"}"#);
+        assert_eq!(
+            LogLine::parse(2, &serde_json::to_string(&stored).unwrap()),
+            stored
+        );
+
+        let legacy = LogLine::parse(2, "plain text");
+        assert_eq!(legacy.stream, LogStream::Stdout);
+        assert_eq!(legacy.attempt, 2);
+        assert_eq!(legacy.level, None);
+        assert_eq!(legacy.message, "plain text");
+    }
+}
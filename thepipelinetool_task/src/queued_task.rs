@@ -8,4 +8,7 @@ pub struct QueuedTask {
     pub pipeline_name: String,
     pub scheduled_date_for_run: DateTime<Utc>,
     pub attempt: usize,
+    // trace context of the span that enqueued the task, not part of the redis queue member
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traceparent: Option<String>,
 }
@@ -0,0 +1,54 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{queued_task::QueuedTask, task_result::TaskResult};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallbackEvent {
+    OnSuccess,
+    OnFailure,
+    OnRetry,
+}
+
+impl Display for CallbackEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CallbackEvent::OnSuccess => "on_success",
+            CallbackEvent::OnFailure => "on_failure",
+            CallbackEvent::OnRetry => "on_retry",
+        })
+    }
+}
+
+/// A function the backend runs with `tpt run function` once an attempt of the task is handled.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TaskCallback {
+    pub function: String,
+
+    // passed instead of the `CallbackContext`, with `{{ field }}` replaced by its fields, e.g.
+    // `["bash", "-c", "echo {{ task_result.name }} failed"]` for `bash_operator`
+    #[serde(default)]
+    pub args: Option<Value>,
+}
+
+/// What callbacks without `args` receive.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CallbackContext {
+    pub event: CallbackEvent,
+    pub run_id: usize,
+    pub pipeline_name: String,
+    pub scheduled_date_for_run: DateTime<Utc>,
+    pub task_result: TaskResult,
+}
+
+/// Callbacks of a handled attempt, waiting for a worker to run them.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueuedCallback {
+    pub queued_task: QueuedTask,
+    pub event: CallbackEvent,
+    pub task_result: TaskResult,
+}
@@ -0,0 +1,230 @@
+use std::{any::type_name, collections::HashMap, env, path::PathBuf, process::Command};
+
+use anyhow::{Context, Result};
+
+use chrono::{DateTime, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use thepipelinetool_utils::{value_from_file, value_to_file};
+
+use crate::connection::Connection;
+
+pub const RUN_ID_ENV: &str = "TPT_RUN_ID";
+pub const TASK_ID_ENV: &str = "TPT_TASK_ID";
+pub const TASK_KEY_ENV: &str = "TPT_TASK_KEY";
+pub const TASK_NAME_ENV: &str = "TPT_TASK_NAME";
+pub const ATTEMPT_ENV: &str = "TPT_ATTEMPT";
+pub const MAX_ATTEMPTS_ENV: &str = "TPT_MAX_ATTEMPTS";
+pub const PIPELINE_NAME_ENV: &str = "TPT_PIPELINE_NAME";
+pub const SCHEDULED_DATE_ENV: &str = "TPT_SCHEDULED_DATE";
+pub const TRIGGER_PARAMS_ENV: &str = "TPT_TRIGGER_PARAMS";
+pub const VARIABLES_PATH_ENV: &str = "TPT_VARIABLES_PATH";
+pub const CONNECTIONS_ENV: &str = "TPT_CONNECTIONS";
+pub const CONNECTION_ENV_PREFIX: &str = "TPT_CONN_";
+// W3C trace context of the span executing the task
+pub const TRACEPARENT_ENV: &str = "TRACEPARENT";
+
+/// Snapshot of the variable store handed to a task as a JSON file at `TPT_VARIABLES_PATH`.
+///
+/// Scripts may edit the file directly, changes are written back to the backend once the
+/// task succeeds.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskVariables {
+    #[serde(default)]
+    pub pipeline: HashMap<String, Value>,
+    #[serde(default)]
+    pub run: HashMap<String, Value>,
+}
+
+/// Metadata about the run a task is executing in.
+///
+/// Populated by the executor through `TPT_*` environment variables, so it is available to task
+/// functions taking a `TaskContext` as their second argument and to bash/python scripts alike.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TaskContext {
+    pub run_id: usize,
+    pub task_id: usize,
+    pub task_key: String,
+    pub task_name: String,
+    pub attempt: usize,
+    pub max_attempts: usize,
+    pub pipeline_name: String,
+    pub scheduled_date: Option<DateTime<Utc>>,
+    pub trigger_params: Value,
+    pub variables_path: Option<PathBuf>,
+    pub connections: HashMap<String, Connection>,
+    pub traceparent: Option<String>,
+}
+
+impl TaskContext {
+    // missing variables are left at their defaults, e.g. when running a function by hand
+    pub fn from_env() -> Self {
+        let var = |name: &str| env::var(name).ok();
+        let number = |name: &str| var(name).and_then(|v| v.parse().ok()).unwrap_or_default();
+
+        Self {
+            run_id: number(RUN_ID_ENV),
+            task_id: number(TASK_ID_ENV),
+            task_key: var(TASK_KEY_ENV).unwrap_or_default(),
+            task_name: var(TASK_NAME_ENV).unwrap_or_default(),
+            attempt: number(ATTEMPT_ENV),
+            max_attempts: number(MAX_ATTEMPTS_ENV),
+            pipeline_name: var(PIPELINE_NAME_ENV).unwrap_or_default(),
+            scheduled_date: var(SCHEDULED_DATE_ENV)
+                .and_then(|v| DateTime::parse_from_rfc3339(&v).ok())
+                .map(|d| d.with_timezone(&Utc)),
+            trigger_params: var(TRIGGER_PARAMS_ENV)
+                .and_then(|v| serde_json::from_str(&v).ok())
+                .unwrap_or(Value::Null),
+            variables_path: var(VARIABLES_PATH_ENV).map(PathBuf::from),
+            connections: var(CONNECTIONS_ENV)
+                .and_then(|v| serde_json::from_str(&v).ok())
+                .unwrap_or_default(),
+            traceparent: var(TRACEPARENT_ENV),
+        }
+    }
+
+    pub fn set_env(&self, cmd: &mut Command) {
+        cmd.env(RUN_ID_ENV, self.run_id.to_string());
+        cmd.env(TASK_ID_ENV, self.task_id.to_string());
+        cmd.env(TASK_KEY_ENV, &self.task_key);
+        cmd.env(TASK_NAME_ENV, &self.task_name);
+        cmd.env(ATTEMPT_ENV, self.attempt.to_string());
+        cmd.env(MAX_ATTEMPTS_ENV, self.max_attempts.to_string());
+        cmd.env(PIPELINE_NAME_ENV, &self.pipeline_name);
+        if let Some(scheduled_date) = self.scheduled_date {
+            cmd.env(SCHEDULED_DATE_ENV, scheduled_date.to_rfc3339());
+        }
+        cmd.env(TRIGGER_PARAMS_ENV, self.trigger_params.to_string());
+        if let Some(variables_path) = &self.variables_path {
+            cmd.env(VARIABLES_PATH_ENV, variables_path);
+        }
+
+        // scripts get each connection as TPT_CONN_<ID>_<FIELD>, e.g. TPT_CONN_MY_DB_HOST
+        cmd.env(
+            CONNECTIONS_ENV,
+            serde_json::to_string(&self.connections).unwrap(),
+        );
+        for (id, connection) in &self.connections {
+            let prefix = format!(
+                "{CONNECTION_ENV_PREFIX}{}_",
+                id.to_uppercase()
+                    .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+            );
+            cmd.env(format!("{prefix}TYPE"), &connection.conn_type);
+            cmd.env(format!("{prefix}HOST"), &connection.host);
+            if let Some(port) = connection.port {
+                cmd.env(format!("{prefix}PORT"), port.to_string());
+            }
+            cmd.env(format!("{prefix}LOGIN"), &connection.login);
+            cmd.env(format!("{prefix}PASSWORD"), &connection.password);
+            cmd.env(format!("{prefix}EXTRAS"), connection.extras.to_string());
+        }
+
+        if let Some(traceparent) = &self.traceparent {
+            cmd.env(TRACEPARENT_ENV, traceparent);
+        }
+
+        // kept for scripts using the old variable
+        cmd.env("run_id", self.run_id.to_string());
+    }
+
+    /// Gets a connection listed in the task's connections.
+    pub fn get_connection(&self, id: &str) -> Option<&Connection> {
+        self.connections.get(id)
+    }
+
+    fn read_variables(&self) -> TaskVariables {
+        self.variables_path
+            .as_ref()
+            .and_then(|path| value_from_file(path).ok())
+            .unwrap_or_default()
+    }
+
+    fn update_variables(&self, update: impl FnOnce(&mut TaskVariables)) {
+        let Some(variables_path) = &self.variables_path else {
+            eprintln!("variables are only stored when running inside a pipeline");
+            return;
+        };
+        let mut variables = self.read_variables();
+        update(&mut variables);
+        value_to_file(&variables, variables_path);
+    }
+
+    /// Gets a variable shared by all runs of this pipeline, e.g. a watermark. Fails if the
+    /// stored value is not a `T`.
+    pub fn get_variable<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        get_typed_variable(key, self.read_variables().pipeline.remove(key))
+    }
+
+    pub fn set_variable<T: Serialize>(&self, key: &str, value: T) {
+        let value = serde_json::to_value(value).unwrap();
+        self.update_variables(|variables| {
+            variables.pipeline.insert(key.to_string(), value);
+        });
+    }
+
+    pub fn delete_variable(&self, key: &str) {
+        self.update_variables(|variables| {
+            variables.pipeline.remove(key);
+        });
+    }
+
+    /// Gets a variable shared by the tasks of this run only.
+    pub fn get_run_variable<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        get_typed_variable(key, self.read_variables().run.remove(key))
+    }
+
+    pub fn set_run_variable<T: Serialize>(&self, key: &str, value: T) {
+        let value = serde_json::to_value(value).unwrap();
+        self.update_variables(|variables| {
+            variables.run.insert(key.to_string(), value);
+        });
+    }
+
+    pub fn delete_run_variable(&self, key: &str) {
+        self.update_variables(|variables| {
+            variables.run.remove(key);
+        });
+    }
+
+    /// Writes a structured log line, e.g. `ctx.log("info", "fetched rows", json!({"rows": 10}))`.
+    pub fn log<F: Serialize>(&self, level: &str, message: &str, fields: F) {
+        eprintln!(
+            "{}",
+            json!({
+                "type": "log",
+                "level": level,
+                "message": message,
+                "fields": fields,
+                "run_id": self.run_id,
+                "task_id": self.task_id,
+                "attempt": self.attempt,
+            })
+        );
+    }
+
+    /// Writes a metric line, e.g. `ctx.metric("rows_processed", 10.0)`.
+    pub fn metric(&self, name: &str, value: f64) {
+        eprintln!(
+            "{}",
+            json!({
+                "type": "metric",
+                "name": name,
+                "value": value,
+                "run_id": self.run_id,
+                "task_id": self.task_id,
+                "attempt": self.attempt,
+            })
+        );
+    }
+}
+
+fn get_typed_variable<T: DeserializeOwned>(key: &str, value: Option<Value>) -> Result<Option<T>> {
+    value
+        .map(|value| {
+            serde_json::from_value(value)
+                .with_context(|| format!("variable '{key}' is not a {}", type_name::<T>()))
+        })
+        .transpose()
+}
@@ -0,0 +1,56 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Structured error reported by a task function that returned `Err`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskError {
+    pub message: String,
+    pub kind: String,
+    #[serde(default)]
+    pub data: Option<Value>,
+}
+
+/// Error returned from task functions to fail with a custom kind and optional data, e.g.
+/// `Err(TaskFailure::new("not_found", "user 42 does not exist").with_data(json!({"id": 42})))`.
+#[derive(Debug)]
+pub struct TaskFailure(TaskError);
+
+impl TaskFailure {
+    pub fn new(kind: &str, message: impl Display) -> Self {
+        Self(TaskError {
+            message: message.to_string(),
+            kind: kind.to_string(),
+            data: None,
+        })
+    }
+
+    pub fn with_data<D: Serialize>(mut self, data: D) -> Self {
+        self.0.data = Some(serde_json::to_value(data).unwrap());
+        self
+    }
+}
+
+/// Error types task functions may return in `Result<T, E>`: `TaskFailure` and anything
+/// `Display`, e.g. `anyhow::Error`, `std::io::Error` or `String`.
+pub trait IntoTaskError {
+    fn into_task_error(self) -> TaskError;
+}
+
+impl IntoTaskError for TaskFailure {
+    fn into_task_error(self) -> TaskError {
+        self.0
+    }
+}
+
+impl<E: Display> IntoTaskError for E {
+    fn into_task_error(self) -> TaskError {
+        TaskError {
+            // the alternate form includes the context of anyhow errors
+            message: format!("{self:#}"),
+            kind: "error".into(),
+            data: None,
+        }
+    }
+}
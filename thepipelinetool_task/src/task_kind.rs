@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum TaskKind {
+    #[default]
+    Normal,
+
+    // creates a resource for the tasks downstream of it, e.g. a temporary table
+    Setup,
+    // runs once all tasks between it and its upstream setups are done, whatever their status
+    Teardown,
+}
@@ -2,9 +2,13 @@ use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 
-use crate::trigger_rule::TriggerRule;
+use crate::{
+    task_callback::{CallbackEvent, TaskCallback},
+    task_kind::TaskKind,
+    trigger_rule::TriggerRule,
+};
 
-#[derive(Clone, Copy, Serialize, Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct TaskOptions {
     #[serde(default)]
     pub max_attempts: usize,
@@ -20,6 +24,24 @@ pub struct TaskOptions {
 
     #[serde(default)]
     pub trigger_rule: TriggerRule,
+
+    // the trigger rule of teardowns is ignored
+    #[serde(default)]
+    pub kind: TaskKind,
+
+    // attempts running longer are reported as SLA misses, unlike `timeout` they are not stopped
+    #[serde(default)]
+    pub expected_duration: Option<Duration>,
+
+    #[serde(default)]
+    pub on_success: Vec<TaskCallback>,
+
+    // only once no attempts are left
+    #[serde(default)]
+    pub on_failure: Vec<TaskCallback>,
+
+    #[serde(default)]
+    pub on_retry: Vec<TaskCallback>,
 }
 
 impl Default for TaskOptions {
@@ -30,6 +52,21 @@ impl Default for TaskOptions {
             timeout: None,
             max_attempts: 1,
             trigger_rule: TriggerRule::AllDone,
+            kind: TaskKind::Normal,
+            expected_duration: None,
+            on_success: vec![],
+            on_failure: vec![],
+            on_retry: vec![],
+        }
+    }
+}
+
+impl TaskOptions {
+    pub fn get_callbacks(&self, event: CallbackEvent) -> &[TaskCallback] {
+        match event {
+            CallbackEvent::OnSuccess => &self.on_success,
+            CallbackEvent::OnFailure => &self.on_failure,
+            CallbackEvent::OnRetry => &self.on_retry,
         }
     }
 }
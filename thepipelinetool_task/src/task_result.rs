@@ -2,6 +2,8 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::task_error::TaskError;
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct TaskResult {
     pub task_id: usize,
@@ -20,6 +22,11 @@ pub struct TaskResult {
     pub is_branch: bool,
     pub is_sensor: bool,
     pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub error: Option<TaskError>,
+    // set when the result was offloaded to a result store, `result` is null in that case
+    #[serde(default)]
+    pub result_ref: Option<String>,
 }
 
 impl TaskResult {
@@ -58,6 +65,8 @@ impl TaskResult {
             is_branch,
             is_sensor,
             exit_code: None,
+            error: None,
+            result_ref: None,
         }
     }
 
@@ -77,6 +86,18 @@ impl TaskResult {
             "result: {}",
             serde_json::to_string_pretty(&self.result).unwrap()
         );
+        if let Some(result_ref) = &self.result_ref {
+            println!("result_ref:\t{}", result_ref);
+        }
+        if let Some(error) = &self.error {
+            println!("error:\t\t{}: {}", error.kind, error.message);
+            if let Some(data) = &error.data {
+                println!(
+                    "error_data: {}",
+                    serde_json::to_string_pretty(data).unwrap()
+                );
+            }
+        }
         println!("------Log------\n{}\n------------------", log);
         println!("success:\t{}", self.success);
         println!(
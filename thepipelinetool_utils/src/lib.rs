@@ -1,6 +1,7 @@
 use std::{
     cmp::max,
-    fs::File,
+    env,
+    fs::{self, File},
     io::{BufRead, BufReader, Error, Read, Write},
     path::Path,
     process::{self, Command, ExitStatus, Stdio},
@@ -15,6 +16,9 @@ use serde_json::{json, Value};
 
 pub const UPSTREAM_TASK_ID_KEY: &str = "upstream_task_id";
 pub const UPSTREAM_TASK_RESULT_KEY: &str = "key";
+pub const TASK_ERROR_KEY: &str = "_task_error";
+// file the executor reads the task result from, scripts run by operators may write it directly
+pub const RESULT_PATH_ENV: &str = "TPT_RESULT_PATH";
 
 pub fn function_name_as_string<T>(_: T) -> String {
     let name = std::any::type_name::<T>();
@@ -50,7 +54,7 @@ pub fn execute_function_using_json_files(
     let task_args = value_from_file(in_file).unwrap(); // TODO handle error
     let task_result = (task_function)(task_args);
     value_to_file(&task_result, out_file);
-    process::exit(0);
+    exit_with_task_result(&task_result);
 }
 
 pub fn execute_function_using_json_str_args(
@@ -60,7 +64,22 @@ pub fn execute_function_using_json_str_args(
     let task_args = serde_json::from_str(task_args_str).unwrap();
     let task_result = (task_function)(task_args);
     println!("{}", serde_json::to_string(&task_result).unwrap());
-    process::exit(0);
+    exit_with_task_result(&task_result);
+}
+
+/// Reads a result a script wrote to `TPT_RESULT_PATH`, if any. Content that is not JSON is
+/// taken as a string, e.g. from `date > $TPT_RESULT_PATH`.
+pub fn read_script_result() -> Option<Value> {
+    let result = fs::read_to_string(env::var(RESULT_PATH_ENV).ok()?).ok()?;
+    Some(serde_json::from_str(&result).unwrap_or_else(|_| json!(result.trim_end())))
+}
+
+// fallible task functions return their error under TASK_ERROR_KEY
+fn exit_with_task_result(task_result: &Value) -> ! {
+    process::exit(match task_result.get(TASK_ERROR_KEY) {
+        Some(_) => 1,
+        None => 0,
+    })
 }
 
 pub fn collector(args: Value) -> Value {